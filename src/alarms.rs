@@ -0,0 +1,246 @@
+//! Alarm rules that turn field values and events into status messages.
+//!
+//! A threshold rule watches a telemetry value or parameter and sends a `WarningStatus` or
+//! `ErrorStatus` when it crosses `warn_*` or `error_*`, and an `InfoStatus` once it is back in
+//! range by more than `hysteresis`. An event rule sends a status message every time its event
+//! happens. Message texts are templates with `{rule}`, `{field}`, `{value}` and `{level}`.
+
+use crate::config::config_representation::EmulatorData;
+use crate::config::serde_deserializer::value_to_f64;
+use crate::message_handling::{
+    build_status_message, truncate_status_text, Event, StatusMessageKind,
+};
+use crate::value_format::value_text;
+use liquidcan::payloads::CanDataValue;
+use liquidcan::CanMessage;
+use serde::Deserialize;
+
+const DEFAULT_MESSAGE: &str = "{rule}: {field} is {value}";
+const DEFAULT_CLEAR_MESSAGE: &str = "{rule} cleared: {field} is {value}";
+const PLACEHOLDERS: [&str; 4] = ["rule", "field", "value", "level"];
+
+/// One rule of a node's `[Alarms]` table.
+#[derive(Deserialize, Debug)]
+pub struct Alarm {
+    #[serde(skip)]
+    pub name: String,
+    /// Telemetry value or parameter a threshold rule watches.
+    pub field: Option<String>,
+    pub warn_above: Option<f64>,
+    pub warn_below: Option<f64>,
+    pub error_above: Option<f64>,
+    pub error_below: Option<f64>,
+    /// How far back past a threshold the value has to move before the alarm level drops.
+    #[serde(default)]
+    pub hysteresis: f64,
+    /// Event an event rule fires on.
+    pub on: Option<Event>,
+    /// Level of an event rule's message.
+    #[serde(default = "default_level")]
+    pub level: StatusMessageKind,
+    /// Text sent when the alarm is raised or the event happens.
+    pub message: Option<String>,
+    /// Text sent when a threshold rule's value is back in range.
+    pub clear_message: Option<String>,
+    /// The level a threshold rule currently reports; `None` while the value is in range.
+    #[serde(skip)]
+    active: Option<StatusMessageKind>,
+}
+
+fn default_level() -> StatusMessageKind {
+    StatusMessageKind::Info
+}
+
+impl Alarm {
+    /// Keeps the level `running` reports, if it watches the same field. Used when a reloaded
+    /// config replaces the running rule, so a raised alarm is not raised again.
+    pub(crate) fn keep_level(&mut self, running: &Alarm) {
+        if self.field == running.field {
+            self.active = running.active;
+        }
+    }
+
+    pub fn has_thresholds(&self) -> bool {
+        self.warn_above.is_some()
+            || self.warn_below.is_some()
+            || self.error_above.is_some()
+            || self.error_below.is_some()
+    }
+
+    /// The level `value` is in when every threshold is moved `slack` towards the safe range.
+    fn level_at(&self, value: f64, slack: f64) -> Option<StatusMessageKind> {
+        let above = |threshold: Option<f64>| threshold.is_some_and(|t| value > t - slack);
+        let below = |threshold: Option<f64>| threshold.is_some_and(|t| value < t + slack);
+        if above(self.error_above) || below(self.error_below) {
+            Some(StatusMessageKind::Error)
+        } else if above(self.warn_above) || below(self.warn_below) {
+            Some(StatusMessageKind::Warning)
+        } else {
+            None
+        }
+    }
+
+    /// The level after a change to `value`. A level is entered at its threshold but only left
+    /// once the value is `hysteresis` past it.
+    fn next_level(&self, value: f64) -> Option<StatusMessageKind> {
+        let held = self.active.min(self.level_at(value, self.hysteresis));
+        self.level_at(value, 0.0).max(held)
+    }
+}
+
+/// Fills in `{name}` placeholders from `vars`. Fails on unknown placeholders and unbalanced
+/// braces; `{{` and `}}` stand for literal braces.
+pub fn render_template(template: &str, vars: &[(&str, &str)]) -> Result<String, String> {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(idx) = rest.find(['{', '}']) {
+        out.push_str(&rest[..idx]);
+        let brace = &rest[idx..idx + 1];
+        rest = &rest[idx + 1..];
+        if rest.starts_with(brace) {
+            out.push_str(brace);
+            rest = &rest[1..];
+            continue;
+        }
+        if brace == "}" {
+            return Err(format!("unmatched `}}` in \"{template}\""));
+        }
+        let end = rest
+            .find('}')
+            .ok_or_else(|| format!("unclosed `{{` in \"{template}\""))?;
+        let name = &rest[..end];
+        let value = vars
+            .iter()
+            .find(|(var, _)| *var == name)
+            .map(|(_, value)| *value)
+            .ok_or_else(|| format!("unknown placeholder {{{name}}} in \"{template}\""))?;
+        out.push_str(value);
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Checks a message template without sending it.
+pub fn check_template(template: &str) -> Result<(), String> {
+    let vars: Vec<(&str, &str)> = PLACEHOLDERS.iter().map(|name| (*name, "")).collect();
+    render_template(template, &vars).map(|_| ())
+}
+
+fn level_text(level: StatusMessageKind) -> &'static str {
+    match level {
+        StatusMessageKind::Info => "info",
+        StatusMessageKind::Warning => "warning",
+        StatusMessageKind::Error => "error",
+    }
+}
+
+fn field_value(emulator_data: &EmulatorData, name: &str) -> Option<CanDataValue> {
+    let telemetry = emulator_data
+        .telemetry_values
+        .iter()
+        .flatten()
+        .find(|tel| tel.name == name)
+        .map(|tel| tel.value.clone());
+    telemetry.or_else(|| {
+        emulator_data
+            .parameters
+            .iter()
+            .flatten()
+            .find(|param| param.name == name)
+            .map(|param| param.value.clone())
+    })
+}
+
+/// Renders the rule's text and builds the status message, cut to what fits into one frame.
+fn status_message(
+    alarm: &Alarm,
+    template: Option<&String>,
+    default: &str,
+    level: StatusMessageKind,
+    field: &str,
+    value: &CanDataValue,
+) -> CanMessage {
+    let value = value_text(value);
+    let vars = [
+        ("rule", alarm.name.as_str()),
+        ("field", field),
+        ("value", value.as_str()),
+        ("level", level_text(level)),
+    ];
+    let template = template.map_or(default, String::as_str);
+    let text = render_template(template, &vars).unwrap_or_else(|err| {
+        eprintln!("Alarm {}: {err}", alarm.name);
+        alarm.name.clone()
+    });
+    build_status_message(level, truncate_status_text(&text))
+}
+
+/// Re-evaluates every threshold rule and returns the status messages for the levels that changed.
+pub fn check_thresholds(emulator_data: &mut EmulatorData) -> Vec<CanMessage> {
+    let Some(mut alarms) = emulator_data.alarms.take() else {
+        return Vec::new();
+    };
+    let mut messages = Vec::new();
+    for alarm in &mut alarms {
+        let Some(field) = alarm.field.clone() else {
+            continue;
+        };
+        let Some(value) = field_value(emulator_data, &field) else {
+            continue;
+        };
+        let Some(number) = value_to_f64(&value).filter(|number| !number.is_nan()) else {
+            continue;
+        };
+        let level = alarm.next_level(number);
+        if level == alarm.active {
+            continue;
+        }
+        alarm.active = level;
+        let message = match level {
+            Some(level) => status_message(
+                alarm,
+                alarm.message.as_ref(),
+                DEFAULT_MESSAGE,
+                level,
+                &field,
+                &value,
+            ),
+            None => status_message(
+                alarm,
+                alarm.clear_message.as_ref(),
+                DEFAULT_CLEAR_MESSAGE,
+                StatusMessageKind::Info,
+                &field,
+                &value,
+            ),
+        };
+        messages.push(message);
+    }
+    emulator_data.alarms = Some(alarms);
+    messages
+}
+
+/// The status messages of the event rules that fire on `event`.
+pub fn on_event(emulator_data: &EmulatorData, event: &Event) -> Vec<CanMessage> {
+    let Event::ParameterSet(name) = event;
+    let Some(value) = field_value(emulator_data, name) else {
+        return Vec::new();
+    };
+    emulator_data
+        .alarms
+        .iter()
+        .flatten()
+        .filter(|alarm| alarm.on.as_ref() == Some(event))
+        .map(|alarm| {
+            status_message(
+                alarm,
+                alarm.message.as_ref(),
+                DEFAULT_MESSAGE,
+                alarm.level,
+                name,
+                &value,
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,147 @@
+//! Reading and writing frames in the `candump -L` log format, e.g.
+//! `(1700000000.123456) vcan0 123##1DEADBEEF`.
+
+pub use crate::can_manager::errors::CandumpError;
+use socketcan::{CanAnyFrame, CanFdFrame, EmbeddedFrame, ExtendedId, Id, StandardId};
+use std::fmt::Write;
+use std::time::Duration;
+
+const FD_FLAG_BRS: u8 = 0x1;
+const FD_FLAG_ESI: u8 = 0x2;
+
+/// One line of a candump log.
+pub struct LogEntry {
+    /// Time since the Unix epoch.
+    pub timestamp: Duration,
+    pub interface: String,
+    /// `None` for classic CAN and remote frames, which the emulator cannot send.
+    pub frame: Option<CanFdFrame>,
+}
+
+fn format_id(id: Id) -> String {
+    match id {
+        Id::Standard(id) => format!("{:03X}", id.as_raw()),
+        Id::Extended(id) => format!("{:08X}", id.as_raw()),
+    }
+}
+
+fn format_data(data: &[u8]) -> String {
+    data.iter().fold(String::new(), |mut out, byte| {
+        let _ = write!(out, "{byte:02X}");
+        out
+    })
+}
+
+/// Formats a frame as a candump log line without the trailing newline.
+/// Returns `None` for error frames, which candump only logs on request.
+pub fn format_line(timestamp: Duration, interface: &str, frame: &CanAnyFrame) -> Option<String> {
+    let body = match frame {
+        CanAnyFrame::Fd(frame) => {
+            let mut flags = 0;
+            if frame.is_brs() {
+                flags |= FD_FLAG_BRS;
+            }
+            if frame.is_esi() {
+                flags |= FD_FLAG_ESI;
+            }
+            format!(
+                "{}##{flags:X}{}",
+                format_id(frame.id()),
+                format_data(frame.data())
+            )
+        }
+        CanAnyFrame::Normal(frame) => {
+            format!("{}#{}", format_id(frame.id()), format_data(frame.data()))
+        }
+        CanAnyFrame::Remote(frame) => format!("{}#R", format_id(frame.id())),
+        CanAnyFrame::Error(_) => return None,
+    };
+    Some(format!(
+        "({}.{:06}) {interface} {body}",
+        timestamp.as_secs(),
+        timestamp.subsec_micros()
+    ))
+}
+
+fn parse_hex_bytes(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(hex.get(idx..idx + 2)?, 16).ok())
+        .collect()
+}
+
+fn parse_id(id: &str) -> Option<Id> {
+    let raw = u32::from_str_radix(id, 16).ok()?;
+    if id.len() == 3 {
+        Some(Id::Standard(StandardId::new(u16::try_from(raw).ok()?)?))
+    } else {
+        Some(Id::Extended(ExtendedId::new(raw)?))
+    }
+}
+
+fn parse_timestamp(timestamp: &str) -> Option<Duration> {
+    let timestamp = timestamp.strip_prefix('(')?.strip_suffix(')')?;
+    let (secs, fraction) = timestamp.split_once('.')?;
+    let micros = format!("{fraction:0<6}");
+    Some(
+        Duration::from_secs(secs.parse().ok()?)
+            + Duration::from_micros(micros.get(..6)?.parse().ok()?),
+    )
+}
+
+/// Parses one candump log line, numbered from 1 for error messages.
+pub fn parse_line(line_number: usize, line: &str) -> Result<LogEntry, CandumpError> {
+    let error = |reason: &str| CandumpError::InvalidLine {
+        line: line_number,
+        reason: reason.to_string(),
+    };
+    let mut parts = line.split_whitespace();
+    let (Some(timestamp), Some(interface), Some(body)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(error("expected `(timestamp) interface frame`"));
+    };
+    let timestamp = parse_timestamp(timestamp).ok_or_else(|| error("invalid timestamp"))?;
+
+    let (id, data) = body
+        .split_once('#')
+        .ok_or_else(|| error("missing `#` between ID and data"))?;
+    let id = parse_id(id).ok_or_else(|| error("invalid CAN ID"))?;
+    let frame = match data.strip_prefix('#') {
+        Some(fd_data) => {
+            let flags = fd_data
+                .get(..1)
+                .and_then(|flags| u8::from_str_radix(flags, 16).ok())
+                .ok_or_else(|| error("missing CAN FD flags"))?;
+            let data = parse_hex_bytes(&fd_data[1..]).ok_or_else(|| error("invalid data"))?;
+            let mut frame =
+                CanFdFrame::new(id, &data).ok_or_else(|| error("invalid CAN FD data length"))?;
+            frame.set_brs(flags & FD_FLAG_BRS != 0);
+            frame.set_esi(flags & FD_FLAG_ESI != 0);
+            Some(frame)
+        }
+        None if data.starts_with('R') => None,
+        None => {
+            parse_hex_bytes(data).ok_or_else(|| error("invalid data"))?;
+            None
+        }
+    };
+
+    Ok(LogEntry {
+        timestamp,
+        interface: interface.to_string(),
+        frame,
+    })
+}
+
+/// Parses a whole log, skipping blank lines.
+pub fn parse_log(contents: &str) -> Result<Vec<LogEntry>, CandumpError> {
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(idx, line)| parse_line(idx + 1, line))
+        .collect()
+}
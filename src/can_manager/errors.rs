@@ -11,3 +11,12 @@ pub enum SendFrameError {
     #[error(transparent)]
     Io(#[from] std::io::Error),
 }
+
+#[derive(Debug, Error)]
+pub enum CandumpError {
+    #[error("line {line}: {reason}")]
+    InvalidLine { line: usize, reason: String },
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
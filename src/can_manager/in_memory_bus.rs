@@ -0,0 +1,111 @@
+use crate::can_manager::transport::{receiver_filters, timed_out, CanTransport};
+use socketcan::{CanAnyFrame, CanFdFrame, EmbeddedFrame, Id};
+use std::collections::VecDeque;
+use std::io;
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+struct Mailbox {
+    frames: Mutex<VecDeque<CanAnyFrame>>,
+    available: Condvar,
+    /// Acceptance filters as `(id, mask)` pairs; `None` accepts every frame.
+    filters: Mutex<Option<Vec<(u32, u32)>>>,
+}
+
+impl Mailbox {
+    fn accepts(&self, frame: &CanFdFrame) -> bool {
+        let filters = self.filters.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(filters) = filters.as_ref() else {
+            return true;
+        };
+        let raw_id = match frame.id() {
+            Id::Standard(id) => u32::from(id.as_raw()),
+            Id::Extended(id) => id.as_raw(),
+        };
+        filters.iter().any(|&(id, mask)| raw_id & mask == id & mask)
+    }
+}
+
+/// An in-process CAN bus. Every frame sent by one attached transport is delivered to all other
+/// transports on the same bus, like on a real bus where a node does not receive its own frames.
+#[derive(Clone, Default)]
+pub struct InMemoryBus {
+    mailboxes: Arc<Mutex<Vec<Weak<Mailbox>>>>,
+}
+
+impl InMemoryBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn attach(&self) -> InMemoryTransport {
+        let mailbox = Arc::new(Mailbox::default());
+        self.mailboxes
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(Arc::downgrade(&mailbox));
+        InMemoryTransport {
+            bus: self.clone(),
+            mailbox,
+        }
+    }
+}
+
+pub struct InMemoryTransport {
+    bus: InMemoryBus,
+    mailbox: Arc<Mailbox>,
+}
+
+impl CanTransport for InMemoryTransport {
+    fn send_frame(&mut self, frame: &CanFdFrame) -> io::Result<()> {
+        let mut mailboxes = self.bus.mailboxes.lock().unwrap_or_else(|e| e.into_inner());
+        // Transports that were dropped leave a dead entry behind; clean those up on the way.
+        mailboxes.retain(|mailbox| mailbox.strong_count() > 0);
+        for mailbox in mailboxes.iter().filter_map(Weak::upgrade) {
+            if Arc::ptr_eq(&mailbox, &self.mailbox) || !mailbox.accepts(frame) {
+                continue;
+            }
+            mailbox
+                .frames
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push_back(CanAnyFrame::Fd(*frame));
+            mailbox.available.notify_all();
+        }
+        Ok(())
+    }
+
+    fn read_frame(&mut self, timeout: Duration) -> io::Result<CanAnyFrame> {
+        let deadline = Instant::now() + timeout;
+        let mut frames = self
+            .mailbox
+            .frames
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        loop {
+            if let Some(frame) = frames.pop_front() {
+                return Ok(frame);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(timed_out());
+            }
+            frames = self
+                .mailbox
+                .available
+                .wait_timeout(frames, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    fn set_receiver_filter(&mut self, receiver_ids: &[u8]) -> io::Result<()> {
+        *self
+            .mailbox
+            .filters
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = Some(receiver_filters(receiver_ids));
+        Ok(())
+    }
+}
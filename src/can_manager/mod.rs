@@ -1,43 +1,32 @@
 use crate::can_manager;
+use crate::can_manager::transport::CanTransport;
 use liquidcan::raw_can_message::CanMessagePriority;
 use liquidcan::CanMessageId;
-use socketcan::CanFdSocket;
 
+pub mod candump;
 pub mod errors;
+pub mod in_memory_bus;
+pub mod recording;
 pub mod socket_manager;
+pub mod transport;
 
-fn make_message_id(receiver_id: u8, sender_id: u8) -> CanMessageId {
+pub fn make_message_id(receiver_id: u8, sender_id: u8) -> CanMessageId {
     CanMessageId::new()
         .with_receiver_id(receiver_id)
         .with_sender_id(sender_id)
         .with_priority(CanMessagePriority::Low)
 }
 
-fn should_also_notify_server(msg: &liquidcan::CanMessage) -> bool {
-    matches!(
-        msg,
-        liquidcan::CanMessage::ParameterSetConfirmation { .. }
-            | liquidcan::CanMessage::ParameterSetLockConfirmation { .. }
-    )
-}
-
-pub fn send_messages(
-    socket: &mut CanFdSocket,
+/// Sends each message to the node it is paired with.
+pub fn send_messages<T: CanTransport + ?Sized>(
+    transport: &mut T,
     sender_id: u8,
-    receiver_id: u8,
-    messages: Vec<liquidcan::CanMessage>,
+    messages: Vec<(u8, liquidcan::CanMessage)>,
 ) {
-    for msg in messages {
+    for (receiver_id, msg) in messages {
         let id = make_message_id(receiver_id, sender_id);
-        if let Err(err) = can_manager::socket_manager::send_frame(socket, id, msg.clone()) {
-            eprintln!("Error sending CAN FD frame: {err:?}");
-        }
-        if receiver_id != 0 && should_also_notify_server(&msg) {
-            let server_id = 1;
-            let server_msg_id = make_message_id(server_id, sender_id);
-            if let Err(err) = can_manager::socket_manager::send_frame(socket, server_msg_id, msg) {
-                eprintln!("Error sending CAN FD frame to server: {err:?}");
-            }
+        if let Err(err) = can_manager::socket_manager::send_frame(transport, id, msg) {
+            eprintln!("Error sending CAN FD frame to node {receiver_id}: {err:?}");
         }
     }
 }
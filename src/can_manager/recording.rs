@@ -0,0 +1,96 @@
+use crate::can_manager::candump;
+use crate::can_manager::transport::CanTransport;
+use liquidcan::CanMessageId;
+use socketcan::{CanAnyFrame, CanFdFrame, EmbeddedFrame, Id};
+use std::fs::File;
+use std::io::{self, LineWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A candump log shared by every transport that records into it.
+#[derive(Clone)]
+pub struct Recorder {
+    writer: Arc<Mutex<Box<dyn Write + Send>>>,
+    /// Senders whose frames are already logged when they are sent. Frames from these nodes that
+    /// another local transport receives are not logged a second time.
+    local_senders: Arc<Vec<u8>>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path, local_senders: Vec<u8>) -> io::Result<Self> {
+        Ok(Self::to_writer(
+            LineWriter::new(File::create(path)?),
+            local_senders,
+        ))
+    }
+
+    pub fn to_writer(writer: impl Write + Send + 'static, local_senders: Vec<u8>) -> Self {
+        Self {
+            writer: Arc::new(Mutex::new(Box::new(writer))),
+            local_senders: Arc::new(local_senders),
+        }
+    }
+
+    fn record(&self, interface: &str, frame: &CanAnyFrame) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or(Duration::ZERO);
+        let Some(line) = candump::format_line(timestamp, interface, frame) else {
+            return;
+        };
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(err) = writeln!(writer, "{line}") {
+            eprintln!("Error writing CAN log: {err:?}");
+        }
+    }
+
+    fn is_local(&self, frame: &CanAnyFrame) -> bool {
+        let CanAnyFrame::Fd(frame) = frame else {
+            return false;
+        };
+        let Id::Standard(raw_id) = frame.id() else {
+            return false;
+        };
+        let id = CanMessageId::from_bytes(raw_id.as_raw().to_le_bytes());
+        self.local_senders.contains(&id.sender_id())
+    }
+}
+
+/// Wraps a transport and logs every frame sent and received through it.
+pub struct RecordingTransport<T> {
+    inner: T,
+    recorder: Recorder,
+    interface: String,
+}
+
+impl<T: CanTransport> RecordingTransport<T> {
+    pub fn new(inner: T, recorder: Recorder, interface: &str) -> Self {
+        Self {
+            inner,
+            recorder,
+            interface: interface.to_string(),
+        }
+    }
+}
+
+impl<T: CanTransport> CanTransport for RecordingTransport<T> {
+    fn send_frame(&mut self, frame: &CanFdFrame) -> io::Result<()> {
+        self.inner.send_frame(frame)?;
+        self.recorder
+            .record(&self.interface, &CanAnyFrame::Fd(*frame));
+        Ok(())
+    }
+
+    fn read_frame(&mut self, timeout: Duration) -> io::Result<CanAnyFrame> {
+        let frame = self.inner.read_frame(timeout)?;
+        if !self.recorder.is_local(&frame) {
+            self.recorder.record(&self.interface, &frame);
+        }
+        Ok(frame)
+    }
+
+    fn set_receiver_filter(&mut self, receiver_ids: &[u8]) -> io::Result<()> {
+        self.inner.set_receiver_filter(receiver_ids)
+    }
+}
@@ -1,41 +1,85 @@
 use crate::can_manager::errors::SendFrameError;
+use crate::can_manager::transport::{receiver_filters, CanTransport};
 use liquidcan::{CanMessage, CanMessageId};
-use socketcan::{CanAnyFrame, CanFdSocket, EmbeddedFrame, Socket};
+use socketcan::{
+    CanAnyFrame, CanFdFrame, CanFdSocket, CanFilter, EmbeddedFrame, Socket, SocketOptions,
+};
+use std::io;
+use std::time::Duration;
 
-#[allow(dead_code)]
-pub fn open_socket(interface: &str) -> Result<CanFdSocket, std::io::Error> {
-    CanFdSocket::open(interface)
+/// SocketCAN backend of [`CanTransport`].
+pub struct SocketCanTransport {
+    socket: CanFdSocket,
+    read_timeout: Option<Duration>,
 }
 
-#[allow(dead_code)]
-pub fn read_frame(socket: &mut CanFdSocket) -> Result<CanAnyFrame, std::io::Error> {
-    socket.read_frame()
+impl SocketCanTransport {
+    pub fn open(interface: &str) -> Result<Self, io::Error> {
+        Ok(Self {
+            socket: CanFdSocket::open(interface)?,
+            read_timeout: None,
+        })
+    }
+}
+
+impl CanTransport for SocketCanTransport {
+    fn send_frame(&mut self, frame: &CanFdFrame) -> io::Result<()> {
+        self.socket.write_frame_insist(frame)
+    }
+
+    fn read_frame(&mut self, timeout: Duration) -> io::Result<CanAnyFrame> {
+        if self.read_timeout != Some(timeout) {
+            self.socket.set_read_timeout(Some(timeout))?;
+            self.read_timeout = Some(timeout);
+        }
+        self.socket.read_frame()
+    }
+
+    fn set_receiver_filter(&mut self, receiver_ids: &[u8]) -> io::Result<()> {
+        let filters: Vec<CanFilter> = receiver_filters(receiver_ids)
+            .into_iter()
+            .map(|(id, mask)| CanFilter::new(id, mask))
+            .collect();
+        self.socket.set_filters(&filters)
+    }
+}
+
+pub fn open_socket(interface: &str) -> Result<SocketCanTransport, io::Error> {
+    SocketCanTransport::open(interface)
+}
+
+pub fn read_frame<T: CanTransport + ?Sized>(
+    transport: &mut T,
+    timeout: Duration,
+) -> Result<CanAnyFrame, io::Error> {
+    transport.read_frame(timeout)
 }
 
-#[allow(dead_code)]
-pub fn send_frame(
-    socket: &mut CanFdSocket,
+pub fn send_frame<T: CanTransport + ?Sized>(
+    transport: &mut T,
     can_message_id: CanMessageId,
     can_message: CanMessage,
 ) -> Result<(), SendFrameError> {
-    let raw_id: u16 = can_message_id.into();
-    let id = socketcan::StandardId::new(raw_id).ok_or(SendFrameError::InvalidId { raw_id })?;
-    let frame: socketcan::CanFdFrame = can_message.into();
-    let data = frame.data();
-    let frame = socketcan::CanFdFrame::new(id, data)
-        .ok_or(SendFrameError::InvalidFrameLength { len: data.len() })?;
-
-    socket.write_frame_insist(&frame)?;
+    let frame = encode_frame(can_message_id, can_message)?;
+    transport.send_frame(&frame)?;
     Ok(())
 }
 
-#[allow(dead_code)]
-fn send_raw_frame(
-    socket: &mut CanFdSocket,
-    frame: socketcan::CanFdFrame,
-) -> Result<(), std::io::Error> {
-    match socket.write_frame_insist(&frame) {
-        Err(reason) => Err(reason),
-        Ok(_) => Ok(()),
-    }
+/// Builds the CAN FD frame that carries `can_message` under `can_message_id`.
+pub fn encode_frame(
+    can_message_id: CanMessageId,
+    can_message: CanMessage,
+) -> Result<CanFdFrame, SendFrameError> {
+    let frame: CanFdFrame = can_message.into();
+    build_frame(can_message_id, frame.data())
+}
+
+/// Builds a CAN FD frame with `can_message_id` and the raw payload `data`.
+pub fn build_frame(
+    can_message_id: CanMessageId,
+    data: &[u8],
+) -> Result<CanFdFrame, SendFrameError> {
+    let raw_id: u16 = can_message_id.into();
+    let id = socketcan::StandardId::new(raw_id).ok_or(SendFrameError::InvalidId { raw_id })?;
+    CanFdFrame::new(id, data).ok_or(SendFrameError::InvalidFrameLength { len: data.len() })
 }
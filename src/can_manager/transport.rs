@@ -0,0 +1,54 @@
+use liquidcan::CanMessageId;
+use socketcan::{CanAnyFrame, CanFdFrame};
+use std::io;
+use std::time::Duration;
+
+/// A CAN FD bus the emulator can attach to.
+///
+/// `read_frame` reports an elapsed timeout as an `io::ErrorKind::WouldBlock` error, the same way a
+/// SocketCAN read timeout does, so callers can use `socketcan::ShouldRetry` for every backend.
+pub trait CanTransport {
+    fn send_frame(&mut self, frame: &CanFdFrame) -> io::Result<()>;
+
+    fn read_frame(&mut self, timeout: Duration) -> io::Result<CanAnyFrame>;
+
+    /// Restricts received frames to those addressed to one of `receiver_ids`.
+    /// Transports that cannot filter keep delivering every frame.
+    fn set_receiver_filter(&mut self, _receiver_ids: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<T: CanTransport + ?Sized> CanTransport for Box<T> {
+    fn send_frame(&mut self, frame: &CanFdFrame) -> io::Result<()> {
+        (**self).send_frame(frame)
+    }
+
+    fn read_frame(&mut self, timeout: Duration) -> io::Result<CanAnyFrame> {
+        (**self).read_frame(timeout)
+    }
+
+    fn set_receiver_filter(&mut self, receiver_ids: &[u8]) -> io::Result<()> {
+        (**self).set_receiver_filter(receiver_ids)
+    }
+}
+
+/// `(id, mask)` pairs in SocketCAN filter semantics that match the receiver bits of a standard
+/// LiquidCAN ID against each of `receiver_ids`.
+pub(crate) fn receiver_filters(receiver_ids: &[u8]) -> Vec<(u32, u32)> {
+    let mask: u16 = CanMessageId::new().with_receiver_id(0x1F).into();
+    receiver_ids
+        .iter()
+        .map(|&receiver_id| {
+            let id: u16 = CanMessageId::new().with_receiver_id(receiver_id).into();
+            (u32::from(id), u32::from(mask))
+        })
+        .collect()
+}
+
+pub(crate) fn timed_out() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::WouldBlock,
+        "timed out waiting for a CAN frame",
+    )
+}
@@ -1,17 +1,82 @@
+use crate::alarms::check_template;
 use crate::config::config_representation::EmulatorData;
-use anyhow::{Context, Result};
+use crate::config::serde_deserializer::typed_value;
+use crate::faults::{Direction, FaultKind};
+use crate::message_handling::{Event, MAX_STATUS_LEN};
+use crate::simulation::actuator::Dynamics;
+use crate::simulation::derived::evaluation_order;
+use crate::simulation::expression::TIME;
+use anyhow::{bail, Context, Result};
 use config::{Config, File};
+use serde::Deserialize;
+use std::collections::HashMap;
 use std::env;
+use std::path::Path;
+
+/// A config file that lists several nodes as `[[nodes]]` tables, each shaped like a single-node
+/// config.
+#[derive(Deserialize)]
+struct MultiNodeConfig {
+    nodes: Vec<EmulatorData>,
+}
 
 pub fn load_config(path: &str) -> Result<EmulatorData> {
-    let config = Config::builder()
-        .add_source(File::with_name(path))
-        .build()?;
+    let config = read_config(path)?;
 
     let mut emulator_data: EmulatorData = config
         .try_deserialize()
         .with_context(|| format!("Failed to deserialize config from {}", path))?;
 
+    validate_node(&mut emulator_data)?;
+    Ok(emulator_data)
+}
+
+/// Loads every node of a config file. A single-node config yields one node.
+pub fn load_nodes(path: &str) -> Result<Vec<EmulatorData>> {
+    let config = read_config(path)?;
+    if config.get_array("nodes").is_err() {
+        return Ok(vec![load_config(path)?]);
+    }
+
+    let MultiNodeConfig { mut nodes } = config
+        .try_deserialize()
+        .with_context(|| format!("Failed to deserialize config from {}", path))?;
+    if nodes.is_empty() {
+        bail!("Config {path} lists no nodes");
+    }
+
+    let mut seen: HashMap<u32, usize> = HashMap::new();
+    let mut state_files: HashMap<&Path, usize> = HashMap::new();
+    for (idx, node) in nodes.iter_mut().enumerate() {
+        validate_node(node).with_context(|| format!("nodes[{idx}] ({})", node.device_name))?;
+        if let Some(other) = seen.insert(node.node_id, idx) {
+            bail!(
+                "Duplicate node_id {} (used by nodes[{other}] and nodes[{idx}])",
+                node.node_id
+            );
+        }
+    }
+    for (idx, node) in nodes.iter().enumerate() {
+        let Some(state_file) = node.state_file.as_deref() else {
+            continue;
+        };
+        if let Some(other) = state_files.insert(state_file, idx) {
+            bail!(
+                "Duplicate state_file {} (used by nodes[{other}] and nodes[{idx}])",
+                state_file.display()
+            );
+        }
+    }
+    Ok(nodes)
+}
+
+fn read_config(path: &str) -> Result<Config> {
+    Ok(Config::builder()
+        .add_source(File::with_name(path))
+        .build()?)
+}
+
+fn validate_node(emulator_data: &mut EmulatorData) -> Result<()> {
     if emulator_data.node_id >= 31 || emulator_data.node_id <= 1 {
         return Err(anyhow::anyhow!(
             "Invalid node_id {} (must be <= 31 && > 1)",
@@ -19,8 +84,34 @@ pub fn load_config(path: &str) -> Result<EmulatorData> {
         ));
     }
 
+    validate_field_ids(
+        "TelemetryValues",
+        emulator_data
+            .telemetry_values
+            .iter()
+            .flatten()
+            .map(|tel| (tel.name.as_str(), tel.id)),
+    )?;
+    validate_field_ids(
+        "Parameters",
+        emulator_data
+            .parameters
+            .iter()
+            .flatten()
+            .map(|param| (param.name.as_str(), param.id)),
+    )?;
+    validate_telemetry_groups(emulator_data)?;
+    validate_models(emulator_data)?;
+    validate_faults(emulator_data)?;
+    validate_watchdog(emulator_data)?;
+    validate_alarms(emulator_data)?;
+    validate_reboot(emulator_data)?;
+    validate_expressions(emulator_data)?;
+    validate_routing(emulator_data)?;
+
     // Allow overriding the SocketCAN interface from the environment.
     // Useful for containers where the config is bind-mounted read-only.
+    // With several nodes this moves all of them onto the same interface.
     if let Ok(iface) = env::var("CAN_INTERFACE") {
         let iface = iface.trim().to_string();
         if !iface.is_empty() {
@@ -28,12 +119,336 @@ pub fn load_config(path: &str) -> Result<EmulatorData> {
         }
     }
 
-    Ok(emulator_data)
+    Ok(())
+}
+
+fn validate_field_ids<'a>(
+    section: &str,
+    fields: impl Iterator<Item = (&'a str, Option<u8>)>,
+) -> Result<()> {
+    let mut seen: HashMap<u8, &str> = HashMap::new();
+    for (name, id) in fields {
+        let Some(id) = id else {
+            continue;
+        };
+        if id == 0 || id > 0x7F {
+            bail!("Invalid id {id} for {section}.{name} (must be >= 1 && <= 127)");
+        }
+        if let Some(other) = seen.insert(id, name) {
+            bail!("Duplicate id {id} in {section} (used by {other} and {name})");
+        }
+    }
+    Ok(())
+}
+
+fn validate_routing(emulator_data: &EmulatorData) -> Result<()> {
+    if emulator_data.server_ids.is_empty() {
+        bail!("server_ids must name at least one server");
+    }
+    let node_id = emulator_data.node_id;
+    for &id in &emulator_data.server_ids {
+        if id == 0 || id > 31 || u32::from(id) == node_id {
+            bail!("Invalid server id {id} (must be >= 1 && <= 31 and not the node's own id)");
+        }
+    }
+    for (kind, recipients) in emulator_data.routing.iter().flatten() {
+        for &id in recipients {
+            if id > 31 || u32::from(id) == node_id {
+                bail!("Invalid recipient {id} for Routing.{kind} (must be <= 31 and not the node's own id)");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn validate_telemetry_groups(emulator_data: &EmulatorData) -> Result<()> {
+    let Some(groups) = emulator_data.telemetry_groups.as_ref() else {
+        return Ok(());
+    };
+    let telemetry = emulator_data
+        .telemetry_values
+        .as_deref()
+        .unwrap_or_default();
+    let mut owners: HashMap<&str, &str> = HashMap::new();
+    for group in groups {
+        if !group.rate.is_finite() || group.rate < 0.0 {
+            bail!(
+                "Invalid rate {} for telemetry group {} (must be >= 0)",
+                group.rate,
+                group.name
+            );
+        }
+        if group.members.is_empty() {
+            bail!("Telemetry group {} has no members", group.name);
+        }
+        let mut size = 0;
+        for member in &group.members {
+            let Some(tel) = telemetry.iter().find(|tel| tel.name == *member) else {
+                bail!(
+                    "Telemetry group {} references unknown telemetry value {member}",
+                    group.name
+                );
+            };
+            if let Some(other) = owners.insert(member, &group.name) {
+                bail!(
+                    "Telemetry value {member} is listed in telemetry groups {other} and {}",
+                    group.name
+                );
+            }
+            size += tel.datatype.get_size();
+        }
+        if size > 62 || group.members.len() > 62 {
+            bail!(
+                "Telemetry group {} needs {size} bytes but a group update holds at most 62",
+                group.name
+            );
+        }
+    }
+    Ok(())
+}
+
+fn validate_faults(emulator_data: &EmulatorData) -> Result<()> {
+    for fault in emulator_data.faults.iter().flatten() {
+        if !(0.0..=100.0).contains(&fault.percent) {
+            bail!(
+                "Invalid percent {} for fault {} (must be between 0 and 100)",
+                fault.percent,
+                fault.name
+            );
+        }
+        if fault.direction == Direction::Receive && !fault.kind.applies_on_receive() {
+            bail!(
+                "Fault {} cannot act on received frames, only drop and duplicate can",
+                fault.name
+            );
+        }
+        match fault.kind {
+            FaultKind::Delay { delay } if !(delay.is_finite() && delay >= 0.0) => {
+                bail!(
+                    "Invalid delay {delay} for fault {} (must be >= 0)",
+                    fault.name
+                );
+            }
+            FaultKind::Corrupt { bytes: 0 } => {
+                bail!("Fault {} corrupts no bytes", fault.name);
+            }
+            FaultKind::WrongSender {
+                sender_id: Some(sender_id),
+            } if sender_id > 30 || u32::from(sender_id) == emulator_data.node_id => {
+                bail!(
+                    "Invalid sender_id {sender_id} for fault {} (must be another node <= 30)",
+                    fault.name
+                );
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn validate_watchdog(emulator_data: &EmulatorData) -> Result<()> {
+    let Some(watchdog) = emulator_data.watchdog.as_ref() else {
+        return Ok(());
+    };
+    if !(watchdog.timeout.is_finite() && watchdog.timeout > 0.0) {
+        bail!(
+            "Invalid watchdog timeout {} (must be > 0)",
+            watchdog.timeout
+        );
+    }
+    for message in [&watchdog.message, &watchdog.recovery_message] {
+        if message.len() > MAX_STATUS_LEN {
+            bail!("Watchdog message \"{message}\" is longer than {MAX_STATUS_LEN} bytes");
+        }
+    }
+    for (name, value) in &watchdog.safe_values {
+        let param = emulator_data
+            .parameters
+            .iter()
+            .flatten()
+            .find(|param| param.name == *name)
+            .ok_or_else(|| anyhow::anyhow!("Watchdog safe value for unknown parameter {name}"))?;
+        let value = typed_value(value, param.datatype)
+            .map_err(|e| anyhow::anyhow!("Watchdog safe value for {name}: {e}"))?;
+        param
+            .check_constraints(&value)
+            .map_err(|e| anyhow::anyhow!("Watchdog safe value for {name}: {e}"))?;
+    }
+    Ok(())
+}
+
+fn validate_alarms(emulator_data: &EmulatorData) -> Result<()> {
+    for alarm in emulator_data.alarms.iter().flatten() {
+        let name = &alarm.name;
+        match (&alarm.field, &alarm.on) {
+            (Some(field), None) => {
+                if !alarm.has_thresholds() {
+                    bail!("Alarm {name} on {field} has no thresholds");
+                }
+                let is_telemetry = emulator_data
+                    .telemetry_values
+                    .iter()
+                    .flatten()
+                    .any(|tel| tel.name == *field);
+                let is_parameter = emulator_data
+                    .parameters
+                    .iter()
+                    .flatten()
+                    .any(|param| param.name == *field);
+                if !is_telemetry && !is_parameter {
+                    bail!("Alarm {name} watches unknown field {field}");
+                }
+            }
+            (None, Some(Event::ParameterSet(parameter))) => {
+                if alarm.has_thresholds() {
+                    bail!("Alarm {name} fires on an event and cannot have thresholds");
+                }
+                if !emulator_data
+                    .parameters
+                    .iter()
+                    .flatten()
+                    .any(|param| param.name == *parameter)
+                {
+                    bail!("Alarm {name} fires on unknown parameter {parameter}");
+                }
+            }
+            _ => bail!("Alarm {name} needs exactly one of `field` and `on`"),
+        }
+        if !(alarm.hysteresis.is_finite() && alarm.hysteresis >= 0.0) {
+            bail!(
+                "Invalid hysteresis {} for alarm {name} (must be >= 0)",
+                alarm.hysteresis
+            );
+        }
+        for template in [&alarm.message, &alarm.clear_message].into_iter().flatten() {
+            check_template(template).map_err(|e| anyhow::anyhow!("Alarm {name}: {e}"))?;
+        }
+    }
+    Ok(())
+}
+
+fn validate_reboot(emulator_data: &EmulatorData) -> Result<()> {
+    // The alternative config is only loaded on reboot; it may point back at this one.
+    if let Some(reboot) = emulator_data.reboot.as_ref() {
+        if !(reboot.boot_time.is_finite() && reboot.boot_time >= 0.0) {
+            bail!("Invalid boot_time {} (must be >= 0)", reboot.boot_time);
+        }
+    }
+    Ok(())
+}
+
+fn validate_models(emulator_data: &EmulatorData) -> Result<()> {
+    let Some(models) = emulator_data.models.as_ref() else {
+        return Ok(());
+    };
+    let telemetry = emulator_data
+        .telemetry_values
+        .as_deref()
+        .unwrap_or_default();
+    let mut drivers: HashMap<&str, &str> = HashMap::new();
+    for model in models {
+        let parameter = emulator_data
+            .parameters
+            .iter()
+            .flatten()
+            .find(|param| param.name == model.parameter);
+        if parameter.is_none() {
+            bail!(
+                "Model {} references unknown parameter {}",
+                model.name,
+                model.parameter
+            );
+        }
+        if model.telemetry.is_empty() {
+            bail!("Model {} drives no telemetry values", model.name);
+        }
+        for name in &model.telemetry {
+            let Some(tel) = telemetry.iter().find(|tel| tel.name == *name) else {
+                bail!(
+                    "Model {} references unknown telemetry value {name}",
+                    model.name
+                );
+            };
+            if tel.generator.is_some() {
+                bail!(
+                    "Telemetry value {name} has a generator and is driven by model {}",
+                    model.name
+                );
+            }
+            if let Some(other) = drivers.insert(name, &model.name) {
+                bail!(
+                    "Telemetry value {name} is driven by models {other} and {}",
+                    model.name
+                );
+            }
+        }
+        let invalid = |value: f64| !value.is_finite() || value < 0.0;
+        let constant = match model.dynamics {
+            Dynamics::Copy => None,
+            Dynamics::FirstOrderLag { time_constant } => Some(("time_constant", time_constant)),
+            Dynamics::RateLimit { rate } => Some(("rate", rate)),
+        };
+        for (name, value) in constant.into_iter().chain([("dead_time", model.dead_time)]) {
+            if invalid(value) {
+                bail!(
+                    "Invalid {name} {value} for model {} (must be >= 0)",
+                    model.name
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn validate_expressions(emulator_data: &mut EmulatorData) -> Result<()> {
+    let telemetry = emulator_data
+        .telemetry_values
+        .as_deref()
+        .unwrap_or_default();
+    let parameters = emulator_data.parameters.as_deref().unwrap_or_default();
+    for tel in telemetry {
+        let Some(expr) = tel.expr.as_ref() else {
+            continue;
+        };
+        let field = format!("TelemetryValues.{}", tel.name);
+        if tel.generator.is_some() {
+            bail!("{field} has both a generator and an expression");
+        }
+        let driven_by_model = emulator_data
+            .models
+            .iter()
+            .flatten()
+            .find(|model| model.telemetry.contains(&tel.name));
+        if let Some(model) = driven_by_model {
+            bail!(
+                "{field} has an expression and is driven by model {}",
+                model.name
+            );
+        }
+        for name in expr.names() {
+            let is_telemetry = telemetry.iter().any(|tel| tel.name == name);
+            let is_parameter = parameters.iter().any(|param| param.name == name);
+            match (name == TIME, is_telemetry, is_parameter) {
+                (true, false, false) | (false, true, false) | (false, false, true) => {}
+                (true, _, _) => {
+                    bail!("{field}: `{TIME}` in `{expr}` is the time but also names a field")
+                }
+                (false, true, true) => bail!(
+                    "{field}: `{name}` in `{expr}` names both a telemetry value and a parameter"
+                ),
+                (false, false, false) => bail!("{field}: unknown name `{name}` in `{expr}`"),
+            }
+        }
+    }
+    evaluation_order(telemetry).map_err(|e| anyhow::anyhow!(e))?;
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::message_handling::StatusMessageKind;
+    use crate::message_kind::MessageKind;
     use std::fs;
     use std::sync::Mutex;
 
@@ -117,4 +532,446 @@ device_name = "Emulator1"
 
         let _ = fs::remove_file(&path);
     }
+
+    #[test]
+    fn duplicate_field_ids_are_rejected() {
+        let path = write_temp_config(&format!(
+            "{SAMPLE_CONFIG}  id = 5\n[Parameters.Parameter2]\n  id = 5\n  value = 1\n  locked = false\n  datatype = \"UInt8\"\n"
+        ));
+        let err = load_config(&path).expect_err("duplicate ids should be rejected");
+        assert!(err.to_string().contains("Duplicate id 5"), "{err}");
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn field_ids_outside_7_bit_range_are_rejected() {
+        let path = write_temp_config(&format!("{SAMPLE_CONFIG}  id = 128\n"));
+        let err = load_config(&path).expect_err("id 128 should be rejected");
+        assert!(err.to_string().contains("Parameters.Parameter1"), "{err}");
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn telemetry_groups_are_loaded_and_validated() {
+        let path = write_temp_config(&format!(
+            "{SAMPLE_CONFIG}[TelemetryGroups.fast]\n  members = [\"tel1\"]\n  rate = 1000\n"
+        ));
+        let cfg = load_config(&path).expect("config should load");
+        let groups = cfg.telemetry_groups.expect("groups should be present");
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].name, "fast");
+        assert_eq!(groups[0].members, vec!["tel1"]);
+        assert_eq!(groups[0].rate, 1000.0);
+        let _ = fs::remove_file(&path);
+
+        let path = write_temp_config(&format!(
+            "{SAMPLE_CONFIG}[TelemetryGroups.fast]\n  members = [\"missing\"]\n  rate = 10\n"
+        ));
+        let err = load_config(&path).expect_err("unknown member should be rejected");
+        assert!(
+            err.to_string().contains("unknown telemetry value missing"),
+            "{err}"
+        );
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn oversized_telemetry_group_is_rejected() {
+        let mut contents = SAMPLE_CONFIG.to_string();
+        let mut members = Vec::new();
+        for idx in 0..16 {
+            contents.push_str(&format!(
+                "[TelemetryValues.big{idx}]\n  value = 0\n  datatype = \"UInt32\"\n"
+            ));
+            members.push(format!("\"big{idx}\""));
+        }
+        contents.push_str(&format!(
+            "[TelemetryGroups.big]\n  members = [{}]\n  rate = 1\n",
+            members.join(", ")
+        ));
+        let path = write_temp_config(&contents);
+        let err = load_config(&path).expect_err("64 byte group should be rejected");
+        assert!(err.to_string().contains("needs 64 bytes"), "{err}");
+        let _ = fs::remove_file(&path);
+    }
+
+    const MULTI_NODE_CONFIG: &str = r#"[[nodes]]
+node_id = 2
+frequency = 100
+firmware_hash = "0x123"
+can_interface = "vcan0"
+liquid_hash = "0x123"
+device_name = "Engine"
+  [nodes.TelemetryValues.pressure]
+  value = 1
+  datatype = "UInt16"
+  [nodes.Parameters.gain]
+  value = 2
+  locked = false
+  datatype = "UInt8"
+
+[[nodes]]
+node_id = 3
+frequency = 10
+firmware_hash = "0x456"
+can_interface = "vcan1"
+liquid_hash = "0x123"
+device_name = "Valves"
+  [nodes.TelemetryValues.position]
+  value = 0
+  datatype = "UInt8"
+  [nodes.Parameters.open]
+  value = false
+  locked = false
+  datatype = "Boolean"
+"#;
+
+    #[test]
+    fn multi_node_config_loads_every_node() {
+        let _guard = ENV_MUTEX.lock().unwrap_or_else(|e| e.into_inner());
+
+        let path = write_temp_config(MULTI_NODE_CONFIG);
+        let nodes = load_nodes(&path).expect("config should load");
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(
+            (nodes[0].node_id, nodes[0].device_name.as_str()),
+            (2, "Engine")
+        );
+        assert_eq!(
+            (nodes[1].node_id, nodes[1].device_name.as_str()),
+            (3, "Valves")
+        );
+        assert_eq!(nodes[0].telemetry_values.as_ref().unwrap().len(), 1);
+        assert_eq!(nodes[0].parameters.as_ref().unwrap()[0].name, "gain");
+        assert_eq!(nodes[1].parameters.as_ref().unwrap()[0].name, "open");
+    }
+
+    #[test]
+    fn single_node_config_loads_as_one_node() {
+        let _guard = ENV_MUTEX.lock().unwrap_or_else(|e| e.into_inner());
+
+        let path = write_temp_config(SAMPLE_CONFIG);
+        let nodes = load_nodes(&path).expect("config should load");
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].node_id, 2);
+        assert!(!nodes[0].kernel_filter);
+    }
+
+    #[test]
+    fn duplicate_node_ids_are_rejected() {
+        let path = write_temp_config(&MULTI_NODE_CONFIG.replace("node_id = 3", "node_id = 2"));
+        let err = load_nodes(&path).expect_err("duplicate node ids should be rejected");
+        assert!(err.to_string().contains("Duplicate node_id 2"), "{err}");
+        let _ = fs::remove_file(&path);
+
+        let path = write_temp_config(&MULTI_NODE_CONFIG.replace("node_id = 3", "node_id = 40"));
+        let err = load_nodes(&path).expect_err("invalid node id should be rejected");
+        assert!(format!("{err:#}").contains("nodes[1] (Valves)"), "{err:#}");
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn shared_state_files_are_rejected() {
+        let config = MULTI_NODE_CONFIG.replace(
+            "device_name = \"",
+            "state_file = \"state.toml\"\ndevice_name = \"",
+        );
+        let path = write_temp_config(&config);
+        let err = load_nodes(&path).expect_err("shared state file should be rejected");
+        assert!(
+            err.to_string().contains("Duplicate state_file state.toml"),
+            "{err}"
+        );
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn server_ids_and_routing_are_loaded() {
+        let path = write_temp_config(&SAMPLE_CONFIG.replace(
+            "device_name = \"Emulator1\"\n",
+            "device_name = \"Emulator1\"\nserver_ids = [5, 9]\n[Routing]\n  parameter_set_confirmation = [9, 12]\n  heartbeat_res = []\n",
+        ));
+        let cfg = load_config(&path).expect("config should load");
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(cfg.server_ids, vec![5, 9]);
+        let routing = cfg.routing.expect("routing should be present");
+        assert_eq!(routing[&MessageKind::ParameterSetConfirmation], vec![9, 12]);
+        assert!(routing[&MessageKind::HeartbeatRes].is_empty());
+    }
+
+    #[test]
+    fn invalid_routing_is_rejected() {
+        for (extra, expected) in [
+            ("server_ids = []\n", "at least one server"),
+            ("server_ids = [2]\n", "Invalid server id 2"),
+            (
+                "[Routing]\n  bogus_kind = [1]\n",
+                "unknown message kind bogus_kind",
+            ),
+            (
+                "[Routing]\n  field_get_res = [40]\n",
+                "Invalid recipient 40 for Routing.field_get_res",
+            ),
+        ] {
+            let path = write_temp_config(&SAMPLE_CONFIG.replace(
+                "device_name = \"Emulator1\"\n",
+                &format!("device_name = \"Emulator1\"\n{extra}"),
+            ));
+            let err = load_config(&path).expect_err("routing should be rejected");
+            let _ = fs::remove_file(&path);
+            assert!(format!("{err:#}").contains(expected), "{err:#}");
+        }
+    }
+
+    #[test]
+    fn models_are_loaded() {
+        let path = write_temp_config(&format!(
+            "{SAMPLE_CONFIG}\n[Models.valve]\n  parameter = \"Parameter1\"\n  telemetry = [\"tel1\"]\n  dynamics = {{ kind = \"first_order_lag\", time_constant = 0.5 }}\n  dead_time = 0.1\n"
+        ));
+        let emulator_data = load_config(&path).expect("config should load");
+        let _ = fs::remove_file(&path);
+
+        let models = emulator_data.models.expect("models should be present");
+        assert_eq!(models[0].name, "valve");
+        assert_eq!(models[0].parameter, "Parameter1");
+        assert_eq!(models[0].telemetry, vec!["tel1"]);
+        assert_eq!(models[0].dead_time, 0.1);
+        assert!(matches!(
+            models[0].dynamics,
+            Dynamics::FirstOrderLag { time_constant } if time_constant == 0.5
+        ));
+    }
+
+    #[test]
+    fn invalid_models_are_rejected() {
+        for (model, expected) in [
+            (
+                "parameter = \"missing\"\n  telemetry = [\"tel1\"]\n  dynamics = { kind = \"copy\" }",
+                "unknown parameter missing",
+            ),
+            (
+                "parameter = \"Parameter1\"\n  telemetry = [\"nope\"]\n  dynamics = { kind = \"copy\" }",
+                "unknown telemetry value nope",
+            ),
+            (
+                "parameter = \"Parameter1\"\n  telemetry = [\"tel1\"]\n  dynamics = { kind = \"rate_limit\", rate = -1 }",
+                "Invalid rate -1",
+            ),
+            (
+                "parameter = \"Parameter1\"\n  telemetry = [\"tel1\"]\n  dynamics = { kind = \"copy\" }\n  dead_time = -0.5",
+                "Invalid dead_time -0.5",
+            ),
+        ] {
+            let path = write_temp_config(&format!("{SAMPLE_CONFIG}\n[Models.valve]\n  {model}\n"));
+            let err = load_config(&path).expect_err("model should be rejected");
+            let _ = fs::remove_file(&path);
+            assert!(format!("{err:#}").contains(expected), "{err:#}");
+        }
+    }
+
+    #[test]
+    fn expressions_are_checked() {
+        let derived = "\n  [TelemetryValues.derived]\n  datatype = \"Float32\"\n  expr = ";
+        let with_expr = |expr: &str| {
+            SAMPLE_CONFIG.replace(
+                "datatype = \"UInt32\"\n\n[Parameters]",
+                &format!("datatype = \"UInt32\"{derived}\"{expr}\"\n\n[Parameters]"),
+            )
+        };
+
+        let path = write_temp_config(&with_expr("tel1 * Parameter1 + sin(t)"));
+        let emulator_data = load_config(&path).expect("expression should load");
+        let _ = fs::remove_file(&path);
+        let telemetry = emulator_data.telemetry_values.unwrap();
+        let tel = telemetry.iter().find(|tel| tel.name == "derived").unwrap();
+        assert_eq!(
+            tel.expr.as_ref().unwrap().to_string(),
+            "tel1 * Parameter1 + sin(t)"
+        );
+
+        for (expr, expected) in [
+            ("tel1 + missing", "unknown name `missing`"),
+            (
+                "derived + 1",
+                "Expressions form a cycle: derived -> derived",
+            ),
+            ("tel1 +", "TelemetryValues.derived: expr: unexpected end"),
+        ] {
+            let path = write_temp_config(&with_expr(expr));
+            let err = load_config(&path).expect_err("expression should be rejected");
+            let _ = fs::remove_file(&path);
+            assert!(format!("{err:#}").contains(expected), "{err:#}");
+        }
+    }
+
+    #[test]
+    fn faults_are_loaded() {
+        let path = write_temp_config(&format!(
+            "{SAMPLE_CONFIG}\n[Faults.lossy]\n  kind = \"drop\"\n  percent = 20\n  messages = [\"heartbeat_res\"]\n  seed = 7\n\n[Faults.slow]\n  kind = \"delay\"\n  delay = 0.25\n  enabled = false\n"
+        ));
+        let emulator_data = load_config(&path).expect("config should load");
+        let _ = fs::remove_file(&path);
+
+        let faults = emulator_data.faults.expect("faults should be present");
+        assert_eq!(faults[0].name, "lossy");
+        assert!(matches!(faults[0].kind, FaultKind::Drop));
+        assert_eq!(faults[0].percent, 20.0);
+        assert_eq!(faults[0].messages, vec![MessageKind::HeartbeatRes]);
+        assert_eq!(faults[0].seed, Some(7));
+        assert!(faults[0].enabled);
+        assert_eq!(faults[1].name, "slow");
+        assert!(matches!(faults[1].kind, FaultKind::Delay { delay } if delay == 0.25));
+        assert_eq!(faults[1].direction, Direction::Transmit);
+        assert!(!faults[1].enabled);
+    }
+
+    #[test]
+    fn invalid_faults_are_rejected() {
+        for (fault, expected) in [
+            ("kind = \"drop\"\n  percent = 120", "Invalid percent 120"),
+            ("kind = \"delay\"\n  delay = -1", "Invalid delay -1"),
+            ("kind = \"corrupt\"\n  bytes = 0", "corrupts no bytes"),
+            (
+                "kind = \"wrong_sender\"\n  sender_id = 2",
+                "Invalid sender_id 2",
+            ),
+            (
+                "kind = \"reorder\"\n  direction = \"receive\"",
+                "cannot act on received frames",
+            ),
+        ] {
+            let path = write_temp_config(&format!("{SAMPLE_CONFIG}\n[Faults.bad]\n  {fault}\n"));
+            let err = load_config(&path).expect_err("fault should be rejected");
+            let _ = fs::remove_file(&path);
+            assert!(format!("{err:#}").contains(expected), "{err:#}");
+        }
+    }
+
+    #[test]
+    fn watchdog_is_loaded() {
+        let path = write_temp_config(&format!(
+            "{SAMPLE_CONFIG}\n[Watchdog]\n  timeout = 1.5\n  level = \"warning\"\n  lock = true\n  [Watchdog.safe_values]\n  Parameter1 = 0\n"
+        ));
+        let emulator_data = load_config(&path).expect("config should load");
+        let _ = fs::remove_file(&path);
+
+        let watchdog = emulator_data.watchdog.expect("watchdog should be present");
+        assert_eq!(watchdog.timeout, 1.5);
+        assert_eq!(watchdog.level, StatusMessageKind::Warning);
+        assert_eq!(
+            watchdog.message,
+            "Server heartbeat lost, entering safe state"
+        );
+        assert!(watchdog.lock);
+        assert!(!watchdog.reregister);
+        assert!(watchdog.safe_values.contains_key("Parameter1"));
+    }
+
+    #[test]
+    fn invalid_watchdogs_are_rejected() {
+        for (watchdog, expected) in [
+            ("timeout = 0", "Invalid watchdog timeout 0"),
+            (
+                "timeout = 1\n  [Watchdog.safe_values]\n  missing = 1",
+                "unknown parameter missing",
+            ),
+            (
+                "timeout = 1\n  [Watchdog.safe_values]\n  Parameter1 = -1",
+                "does not fit in UInt32",
+            ),
+            (
+                "timeout = 1\n  message = \"This status message is much too long to fit into a single CAN FD frame\"",
+                "longer than 63 bytes",
+            ),
+        ] {
+            let path = write_temp_config(&format!("{SAMPLE_CONFIG}\n[Watchdog]\n  {watchdog}\n"));
+            let err = load_config(&path).expect_err("watchdog should be rejected");
+            let _ = fs::remove_file(&path);
+            assert!(format!("{err:#}").contains(expected), "{err:#}");
+        }
+    }
+
+    #[test]
+    fn alarms_are_loaded() {
+        let path = write_temp_config(&format!(
+            "{SAMPLE_CONFIG}\n[Alarms.hot]\n  field = \"tel1\"\n  warn_above = 80\n  error_above = 95\n  hysteresis = 2\n[Alarms.changed]\n  on = {{ parameter_set = \"Parameter1\" }}\n  level = \"warning\"\n  message = \"{{field}} set to {{value}}\"\n"
+        ));
+        let emulator_data = load_config(&path).expect("config should load");
+        let _ = fs::remove_file(&path);
+
+        let alarms = emulator_data.alarms.expect("alarms should be present");
+        assert_eq!(alarms[0].name, "changed");
+        assert_eq!(
+            alarms[0].on,
+            Some(Event::ParameterSet("Parameter1".to_string()))
+        );
+        assert_eq!(alarms[0].level, StatusMessageKind::Warning);
+        assert_eq!(alarms[1].name, "hot");
+        assert_eq!(alarms[1].field.as_deref(), Some("tel1"));
+        assert_eq!(alarms[1].warn_above, Some(80.0));
+        assert_eq!(alarms[1].error_above, Some(95.0));
+        assert_eq!(alarms[1].hysteresis, 2.0);
+    }
+
+    #[test]
+    fn invalid_alarms_are_rejected() {
+        for (alarm, expected) in [
+            ("warn_above = 1", "needs exactly one of `field` and `on`"),
+            ("field = \"tel1\"", "has no thresholds"),
+            (
+                "field = \"missing\"\n  warn_above = 1",
+                "unknown field missing",
+            ),
+            (
+                "on = { parameter_set = \"missing\" }",
+                "unknown parameter missing",
+            ),
+            (
+                "on = { parameter_set = \"Parameter1\" }\n  warn_above = 1",
+                "cannot have thresholds",
+            ),
+            (
+                "field = \"tel1\"\n  warn_above = 1\n  hysteresis = -1",
+                "Invalid hysteresis -1",
+            ),
+            (
+                "field = \"tel1\"\n  warn_above = 1\n  message = \"{rule} at {temp}\"",
+                "unknown placeholder {temp}",
+            ),
+        ] {
+            let path = write_temp_config(&format!("{SAMPLE_CONFIG}\n[Alarms.rule]\n  {alarm}\n"));
+            let err = load_config(&path).expect_err("alarm should be rejected");
+            let _ = fs::remove_file(&path);
+            assert!(format!("{err:#}").contains(expected), "{err:#}");
+        }
+    }
+
+    #[test]
+    fn reboot_table_is_loaded_and_checked() {
+        let path = write_temp_config(&format!(
+            "{SAMPLE_CONFIG}\n[Reboot]\n  config = \"updated.toml\"\n"
+        ));
+        let emulator_data = load_config(&path).expect("config should load");
+        let _ = fs::remove_file(&path);
+        let reboot = emulator_data
+            .reboot
+            .expect("reboot table should be present");
+        assert_eq!(reboot.boot_time, 1.0);
+        assert_eq!(reboot.config.as_deref(), Some(Path::new("updated.toml")));
+
+        let path = write_temp_config(&format!("{SAMPLE_CONFIG}\n[Reboot]\n  boot_time = -2\n"));
+        let err = load_config(&path).expect_err("boot time should be rejected");
+        let _ = fs::remove_file(&path);
+        assert!(
+            format!("{err:#}").contains("Invalid boot_time -2"),
+            "{err:#}"
+        );
+    }
 }
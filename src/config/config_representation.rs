@@ -1,12 +1,27 @@
+use crate::alarms::Alarm;
+use crate::config::serde_deserializer::deserialize_alarms;
+use crate::config::serde_deserializer::deserialize_faults;
+use crate::config::serde_deserializer::deserialize_models;
 use crate::config::serde_deserializer::deserialize_parameters;
-use crate::config::serde_deserializer::deserialize_prefixed_u32;
+use crate::config::serde_deserializer::deserialize_routing;
 use crate::config::serde_deserializer::deserialize_telemetry;
+use crate::config::serde_deserializer::deserialize_telemetry_groups;
 use crate::config::serde_deserializer::deserialize_value_or_u32;
 use crate::config::serde_deserializer::max_bytes;
-use liquidcan::payloads::CanDataType;
+use crate::config::serde_deserializer::value_to_f64;
+use crate::faults::Fault;
+use crate::message_kind::MessageKind;
+use crate::reboot::RebootConfig;
+use crate::simulation::actuator::ActuatorModel;
+use crate::simulation::expression::Expression;
+use crate::simulation::generator::Generator;
+use crate::watchdog::Watchdog;
+use liquidcan::payloads::{CanDataType, CanDataValue};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize)]
 #[serde(remote = "CanDataType")]
 enum DataType {
     Float32 = 0,
@@ -19,25 +34,113 @@ enum DataType {
     Boolean = 7,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// A scalar as written in the config file, before it is interpreted according to `datatype`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub(crate) enum ConfigScalar {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+#[derive(Deserialize)]
+pub(crate) struct TelemetryValueConfig {
+    pub id: Option<u8>,
+    pub value: Option<ConfigScalar>,
+    #[serde(with = "DataType")]
+    pub datatype: CanDataType,
+    pub generator: Option<Generator>,
+    pub expr: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ParameterConfig {
+    pub id: Option<u8>,
+    pub value: ConfigScalar,
+    pub locked: bool,
+    #[serde(with = "DataType")]
+    pub datatype: CanDataType,
+    pub min: Option<ConfigScalar>,
+    pub max: Option<ConfigScalar>,
+    pub allowed: Option<Vec<ConfigScalar>>,
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+#[derive(Debug)]
 pub struct TelemetryValue {
-    #[serde(skip)]
     pub name: String,
-    #[serde(deserialize_with = "deserialize_prefixed_u32")]
-    pub value: u32,
-    #[serde(with = "DataType")]
+    /// Explicit 7-bit field ID (without the telemetry bit); assigned automatically when `None`.
+    pub id: Option<u8>,
+    pub value: CanDataValue,
     pub datatype: CanDataType,
+    pub generator: Option<Generator>,
+    /// Derives the value from other telemetry values, parameters and the time on every update.
+    pub expr: Option<Expression>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Debug)]
 pub struct Parameter {
-    #[serde(skip)]
     pub name: String,
-    #[serde(deserialize_with = "deserialize_value_or_u32")]
-    pub value: u32,
+    /// Explicit 7-bit field ID; assigned automatically when `None`.
+    pub id: Option<u8>,
+    pub value: CanDataValue,
     pub locked: bool,
-    #[serde(with = "DataType")]
     pub datatype: CanDataType,
+    /// Writes over CAN below `min`, above `max` or not in `allowed` are rejected.
+    pub min: Option<CanDataValue>,
+    pub max: Option<CanDataValue>,
+    pub allowed: Option<Vec<CanDataValue>>,
+    /// Writes over CAN are rejected as if the parameter was locked, whatever its lock state.
+    pub read_only: bool,
+}
+
+impl Parameter {
+    /// Checks `value` against `min`, `max` and `allowed`. NaN and infinities are out of range
+    /// of any `min` or `max`.
+    pub fn check_constraints(&self, value: &CanDataValue) -> Result<(), String> {
+        let number = value_to_f64(value);
+        let bounded = self.min.is_some() || self.max.is_some();
+        if bounded && number.is_some_and(|number| !number.is_finite()) {
+            return Err(format!("value {value:?} is not a finite number"));
+        }
+        if let (Some(min), Some(number)) = (&self.min, number) {
+            if value_to_f64(min).is_some_and(|min| number < min) {
+                return Err(format!("value {value:?} is below min {min:?}"));
+            }
+        }
+        if let (Some(max), Some(number)) = (&self.max, number) {
+            if value_to_f64(max).is_some_and(|max| number > max) {
+                return Err(format!("value {value:?} is above max {max:?}"));
+            }
+        }
+        if let Some(allowed) = &self.allowed {
+            if !allowed.contains(value) {
+                return Err(format!("value {value:?} is not one of {allowed:?}"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that `value` may replace the configured value at runtime or from a state file:
+    /// the parameter is not read-only and `value` meets its constraints.
+    pub fn check_write(&self, value: &CanDataValue) -> Result<(), String> {
+        if self.read_only {
+            return Err(format!("parameter {} is read-only", self.name));
+        }
+        self.check_constraints(value)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TelemetryGroup {
+    #[serde(skip)]
+    pub name: String,
+    /// Telemetry names in the order their values are packed into the update frame.
+    pub members: Vec<String>,
+    /// Update rate in Hz; 0 disables periodic updates of this group.
+    pub rate: f64,
 }
 
 #[derive(Deserialize, Debug)]
@@ -57,6 +160,47 @@ pub struct EmulatorData {
     #[serde(rename = "Parameters")]
     #[serde(deserialize_with = "deserialize_parameters")]
     pub parameters: Option<Vec<Parameter>>,
+    #[serde(rename = "TelemetryGroups", default)]
+    #[serde(deserialize_with = "deserialize_telemetry_groups")]
+    pub telemetry_groups: Option<Vec<TelemetryGroup>>,
+    /// Telemetry values that follow a parameter over time.
+    #[serde(rename = "Models", default)]
+    #[serde(deserialize_with = "deserialize_models")]
+    pub models: Option<Vec<ActuatorModel>>,
+    /// Let the transport drop frames for other nodes (SocketCAN: kernel filters). Frames
+    /// dropped there never reach the emulator and are not counted as `not_addressed`, so this is
+    /// off unless the bus load calls for it.
+    #[serde(default)]
+    pub kernel_filter: bool,
+    /// Nodes that receive registrations and telemetry updates.
+    #[serde(default = "default_server_ids")]
+    pub server_ids: Vec<u8>,
+    /// Extra recipients per message kind. Parameter confirmations are mirrored to the servers
+    /// unless the `Routing` table lists their kind; an empty list turns that off.
+    #[serde(rename = "Routing", default)]
+    #[serde(deserialize_with = "deserialize_routing")]
+    pub routing: Option<HashMap<MessageKind, Vec<u8>>>,
+    /// File that keeps parameter values and lock states across restarts.
+    #[serde(default)]
+    pub state_file: Option<PathBuf>,
+    /// Faults injected into the frames the node sends and receives.
+    #[serde(rename = "Faults", default)]
+    #[serde(deserialize_with = "deserialize_faults")]
+    pub faults: Option<Vec<Fault>>,
+    /// Safe state the node enters when the servers' heartbeats stop.
+    #[serde(rename = "Watchdog", default)]
+    pub watchdog: Option<Watchdog>,
+    /// Status messages raised by field thresholds and events.
+    #[serde(rename = "Alarms", default)]
+    #[serde(deserialize_with = "deserialize_alarms")]
+    pub alarms: Option<Vec<Alarm>>,
+    /// How the node reboots when asked to without further options.
+    #[serde(rename = "Reboot", default)]
+    pub reboot: Option<RebootConfig>,
+}
+
+fn default_server_ids() -> Vec<u8> {
+    vec![1]
 }
 
 #[cfg(test)]
@@ -78,6 +222,9 @@ device_name = "Emulator1"
     [TelemetryValues.tel2]
     value = 0x12345678
     datatype = "UInt32"
+    [TelemetryValues.tel3]
+    datatype = "Float32"
+    generator = { kind = "sine", amplitude = 2, period = 0.5 }
 
 [Parameters]
     [Parameters.Parameter1]
@@ -104,19 +251,30 @@ device_name = "Emulator1"
         let telemetry_values = emu_config
             .telemetry_values
             .expect("Telemetry Values should be present");
-        assert_eq!(telemetry_values.len(), 2);
+        assert_eq!(telemetry_values.len(), 3);
 
         let var1 = telemetry_values
             .iter()
             .find(|v| v.name == "tel1")
             .expect("tel1 should exist");
-        assert_eq!(var1.value, 0x12345678);
+        assert_eq!(var1.value, CanDataValue::UInt32(0x12345678));
 
         let var2 = telemetry_values
             .iter()
             .find(|v| v.name == "tel2")
             .expect("tel2 should exist");
-        assert_eq!(var2.value, 0x12345678);
+        assert_eq!(var2.value, CanDataValue::UInt32(0x12345678));
+        assert!(var2.generator.is_none());
+
+        let var3 = telemetry_values
+            .iter()
+            .find(|v| v.name == "tel3")
+            .expect("tel3 should exist");
+        assert_eq!(var3.value, CanDataValue::Float32(0.0));
+        assert!(matches!(
+            var3.generator,
+            Some(Generator::Sine { amplitude, period, .. }) if amplitude == 2.0 && period == 0.5
+        ));
 
         let parameters = emu_config.parameters.expect("Parameters should be present");
         assert_eq!(parameters.len(), 2);
@@ -125,14 +283,187 @@ device_name = "Emulator1"
             .iter()
             .find(|p| p.name == "Parameter1")
             .expect("Parameter1 should exist");
-        assert_eq!(param1.value, 0xABAC0);
+        assert_eq!(param1.value, CanDataValue::UInt32(0xABAC0));
         assert!(!param1.locked);
 
         let param2 = parameters
             .iter()
             .find(|p| p.name == "Parameter2")
             .expect("Parameter2 should exist");
-        assert_eq!(param2.value, 0); // false -> 0
+        assert_eq!(param2.value, CanDataValue::Boolean(false));
         assert!(param2.locked);
     }
+
+    fn load_from_str(contents: &str) -> Result<EmulatorData, config::ConfigError> {
+        Config::builder()
+            .add_source(config::File::from_str(contents, config::FileFormat::Toml))
+            .build()?
+            .try_deserialize()
+    }
+
+    const HEADER: &str = r#"node_id = 2
+frequency = 100
+firmware_hash = "0x123"
+can_interface = "vcan0"
+liquid_hash = "0x123"
+device_name = "Emulator1"
+"#;
+
+    #[test]
+    fn test_typed_values() {
+        let config = format!(
+            r#"{HEADER}
+[TelemetryValues]
+    [TelemetryValues.temp]
+    value = -12.5
+    datatype = "Float32"
+    [TelemetryValues.offset]
+    value = -3
+    datatype = "Int8"
+    [TelemetryValues.raw_float]
+    value = "0x3FC00000"
+    datatype = "Float32"
+    [TelemetryValues.neg_hex]
+    value = "-0x10"
+    datatype = "Int16"
+
+[Parameters]
+    [Parameters.gain]
+    value = 2
+    locked = false
+    datatype = "Float32"
+    [Parameters.enabled]
+    value = 1
+    locked = false
+    datatype = "Boolean""#
+        );
+
+        let emu_config = load_from_str(&config).expect("config should load");
+        let telemetry_values = emu_config.telemetry_values.unwrap();
+        let value_of = |name: &str| {
+            telemetry_values
+                .iter()
+                .find(|v| v.name == name)
+                .map(|v| v.value.clone())
+                .unwrap()
+        };
+        assert_eq!(value_of("temp"), CanDataValue::Float32(-12.5));
+        assert_eq!(value_of("offset"), CanDataValue::Int8(-3));
+        assert_eq!(value_of("raw_float"), CanDataValue::Float32(1.5));
+        assert_eq!(value_of("neg_hex"), CanDataValue::Int16(-16));
+
+        let parameters = emu_config.parameters.unwrap();
+        let gain = parameters.iter().find(|p| p.name == "gain").unwrap();
+        assert_eq!(gain.value, CanDataValue::Float32(2.0));
+        let enabled = parameters.iter().find(|p| p.name == "enabled").unwrap();
+        assert_eq!(enabled.value, CanDataValue::Boolean(true));
+    }
+
+    #[test]
+    fn test_values_out_of_range_are_rejected() {
+        let cases = [
+            ("300", "UInt8"),
+            ("-1", "UInt32"),
+            ("-129", "Int8"),
+            ("1.5", "Int16"),
+            ("true", "UInt8"),
+            ("2", "Boolean"),
+            ("1e39", "Float32"),
+        ];
+        for (value, datatype) in cases {
+            let config = format!(
+                r#"{HEADER}
+[TelemetryValues]
+    [TelemetryValues.bad_field]
+    value = {value}
+    datatype = "{datatype}""#
+            );
+            let err = load_from_str(&config)
+                .err()
+                .unwrap_or_else(|| panic!("{value} should not fit {datatype}"));
+            assert!(
+                err.to_string().contains("TelemetryValues.bad_field"),
+                "error should name the field: {err}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parameter_constraints() {
+        let config = format!(
+            r#"{HEADER}
+[TelemetryValues]
+
+[Parameters]
+    [Parameters.gain]
+    value = 2
+    locked = false
+    datatype = "Float32"
+    min = 0
+    max = 10.5
+    [Parameters.mode]
+    value = 1
+    locked = false
+    datatype = "UInt8"
+    allowed = [1, 2, "0x4"]
+    [Parameters.serial]
+    value = 1234
+    locked = false
+    datatype = "UInt32"
+    read_only = true"#
+        );
+
+        let emu_config = load_from_str(&config).expect("config should load");
+        let parameters = emu_config.parameters.unwrap();
+        let param = |name: &str| parameters.iter().find(|p| p.name == name).unwrap();
+        assert_eq!(param("gain").min, Some(CanDataValue::Float32(0.0)));
+        assert_eq!(param("gain").max, Some(CanDataValue::Float32(10.5)));
+        assert_eq!(
+            param("mode").allowed,
+            Some(vec![
+                CanDataValue::UInt8(1),
+                CanDataValue::UInt8(2),
+                CanDataValue::UInt8(4)
+            ])
+        );
+        assert!(param("serial").read_only);
+        assert!(!param("gain").read_only);
+    }
+
+    #[test]
+    fn test_defaults_must_satisfy_their_constraints() {
+        let cases = [
+            ("value = 11\n    max = 10", "above max"),
+            ("value = -1\n    min = 0", "below min"),
+            (
+                "value = 5\n    min = 6\n    max = 4",
+                "min Int16(6) is above max",
+            ),
+            ("value = 3\n    allowed = [1, 2]", "not one of"),
+            (
+                "value = 1\n    allowed = [1, 70000]",
+                "allowed: value 70000",
+            ),
+        ];
+        for (attributes, reason) in cases {
+            let config = format!(
+                r#"{HEADER}
+[TelemetryValues]
+
+[Parameters]
+    [Parameters.bad_param]
+    locked = false
+    datatype = "Int16"
+    {attributes}"#
+            );
+            let err = load_from_str(&config)
+                .err()
+                .unwrap_or_else(|| panic!("{attributes} should be rejected"));
+            let err = err.to_string();
+            assert!(
+                err.contains("Parameters.bad_param") && err.contains(reason),
+                "{attributes}: {err}"
+            );
+        }
+    }
 }
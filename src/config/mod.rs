@@ -1,4 +1,5 @@
 pub mod config_loader;
 
 pub mod config_representation;
-mod serde_deserializer;
+pub mod parameter_state;
+pub(crate) mod serde_deserializer;
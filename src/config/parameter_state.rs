@@ -0,0 +1,219 @@
+//! Parameter values and lock states kept across restarts in a node's `state_file`, like an
+//! ECU keeps them in flash.
+
+use crate::config::config_representation::{ConfigScalar, Parameter};
+use crate::config::serde_deserializer::typed_value;
+use anyhow::{Context, Result};
+use liquidcan::payloads::CanDataValue;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+#[derive(Serialize, Deserialize)]
+struct StoredParameter {
+    datatype: String,
+    value: ConfigScalar,
+    locked: bool,
+}
+
+/// Writes the values and lock states of `parameters` to `path`; read-only parameters always
+/// come from the config. The file is replaced atomically, so a crash never leaves a partially
+/// written state behind.
+pub fn save(path: &Path, parameters: &[Parameter]) -> Result<()> {
+    let stored: BTreeMap<&str, StoredParameter> = parameters
+        .iter()
+        .filter(|param| !param.read_only)
+        .filter_map(|param| {
+            let stored = StoredParameter {
+                datatype: format!("{:?}", param.datatype),
+                value: scalar_from_value(&param.value)?,
+                locked: param.locked,
+            };
+            Some((param.name.as_str(), stored))
+        })
+        .collect();
+    let contents = toml::to_string(&stored)?;
+
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    fs::write(&tmp_path, contents)
+        .with_context(|| format!("Failed to write {}", Path::new(&tmp_path).display()))?;
+    fs::rename(&tmp_path, path).with_context(|| format!("Failed to replace {}", path.display()))?;
+    Ok(())
+}
+
+/// Overwrites the config defaults of `parameters` with the state stored at `path`, if there is
+/// one. Entries whose parameter no longer exists, changed its datatype or would not accept the
+/// stored value from the control API either are skipped with a warning.
+pub fn restore(path: &Path, parameters: &mut [Parameter]) -> Result<()> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to read {}", path.display()));
+        }
+    };
+    let stored: BTreeMap<String, StoredParameter> =
+        toml::from_str(&contents).with_context(|| format!("Failed to parse {}", path.display()))?;
+
+    for (name, stored) in stored {
+        let Some(param) = parameters.iter_mut().find(|param| param.name == name) else {
+            eprintln!(
+                "Warning: {}: skipping unknown parameter {name}",
+                path.display()
+            );
+            continue;
+        };
+        let datatype = format!("{:?}", param.datatype);
+        if stored.datatype != datatype {
+            eprintln!(
+                "Warning: {}: skipping parameter {name}, stored as {} but configured as {datatype}",
+                path.display(),
+                stored.datatype
+            );
+            continue;
+        }
+        let value = typed_value(&stored.value, param.datatype)
+            .and_then(|value| param.check_write(&value).map(|()| value));
+        match value {
+            Ok(value) => {
+                param.value = value;
+                param.locked = stored.locked;
+            }
+            Err(err) => {
+                eprintln!(
+                    "Warning: {}: skipping parameter {name}: {err}",
+                    path.display()
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn scalar_from_value(value: &CanDataValue) -> Option<ConfigScalar> {
+    Some(match value {
+        CanDataValue::Float32(v) => ConfigScalar::Float(f64::from(*v)),
+        CanDataValue::Int32(v) => ConfigScalar::Int(i64::from(*v)),
+        CanDataValue::Int16(v) => ConfigScalar::Int(i64::from(*v)),
+        CanDataValue::Int8(v) => ConfigScalar::Int(i64::from(*v)),
+        CanDataValue::UInt32(v) => ConfigScalar::Int(i64::from(*v)),
+        CanDataValue::UInt16(v) => ConfigScalar::Int(i64::from(*v)),
+        CanDataValue::UInt8(v) => ConfigScalar::Int(i64::from(*v)),
+        CanDataValue::Boolean(v) => ConfigScalar::Bool(*v),
+        CanDataValue::Raw(_) => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use liquidcan::payloads::CanDataType;
+    use std::path::PathBuf;
+
+    fn parameter(name: &str, value: CanDataValue, datatype: CanDataType) -> Parameter {
+        Parameter {
+            name: name.to_string(),
+            id: None,
+            value,
+            locked: false,
+            datatype,
+            min: None,
+            max: None,
+            allowed: None,
+            read_only: false,
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "ecuemulator_state_{name}_{}.toml",
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn saved_parameters_are_restored() {
+        let path = temp_path("roundtrip");
+        let mut saved = vec![
+            parameter("gain", CanDataValue::Float32(0.1), CanDataType::Float32),
+            parameter("offset", CanDataValue::Int16(-300), CanDataType::Int16),
+            parameter("enabled", CanDataValue::Boolean(true), CanDataType::Boolean),
+        ];
+        saved[1].locked = true;
+        save(&path, &saved).unwrap();
+
+        let mut restored = vec![
+            parameter("gain", CanDataValue::Float32(1.0), CanDataType::Float32),
+            parameter("offset", CanDataValue::Int16(0), CanDataType::Int16),
+            parameter(
+                "enabled",
+                CanDataValue::Boolean(false),
+                CanDataType::Boolean,
+            ),
+        ];
+        restore(&path, &mut restored).unwrap();
+        let _ = fs::remove_file(&path);
+
+        for (saved, restored) in saved.iter().zip(&restored) {
+            assert_eq!(saved.value, restored.value, "{}", saved.name);
+            assert_eq!(saved.locked, restored.locked, "{}", saved.name);
+        }
+    }
+
+    #[test]
+    fn mismatched_entries_are_skipped() {
+        let path = temp_path("mismatch");
+        let saved = vec![
+            parameter("gain", CanDataValue::UInt8(7), CanDataType::UInt8),
+            parameter("removed", CanDataValue::UInt8(7), CanDataType::UInt8),
+            parameter("limit", CanDataValue::UInt16(500), CanDataType::UInt16),
+        ];
+        save(&path, &saved).unwrap();
+
+        let mut restored = vec![
+            parameter("gain", CanDataValue::UInt8(1), CanDataType::UInt8),
+            parameter("limit", CanDataValue::UInt32(1), CanDataType::UInt32),
+        ];
+        restore(&path, &mut restored).unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(restored[0].value, CanDataValue::UInt8(7));
+        assert_eq!(restored[1].value, CanDataValue::UInt32(1));
+    }
+
+    #[test]
+    fn read_only_parameters_keep_their_configured_value() {
+        let path = temp_path("read_only");
+        let saved = vec![parameter(
+            "serial",
+            CanDataValue::UInt32(7),
+            CanDataType::UInt32,
+        )];
+        save(&path, &saved).unwrap();
+
+        let mut restored = vec![parameter(
+            "serial",
+            CanDataValue::UInt32(1),
+            CanDataType::UInt32,
+        )];
+        restored[0].read_only = true;
+        restore(&path, &mut restored).unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(restored[0].value, CanDataValue::UInt32(1));
+    }
+
+    #[test]
+    fn missing_state_file_keeps_the_defaults() {
+        let mut parameters = vec![parameter(
+            "gain",
+            CanDataValue::UInt8(1),
+            CanDataType::UInt8,
+        )];
+        restore(&temp_path("missing"), &mut parameters).unwrap();
+        assert_eq!(parameters[0].value, CanDataValue::UInt8(1));
+    }
+}
@@ -1,9 +1,17 @@
-use crate::config::config_representation::{Parameter, TelemetryValue};
+use crate::alarms::Alarm;
+use crate::config::config_representation::{
+    ConfigScalar, Parameter, ParameterConfig, TelemetryGroup, TelemetryValue, TelemetryValueConfig,
+};
+use crate::faults::Fault;
+use crate::message_kind::MessageKind;
+use crate::simulation::actuator::ActuatorModel;
+use crate::simulation::expression::Expression;
+use liquidcan::payloads::{CanDataType, CanDataValue};
 use num_bigint::BigUint;
 use num_traits::{FromPrimitive, ToPrimitive};
 use serde::de::Error;
 use serde::{Deserialize, Deserializer};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 pub mod max_bytes {
     use super::*;
@@ -49,32 +57,292 @@ pub fn deserialize_telemetry<'de, D>(
 where
     D: Deserializer<'de>,
 {
-    let map: Option<HashMap<String, TelemetryValue>> = Option::deserialize(deserializer)?;
+    let map: Option<HashMap<String, TelemetryValueConfig>> = Option::deserialize(deserializer)?;
+    let Some(map) = map else {
+        return Ok(None);
+    };
+    map.into_iter()
+        .map(|(name, var)| {
+            let field_error = |e: String| D::Error::custom(format!("TelemetryValues.{name}: {e}"));
+            let value = match &var.value {
+                Some(value) => typed_value(value, var.datatype).map_err(field_error)?,
+                None => zero_value(var.datatype),
+            };
+            let expr = var.expr.as_deref().map(Expression::parse).transpose();
+            let expr = expr.map_err(|e| field_error(format!("expr: {e}")))?;
+            Ok(TelemetryValue {
+                name,
+                id: var.id,
+                value,
+                datatype: var.datatype,
+                generator: var.generator,
+                expr,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(Some)
+}
+
+pub fn deserialize_parameters<'de, D>(deserializer: D) -> Result<Option<Vec<Parameter>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let map: Option<HashMap<String, ParameterConfig>> = Option::deserialize(deserializer)?;
+    let Some(map) = map else {
+        return Ok(None);
+    };
+    map.into_iter()
+        .map(|(name, param)| {
+            let field_error = |e: String| D::Error::custom(format!("Parameters.{name}: {e}"));
+            let typed = |value: &ConfigScalar| typed_value(value, param.datatype);
+            let value = typed(&param.value).map_err(field_error)?;
+            let min = param.min.as_ref().map(typed).transpose();
+            let min = min.map_err(|e| field_error(format!("min: {e}")))?;
+            let max = param.max.as_ref().map(typed).transpose();
+            let max = max.map_err(|e| field_error(format!("max: {e}")))?;
+            let allowed = param
+                .allowed
+                .as_ref()
+                .map(|allowed| allowed.iter().map(typed).collect::<Result<Vec<_>, _>>())
+                .transpose()
+                .map_err(|e| field_error(format!("allowed: {e}")))?;
+            let parameter = Parameter {
+                id: param.id,
+                value,
+                locked: param.locked,
+                datatype: param.datatype,
+                min,
+                max,
+                allowed,
+                read_only: param.read_only,
+                name: name.clone(),
+            };
+            if let (Some(min), Some(max)) = (&parameter.min, &parameter.max) {
+                if value_to_f64(min) > value_to_f64(max) {
+                    return Err(field_error(format!("min {min:?} is above max {max:?}")));
+                }
+            }
+            parameter
+                .check_constraints(&parameter.value)
+                .map_err(|e| field_error(format!("default {e}")))?;
+            Ok(parameter)
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(Some)
+}
+
+pub fn deserialize_telemetry_groups<'de, D>(
+    deserializer: D,
+) -> Result<Option<Vec<TelemetryGroup>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let map: Option<HashMap<String, TelemetryGroup>> = Option::deserialize(deserializer)?;
     Ok(map.map(|m| {
         m.into_iter()
-            .map(|(name, mut var)| {
-                var.name = name;
-                var
+            .map(|(name, mut group)| {
+                group.name = name;
+                group
             })
             .collect()
     }))
 }
 
-pub fn deserialize_parameters<'de, D>(deserializer: D) -> Result<Option<Vec<Parameter>>, D::Error>
+pub fn deserialize_models<'de, D>(deserializer: D) -> Result<Option<Vec<ActuatorModel>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let map: Option<HashMap<String, ActuatorModel>> = Option::deserialize(deserializer)?;
+    Ok(map.map(|m| {
+        m.into_iter()
+            .map(|(name, mut model)| {
+                model.name = name;
+                model
+            })
+            .collect()
+    }))
+}
+
+/// Faults sorted by name, so they act on a frame in a fixed order.
+pub fn deserialize_faults<'de, D>(deserializer: D) -> Result<Option<Vec<Fault>>, D::Error>
 where
     D: Deserializer<'de>,
 {
-    let map: Option<HashMap<String, Parameter>> = Option::deserialize(deserializer)?;
+    let map: Option<BTreeMap<String, Fault>> = Option::deserialize(deserializer)?;
     Ok(map.map(|m| {
         m.into_iter()
-            .map(|(name, mut param)| {
-                param.name = name;
-                param
+            .map(|(name, mut fault)| {
+                fault.name = name;
+                fault
             })
             .collect()
     }))
 }
 
+pub fn deserialize_alarms<'de, D>(deserializer: D) -> Result<Option<Vec<Alarm>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let map: Option<BTreeMap<String, Alarm>> = Option::deserialize(deserializer)?;
+    Ok(map.map(|m| {
+        m.into_iter()
+            .map(|(name, mut alarm)| {
+                alarm.name = name;
+                alarm
+            })
+            .collect()
+    }))
+}
+
+pub fn deserialize_routing<'de, D>(
+    deserializer: D,
+) -> Result<Option<HashMap<MessageKind, Vec<u8>>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let map: Option<HashMap<String, Vec<u8>>> = Option::deserialize(deserializer)?;
+    map.map(|m| {
+        m.into_iter()
+            .map(|(name, recipients)| {
+                let kind = name
+                    .parse::<MessageKind>()
+                    .map_err(|e| D::Error::custom(format!("Routing: {e}")))?;
+                Ok((kind, recipients))
+            })
+            .collect()
+    })
+    .transpose()
+}
+
+/// The value a field starts with when the config does not give one.
+pub fn zero_value(data_type: CanDataType) -> CanDataValue {
+    match data_type {
+        CanDataType::Float32 => CanDataValue::Float32(0.0),
+        CanDataType::Int32 => CanDataValue::Int32(0),
+        CanDataType::Int16 => CanDataValue::Int16(0),
+        CanDataType::Int8 => CanDataValue::Int8(0),
+        CanDataType::UInt32 => CanDataValue::UInt32(0),
+        CanDataType::UInt16 => CanDataValue::UInt16(0),
+        CanDataType::UInt8 => CanDataValue::UInt8(0),
+        CanDataType::Boolean => CanDataValue::Boolean(false),
+    }
+}
+
+/// Interprets a config scalar according to the declared datatype.
+/// Integers must fit the target type exactly, floats are only accepted for `Float32` unless they
+/// are whole numbers, and a '0x'/'0b' string for a `Float32` field is taken as its IEEE bit pattern.
+pub(crate) fn typed_value(
+    value: &ConfigScalar,
+    data_type: CanDataType,
+) -> Result<CanDataValue, String> {
+    let out_of_range = |v: i128| format!("value {v} does not fit in {data_type:?}");
+    Ok(match data_type {
+        CanDataType::Float32 => CanDataValue::Float32(float_value(value)?),
+        CanDataType::Boolean => match value {
+            ConfigScalar::Bool(b) => CanDataValue::Boolean(*b),
+            _ => match integer_value(value)? {
+                0 => CanDataValue::Boolean(false),
+                1 => CanDataValue::Boolean(true),
+                v => {
+                    return Err(format!(
+                        "value {v} is not a valid Boolean (expected true, false, 0 or 1)"
+                    ))
+                }
+            },
+        },
+        CanDataType::Int32 => {
+            let v = integer_value(value)?;
+            CanDataValue::Int32(i32::try_from(v).map_err(|_| out_of_range(v))?)
+        }
+        CanDataType::Int16 => {
+            let v = integer_value(value)?;
+            CanDataValue::Int16(i16::try_from(v).map_err(|_| out_of_range(v))?)
+        }
+        CanDataType::Int8 => {
+            let v = integer_value(value)?;
+            CanDataValue::Int8(i8::try_from(v).map_err(|_| out_of_range(v))?)
+        }
+        CanDataType::UInt32 => {
+            let v = integer_value(value)?;
+            CanDataValue::UInt32(u32::try_from(v).map_err(|_| out_of_range(v))?)
+        }
+        CanDataType::UInt16 => {
+            let v = integer_value(value)?;
+            CanDataValue::UInt16(u16::try_from(v).map_err(|_| out_of_range(v))?)
+        }
+        CanDataType::UInt8 => {
+            let v = integer_value(value)?;
+            CanDataValue::UInt8(u8::try_from(v).map_err(|_| out_of_range(v))?)
+        }
+    })
+}
+
+/// The numeric value of `value`, with booleans as 0 and 1. Raw values have none.
+pub fn value_to_f64(value: &CanDataValue) -> Option<f64> {
+    Some(match value {
+        CanDataValue::Float32(v) => f64::from(*v),
+        CanDataValue::Int32(v) => f64::from(*v),
+        CanDataValue::Int16(v) => f64::from(*v),
+        CanDataValue::Int8(v) => f64::from(*v),
+        CanDataValue::UInt32(v) => f64::from(*v),
+        CanDataValue::UInt16(v) => f64::from(*v),
+        CanDataValue::UInt8(v) => f64::from(*v),
+        CanDataValue::Boolean(v) => f64::from(u8::from(*v)),
+        CanDataValue::Raw(_) => return None,
+    })
+}
+
+fn integer_value(value: &ConfigScalar) -> Result<i128, String> {
+    match value {
+        ConfigScalar::Int(v) => Ok(*v as i128),
+        ConfigScalar::Float(f) => {
+            if f.fract() == 0.0 && f.abs() < 1e38 {
+                Ok(*f as i128)
+            } else {
+                Err(format!("value {f} is not a whole number"))
+            }
+        }
+        ConfigScalar::Bool(b) => Err(format!("boolean {b} given for an integer field")),
+        ConfigScalar::String(s) => parse_prefixed_i128(s),
+    }
+}
+
+fn float_value(value: &ConfigScalar) -> Result<f32, String> {
+    let v = match value {
+        ConfigScalar::Int(v) => *v as f64,
+        ConfigScalar::Float(f) => *f,
+        ConfigScalar::Bool(b) => return Err(format!("boolean {b} given for a Float32 field")),
+        ConfigScalar::String(s) => {
+            let lower = s.to_ascii_lowercase();
+            if lower.starts_with("0x") || lower.starts_with("0b") {
+                let bits = parse_prefixed_biguint(s)?
+                    .to_u32()
+                    .ok_or_else(|| format!("bit pattern {s} does not fit in 32 bits"))?;
+                return Ok(f32::from_bits(bits));
+            }
+            s.trim()
+                .parse::<f64>()
+                .map_err(|_| format!("expecting a floating point number but got {s}"))?
+        }
+    };
+    if v.is_finite() && v.abs() > f32::MAX as f64 {
+        return Err(format!("value {v} does not fit in Float32"));
+    }
+    Ok(v as f32)
+}
+
+fn parse_prefixed_i128(s: &str) -> Result<i128, String> {
+    let trimmed = s.trim();
+    let (negative, magnitude) = match trimmed.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, trimmed),
+    };
+    let magnitude = parse_prefixed_biguint(magnitude)?
+        .to_i128()
+        .ok_or_else(|| format!("value {s} is too large"))?;
+    Ok(if negative { -magnitude } else { magnitude })
+}
+
 fn parse_prefixed_biguint(s: &str) -> Result<BigUint, String> {
     let s = s.replace('_', "");
     if s.starts_with("-") {
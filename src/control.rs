@@ -0,0 +1,276 @@
+//! Runtime control of running emulators over a Unix domain socket.
+//!
+//! Clients send one JSON request per line, e.g.
+//! `{"op": "set_telemetry", "node": 3, "name": "chamber_pressure", "value": 35}`,
+//! and get one JSON response per line: `{"ok": true, "result": ...}` or
+//! `{"ok": false, "error": "..."}`. `node` may be left out while only one node runs.
+
+use crate::config::config_representation::ConfigScalar;
+use crate::config::serde_deserializer::typed_value;
+use crate::emulator::EmulatorHandle;
+use crate::message_handling::{build_status_message, StatusMessageKind, MAX_STATUS_LEN};
+use crate::simulation::actuator;
+use crate::value_format::value_json;
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Request {
+    ListFields {
+        node: Option<u8>,
+    },
+    GetTelemetry {
+        node: Option<u8>,
+        name: String,
+    },
+    /// Pins a telemetry value; a generator or expression configured for it stops driving it.
+    SetTelemetry {
+        node: Option<u8>,
+        name: String,
+        value: ConfigScalar,
+    },
+    GetParameter {
+        node: Option<u8>,
+        name: String,
+    },
+    SetParameter {
+        node: Option<u8>,
+        name: String,
+        value: Option<ConfigScalar>,
+        locked: Option<bool>,
+    },
+    Reregister {
+        node: Option<u8>,
+    },
+    SendStatus {
+        node: Option<u8>,
+        level: StatusMessageKind,
+        message: String,
+    },
+    /// Counters of ignored frames and of received heartbeats.
+    Stats {
+        node: Option<u8>,
+    },
+    /// Reboots the node; `boot_time` and `config` override its `[Reboot]` table.
+    Reboot {
+        node: Option<u8>,
+        boot_time: Option<f64>,
+        config: Option<PathBuf>,
+    },
+}
+
+impl Request {
+    fn node(&self) -> Option<u8> {
+        match self {
+            Request::ListFields { node }
+            | Request::GetTelemetry { node, .. }
+            | Request::SetTelemetry { node, .. }
+            | Request::GetParameter { node, .. }
+            | Request::SetParameter { node, .. }
+            | Request::Reregister { node }
+            | Request::SendStatus { node, .. }
+            | Request::Stats { node }
+            | Request::Reboot { node, .. } => *node,
+        }
+    }
+}
+
+fn select_node(handles: &[EmulatorHandle], node: Option<u8>) -> Result<&EmulatorHandle> {
+    match (node, handles) {
+        (None, [handle]) => Ok(handle),
+        (None, _) => bail!("several nodes are running, the request needs a `node`"),
+        (Some(node_id), _) => handles
+            .iter()
+            .find(|handle| handle.node_id() == node_id)
+            .ok_or_else(|| anyhow!("no node with id {node_id}")),
+    }
+}
+
+fn list_fields(handle: &EmulatorHandle) -> Value {
+    handle.with_data(|data| {
+        let telemetry: Vec<Value> = data
+            .telemetry_values
+            .iter()
+            .flatten()
+            .map(|tel| {
+                json!({
+                    "name": tel.name,
+                    "datatype": format!("{:?}", tel.datatype),
+                    "value": value_json(&tel.value),
+                    "generated": tel.generator.is_some() || tel.expr.is_some(),
+                })
+            })
+            .collect();
+        let parameters: Vec<Value> = data
+            .parameters
+            .iter()
+            .flatten()
+            .map(|param| {
+                json!({
+                    "name": param.name,
+                    "datatype": format!("{:?}", param.datatype),
+                    "value": value_json(&param.value),
+                    "locked": param.locked,
+                })
+            })
+            .collect();
+        json!({ "telemetry": telemetry, "parameters": parameters })
+    })
+}
+
+fn stats(handle: &EmulatorHandle) -> Value {
+    let dropped = handle.dropped_frames();
+    let heartbeats = handle.heartbeat_stats();
+    json!({
+        "dropped_frames": {
+            "not_addressed": dropped.not_addressed,
+            "non_fd": dropped.non_fd,
+            "extended_id": dropped.extended_id,
+            "malformed": dropped.malformed,
+        },
+        "heartbeats": {
+            "received": heartbeats.received,
+            "gaps": heartbeats.gaps,
+            "missed": heartbeats.missed,
+            "repeats": heartbeats.repeats,
+            "resets": heartbeats.resets,
+        },
+    })
+}
+
+fn execute(handles: &[EmulatorHandle], request: Request) -> Result<Value> {
+    let handle = select_node(handles, request.node())?;
+    match request {
+        Request::ListFields { .. } => Ok(list_fields(handle)),
+        Request::GetTelemetry { name, .. } => handle
+            .telemetry_value(&name)
+            .map(|value| value_json(&value))
+            .ok_or_else(|| anyhow!("Unknown telemetry value {name}")),
+        Request::SetTelemetry { name, value, .. } => handle.with_data(|data| {
+            if let Some(model) = actuator::driving_model(data, &name) {
+                bail!("Telemetry value {name} is driven by model {}", model.name);
+            }
+            let tel = data
+                .telemetry_values
+                .iter_mut()
+                .flatten()
+                .find(|tel| tel.name == name)
+                .ok_or_else(|| anyhow!("Unknown telemetry value {name}"))?;
+            tel.value = typed_value(&value, tel.datatype).map_err(|e| anyhow!(e))?;
+            tel.generator = None;
+            tel.expr = None;
+            Ok(value_json(&tel.value))
+        }),
+        Request::GetParameter { name, .. } => handle.with_data(|data| {
+            let param = data
+                .parameters
+                .iter()
+                .flatten()
+                .find(|param| param.name == name)
+                .ok_or_else(|| anyhow!("Unknown parameter {name}"))?;
+            Ok(json!({ "value": value_json(&param.value), "locked": param.locked }))
+        }),
+        Request::SetParameter {
+            name,
+            value,
+            locked,
+            ..
+        } => handle.with_data(|data| {
+            let param = data
+                .parameters
+                .iter_mut()
+                .flatten()
+                .find(|param| param.name == name)
+                .ok_or_else(|| anyhow!("Unknown parameter {name}"))?;
+            if let Some(value) = value {
+                let value = typed_value(&value, param.datatype).map_err(|e| anyhow!(e))?;
+                param.check_write(&value).map_err(|e| anyhow!(e))?;
+                param.value = value;
+            }
+            if let Some(locked) = locked {
+                param.locked = locked;
+            }
+            let result = json!({ "value": value_json(&param.value), "locked": param.locked });
+            actuator::parameter_changed(data, &name);
+            Ok(result)
+        }),
+        Request::Reregister { .. } => {
+            handle.request_registration();
+            Ok(Value::Null)
+        }
+        Request::SendStatus { level, message, .. } => {
+            if message.len() > MAX_STATUS_LEN {
+                bail!("status message is longer than {MAX_STATUS_LEN} bytes");
+            }
+            handle.send_to_servers(vec![build_status_message(level, &message)]);
+            Ok(Value::Null)
+        }
+        Request::Stats { .. } => Ok(stats(handle)),
+        Request::Reboot {
+            boot_time, config, ..
+        } => {
+            handle.reboot(boot_time, config.as_deref())?;
+            Ok(Value::Null)
+        }
+    }
+}
+
+/// Executes one request line and returns the response line.
+pub fn handle_request(handles: &[EmulatorHandle], line: &str) -> String {
+    let result = serde_json::from_str::<Request>(line)
+        .map_err(|e| anyhow!("invalid request: {e}"))
+        .and_then(|request| execute(handles, request));
+    match result {
+        Ok(result) => json!({ "ok": true, "result": result }),
+        Err(err) => json!({ "ok": false, "error": format!("{err:#}") }),
+    }
+    .to_string()
+}
+
+fn serve_client(handles: &[EmulatorHandle], stream: UnixStream) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        writeln!(writer, "{}", handle_request(handles, &line))?;
+    }
+    Ok(())
+}
+
+/// Listens on `path` and serves every client on its own thread. A stale socket file left by an
+/// earlier run is replaced.
+pub fn spawn(path: &Path, handles: Vec<EmulatorHandle>) -> io::Result<JoinHandle<()>> {
+    if fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket()) {
+        fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    let handles = Arc::new(handles);
+    Ok(thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    eprintln!("Error accepting control connection: {err:?}");
+                    continue;
+                }
+            };
+            let handles = Arc::clone(&handles);
+            thread::spawn(move || {
+                if let Err(err) = serve_client(&handles, stream) {
+                    eprintln!("Error serving control connection: {err:?}");
+                }
+            });
+        }
+    }))
+}
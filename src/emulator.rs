@@ -0,0 +1,721 @@
+use crate::alarms;
+use crate::can_manager::socket_manager;
+use crate::can_manager::transport::CanTransport;
+use crate::config::config_representation::EmulatorData;
+use crate::config::parameter_state;
+use crate::faults::FaultInjector;
+use crate::message_handling::errors::ParseFrameError;
+use crate::message_handling::routing::route_messages;
+use crate::message_handling::{
+    build_status_message, handle_message, parse_can_message, registration_flow_messages,
+    truncate_status_text, typed_from_value, Event, HeartbeatStats, HeartbeatTracker,
+    StatusMessageKind, TelemetrySchedule, BROADCAST_ID,
+};
+use crate::reboot::Reboot;
+use crate::reload::{self, ConfigChange, ConfigValues};
+use crate::scenario::ScenarioRunner;
+use crate::simulation;
+use crate::simulation::actuator;
+use crate::simulation::derived::evaluation_order;
+use crate::watchdog::HeartbeatMonitor;
+use anyhow::{anyhow, Result};
+use liquidcan::payloads::CanDataValue;
+use liquidcan::CanMessage;
+use socketcan::{CanFdFrame, ShouldRetry};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+const MIN_READ_TIMEOUT: Duration = Duration::from_micros(100);
+const MAX_READ_TIMEOUT: Duration = Duration::from_millis(50);
+const DROP_LOG_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Received frames the emulator ignored, by reason.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DroppedFrames {
+    /// Frames addressed to another node that got past the receiver filter.
+    pub not_addressed: u64,
+    /// Classic CAN, remote and error frames.
+    pub non_fd: u64,
+    pub extended_id: u64,
+    /// CAN FD frames that do not hold a valid LiquidCAN message.
+    pub malformed: u64,
+}
+
+impl DroppedFrames {
+    fn count(&mut self, err: &ParseFrameError) {
+        match err {
+            ParseFrameError::NonFdFrame { .. } => self.non_fd += 1,
+            ParseFrameError::ExtendedId { .. } => self.extended_id += 1,
+            ParseFrameError::Malformed { .. } => self.malformed += 1,
+        }
+    }
+}
+
+/// Logs at most one unparseable frame per interval and reports how many were left out.
+struct DropLog {
+    last_logged: Option<Instant>,
+    suppressed: u64,
+}
+
+impl DropLog {
+    fn log(&mut self, err: &ParseFrameError) {
+        let now = Instant::now();
+        if self
+            .last_logged
+            .is_some_and(|last| now.duration_since(last) < DROP_LOG_INTERVAL)
+        {
+            self.suppressed += 1;
+            return;
+        }
+        if self.suppressed > 0 {
+            eprintln!(
+                "Dropped frame: {err} ({} more dropped since last report)",
+                self.suppressed
+            );
+        } else {
+            eprintln!("Dropped frame: {err}");
+        }
+        self.last_logged = Some(now);
+        self.suppressed = 0;
+    }
+}
+
+/// An emulated ECU attached to a CAN transport.
+///
+/// The emulator announces itself on the first [`Emulator::step`], then sends telemetry group
+/// updates on schedule and answers requests addressed to it.
+pub struct Emulator<T: CanTransport> {
+    transport: T,
+    data: Arc<Mutex<EmulatorData>>,
+    shutdown: Arc<AtomicBool>,
+    dropped_frames: Arc<Mutex<DroppedFrames>>,
+    heartbeats: Arc<Mutex<HeartbeatTracker>>,
+    outbox: Arc<Mutex<Vec<CanMessage>>>,
+    reloaded: Arc<AtomicBool>,
+    reboot: Arc<Mutex<Option<Reboot>>>,
+    /// Parameter values and lock states of the config, which a reboot falls back to.
+    defaults: Arc<Mutex<ConfigValues>>,
+    /// Parameter values and lock states as last written to the state file.
+    saved_parameters: Vec<(CanDataValue, bool)>,
+    drop_log: DropLog,
+    schedule: TelemetrySchedule,
+    /// Order the telemetry expressions are evaluated in, worked out with the schedule.
+    expression_order: Vec<usize>,
+    scenario: Option<ScenarioRunner>,
+    faults: FaultInjector,
+    watchdog: HeartbeatMonitor,
+    start: Instant,
+    registered: bool,
+    /// When a rebooting node has booted and speaks again.
+    booting_until: Option<Instant>,
+}
+
+/// Shared access to a running [`Emulator`], usable from other threads.
+#[derive(Clone)]
+pub struct EmulatorHandle {
+    data: Arc<Mutex<EmulatorData>>,
+    shutdown: Arc<AtomicBool>,
+    dropped_frames: Arc<Mutex<DroppedFrames>>,
+    heartbeats: Arc<Mutex<HeartbeatTracker>>,
+    outbox: Arc<Mutex<Vec<CanMessage>>>,
+    reloaded: Arc<AtomicBool>,
+    reboot: Arc<Mutex<Option<Reboot>>>,
+    defaults: Arc<Mutex<ConfigValues>>,
+}
+
+impl<T: CanTransport> Emulator<T> {
+    pub fn new(mut emulator_data: EmulatorData, mut transport: T) -> Self {
+        let defaults = ConfigValues::of(&emulator_data);
+        restore_parameter_state(&mut emulator_data);
+        if emulator_data.kernel_filter {
+            let node_id = emulator_data.node_id as u8;
+            if let Err(err) = transport.set_receiver_filter(&[node_id, BROADCAST_ID]) {
+                eprintln!("Error setting receiver filter, filtering in user space: {err:?}");
+            }
+        }
+        let start = Instant::now();
+        let schedule = TelemetrySchedule::new(&emulator_data, start);
+        let expression_order = expression_order(&emulator_data);
+        let saved_parameters = parameter_snapshot(&emulator_data);
+        Self {
+            transport,
+            data: Arc::new(Mutex::new(emulator_data)),
+            shutdown: Arc::new(AtomicBool::new(false)),
+            dropped_frames: Arc::new(Mutex::new(DroppedFrames::default())),
+            heartbeats: Arc::new(Mutex::new(HeartbeatTracker::new())),
+            outbox: Arc::new(Mutex::new(Vec::new())),
+            reloaded: Arc::new(AtomicBool::new(false)),
+            reboot: Arc::new(Mutex::new(None)),
+            defaults: Arc::new(Mutex::new(defaults)),
+            saved_parameters,
+            drop_log: DropLog {
+                last_logged: None,
+                suppressed: 0,
+            },
+            schedule,
+            expression_order,
+            scenario: None,
+            faults: FaultInjector::new(),
+            watchdog: HeartbeatMonitor::new(),
+            start,
+            registered: false,
+            booting_until: None,
+        }
+    }
+
+    pub fn handle(&self) -> EmulatorHandle {
+        EmulatorHandle {
+            data: Arc::clone(&self.data),
+            shutdown: Arc::clone(&self.shutdown),
+            dropped_frames: Arc::clone(&self.dropped_frames),
+            heartbeats: Arc::clone(&self.heartbeats),
+            outbox: Arc::clone(&self.outbox),
+            reloaded: Arc::clone(&self.reloaded),
+            reboot: Arc::clone(&self.reboot),
+            defaults: Arc::clone(&self.defaults),
+        }
+    }
+
+    /// Runs `scenario` on the emulator's clock, which starts when the emulator is created.
+    pub fn set_scenario(&mut self, scenario: ScenarioRunner) {
+        self.scenario = Some(scenario);
+    }
+
+    /// Runs a single iteration: sends due telemetry, then waits for at most one incoming frame
+    /// and answers it. Waits no longer than until the next telemetry group is due.
+    pub fn step(&mut self) {
+        self.step_until(None);
+    }
+
+    /// Steps until `deadline` has passed or the emulator was shut down.
+    pub fn run_until(&mut self, deadline: Instant) {
+        while !self.is_shut_down() && Instant::now() < deadline {
+            self.step_until(Some(deadline));
+        }
+    }
+
+    /// Steps until the emulator was shut down.
+    pub fn run(&mut self) {
+        while !self.is_shut_down() {
+            self.step_until(None);
+        }
+    }
+
+    /// Stops [`Emulator::run`] and [`Emulator::run_until`] after the current step.
+    pub fn shutdown(&self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_shut_down(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+
+    fn data(&self) -> MutexGuard<'_, EmulatorData> {
+        lock(&self.data)
+    }
+
+    fn step_until(&mut self, deadline: Option<Instant>) {
+        if self.boot(deadline) {
+            return;
+        }
+        if !self.registered {
+            self.registered = true;
+            let (sender_id, messages) = {
+                let data = self.data();
+                let messages = registration_flow_messages(&data);
+                (
+                    data.node_id as u8,
+                    route_messages(&data, &data.server_ids, messages),
+                )
+            };
+            self.send(sender_id, messages);
+        }
+
+        if self.reloaded.swap(false, Ordering::SeqCst) {
+            let (schedule, expression_order) = {
+                let data = self.data();
+                (
+                    TelemetrySchedule::new(&data, Instant::now()),
+                    expression_order(&data),
+                )
+            };
+            self.schedule = schedule;
+            self.expression_order = expression_order;
+        }
+        self.run_scenario();
+        if self.booting_until.is_some() {
+            // A scenario step rebooted the node.
+            return;
+        }
+        self.run_watchdog();
+        self.send_held_frames();
+        self.save_parameter_state();
+        self.send_outbox();
+        self.send_due_telemetry();
+        self.run_alarms();
+
+        // Wake up in time for the next telemetry group, scenario step, held back frame or
+        // watchdog timeout, but poll the bus at least every 50ms.
+        let now = Instant::now();
+        let mut timeout = self
+            .schedule
+            .time_until_next(now)
+            .unwrap_or(MAX_READ_TIMEOUT)
+            .min(MAX_READ_TIMEOUT);
+        let t = now.duration_since(self.start).as_secs_f64();
+        let scenario_next = self
+            .scenario
+            .as_ref()
+            .and_then(|scenario| scenario.time_until_next(t));
+        let watchdog_next = self.watchdog.time_until_next(t, &self.data());
+        for next in scenario_next
+            .into_iter()
+            .chain(self.faults.time_until_next(t))
+            .chain(watchdog_next)
+        {
+            timeout = timeout.min(Duration::from_secs_f64(next));
+        }
+        if let Some(deadline) = deadline {
+            timeout = timeout.min(deadline.saturating_duration_since(now));
+        }
+        let timeout = timeout.max(MIN_READ_TIMEOUT);
+
+        let res = socket_manager::read_frame(&mut self.transport, timeout);
+        let Ok(frame) = res else {
+            if !res.should_retry() {
+                eprintln!("Error reading CAN FD frame: {:?}", res.err().unwrap());
+            }
+            return;
+        };
+        let (id, msg) = match parse_can_message(frame) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                lock(&self.dropped_frames).count(&err);
+                self.drop_log.log(&err);
+                return;
+            }
+        };
+
+        let t = self.start.elapsed().as_secs_f64();
+        let (sender_id, responses, alerts, events) = {
+            let mut data = lock(&self.data);
+            let node_id = data.node_id as u8;
+            // Other nodes may share the bus; only answer frames addressed to this node.
+            if id.receiver_id() != node_id && id.receiver_id() != BROADCAST_ID {
+                lock(&self.dropped_frames).not_addressed += 1;
+                return;
+            }
+            let copies = self.faults.receive(t, &data, id.sender_id(), &msg);
+            let mut alerts = Vec::new();
+            let mut responses = Vec::new();
+            let mut events = Vec::new();
+            for _ in 0..copies {
+                if let CanMessage::HeartbeatReq { payload } = &msg {
+                    let anomaly = lock(&self.heartbeats).track(id.sender_id(), payload.counter);
+                    if let Some(anomaly) = anomaly {
+                        let text = anomaly.describe(id.sender_id());
+                        let status = StatusMessageKind::Warning;
+                        alerts.push(build_status_message(status, truncate_status_text(&text)));
+                    }
+                }
+                let handled = handle_message(&msg, &mut data);
+                events.extend(Event::of_exchange(&msg, &handled, &data));
+                responses.extend(handled);
+            }
+            // Only the servers' heartbeats keep the node out of its safe state.
+            let from_server = data.server_ids.contains(&id.sender_id());
+            if copies > 0 && from_server && matches!(msg, CanMessage::HeartbeatReq { .. }) {
+                alerts.extend(self.watchdog.heartbeat(t, &mut data));
+            }
+            for event in &events {
+                alerts.extend(alarms::on_event(&data, event));
+            }
+            (
+                node_id,
+                route_messages(&data, &[id.sender_id()], responses),
+                route_messages(&data, &data.server_ids, alerts),
+                events,
+            )
+        };
+        self.send(sender_id, responses);
+        self.send(sender_id, alerts);
+        if let Some(scenario) = self.scenario.as_mut() {
+            for event in &events {
+                scenario.notify(event, t);
+            }
+        }
+        self.save_parameter_state();
+    }
+
+    /// Starts a requested reboot and keeps the node silent until it has booted; frames received
+    /// meanwhile are lost. Returns whether the node is still booting.
+    fn boot(&mut self, deadline: Option<Instant>) -> bool {
+        let reboot = lock(&self.reboot).take();
+        if let Some(reboot) = reboot {
+            self.begin_reboot(reboot);
+        }
+        let Some(until) = self.booting_until else {
+            return false;
+        };
+        let now = Instant::now();
+        if now >= until {
+            self.booting_until = None;
+            let (schedule, expression_order) = {
+                let data = self.data();
+                (TelemetrySchedule::new(&data, now), expression_order(&data))
+            };
+            self.schedule = schedule;
+            self.expression_order = expression_order;
+            return false;
+        }
+        let mut wake = until.min(now + MAX_READ_TIMEOUT);
+        if let Some(deadline) = deadline {
+            wake = wake.min(deadline);
+        }
+        let timeout = wake.saturating_duration_since(now).max(MIN_READ_TIMEOUT);
+        let _ = socket_manager::read_frame(&mut self.transport, timeout);
+        true
+    }
+
+    /// Resets the node's parameters or swaps its config, and drops everything it was about to
+    /// send. The registration flow goes out once the boot time is over.
+    fn begin_reboot(&mut self, reboot: Reboot) {
+        let mut data = lock(&self.data);
+        match reboot.config {
+            Some(mut config) => {
+                *lock(&self.defaults) = ConfigValues::of(&config);
+                restore_parameter_state(&mut config);
+                *data = config;
+            }
+            None => {
+                reset_parameters(&mut data, &lock(&self.defaults));
+                restore_parameter_state(&mut data);
+            }
+        }
+        self.saved_parameters = parameter_snapshot(&data);
+        println!(
+            "Node {} rebooting for {:.3}s",
+            data.node_id,
+            reboot.boot_time.as_secs_f64()
+        );
+        drop(data);
+        lock(&self.outbox).clear();
+        // Servers' heartbeat counters start over with the node's.
+        lock(&self.heartbeats).forget_counters();
+        self.faults = FaultInjector::new();
+        self.watchdog = HeartbeatMonitor::new();
+        self.registered = false;
+        self.booting_until = Some(Instant::now() + reboot.boot_time);
+    }
+
+    /// Sends each message to the node it is paired with, through the node's faults.
+    fn send(&mut self, sender_id: u8, messages: Vec<(u8, CanMessage)>) {
+        let t = self.start.elapsed().as_secs_f64();
+        let frames = self
+            .faults
+            .transmit(t, &lock(&self.data), sender_id, messages);
+        self.send_frames(frames);
+    }
+
+    /// Sends the frames a delay or reorder fault held back once they are due.
+    fn send_held_frames(&mut self) {
+        let frames = self.faults.release_due(self.start.elapsed().as_secs_f64());
+        self.send_frames(frames);
+    }
+
+    fn send_frames(&mut self, frames: Vec<(u8, CanFdFrame)>) {
+        for (receiver_id, frame) in frames {
+            if let Err(err) = self.transport.send_frame(&frame) {
+                eprintln!("Error sending CAN FD frame to node {receiver_id}: {err:?}");
+            }
+        }
+    }
+
+    /// Runs the scenario steps that are due and sends their messages to the servers.
+    fn run_scenario(&mut self) {
+        let Some(scenario) = self.scenario.as_mut() else {
+            return;
+        };
+        let t = self.start.elapsed().as_secs_f64();
+        let (sender_id, messages) = {
+            let mut data = lock(&self.data);
+            let messages = scenario.advance(t, &mut data, &mut self.faults);
+            (
+                data.node_id as u8,
+                route_messages(&data, &data.server_ids, messages),
+            )
+        };
+        let reboot = scenario.take_reboot();
+        self.send(sender_id, messages);
+        if let Some(request) = reboot {
+            match Reboot::load(&self.data, request.boot_time, request.config.as_deref()) {
+                Ok(reboot) => self.begin_reboot(reboot),
+                Err(err) => eprintln!("Error running scenario step on node {sender_id}: {err:#}"),
+            }
+        }
+    }
+
+    /// Enters the safe state when the servers' heartbeats stopped and tells the servers.
+    fn run_watchdog(&mut self) {
+        let t = self.start.elapsed().as_secs_f64();
+        let (sender_id, messages) = {
+            let mut data = lock(&self.data);
+            let messages = self.watchdog.check(t, &mut data);
+            (
+                data.node_id as u8,
+                route_messages(&data, &data.server_ids, messages),
+            )
+        };
+        self.send(sender_id, messages);
+    }
+
+    /// Sends the status messages of the alarm thresholds crossed since the last step.
+    fn run_alarms(&mut self) {
+        let (sender_id, messages) = {
+            let mut data = lock(&self.data);
+            let messages = alarms::check_thresholds(&mut data);
+            (
+                data.node_id as u8,
+                route_messages(&data, &data.server_ids, messages),
+            )
+        };
+        self.send(sender_id, messages);
+    }
+
+    /// Writes the parameters to the state file if they changed since they were last written.
+    fn save_parameter_state(&mut self) {
+        let data = lock(&self.data);
+        let Some(path) = data.state_file.as_deref() else {
+            return;
+        };
+        let snapshot = parameter_snapshot(&data);
+        if snapshot == self.saved_parameters {
+            return;
+        }
+        let parameters = data.parameters.as_deref().unwrap_or_default();
+        if let Err(err) = parameter_state::save(path, parameters) {
+            eprintln!("Error saving parameter state: {err:?}");
+        }
+        self.saved_parameters = snapshot;
+    }
+
+    fn send_outbox(&mut self) {
+        let messages = std::mem::take(&mut *lock(&self.outbox));
+        if messages.is_empty() {
+            return;
+        }
+        let (sender_id, messages) = {
+            let data = self.data();
+            (
+                data.node_id as u8,
+                route_messages(&data, &data.server_ids, messages),
+            )
+        };
+        self.send(sender_id, messages);
+    }
+
+    fn send_due_telemetry(&mut self) {
+        let now = Instant::now();
+        let due_groups = self.schedule.due_groups(now);
+        if due_groups.is_empty() {
+            return;
+        }
+        let (sender_id, updates) = {
+            let mut data = self.data();
+            simulation::update_telemetry(
+                &mut data,
+                &self.expression_order,
+                now.duration_since(self.start),
+            );
+            let updates = due_groups
+                .into_iter()
+                .filter_map(|group_id| self.schedule.update(&data, group_id))
+                .collect();
+            (
+                data.node_id as u8,
+                route_messages(&data, &data.server_ids, updates),
+            )
+        };
+        self.send(sender_id, updates);
+    }
+}
+
+impl EmulatorHandle {
+    /// Runs `f` with exclusive access to the emulator's data.
+    pub fn with_data<R>(&self, f: impl FnOnce(&mut EmulatorData) -> R) -> R {
+        f(&mut lock(&self.data))
+    }
+
+    pub fn telemetry_value(&self, name: &str) -> Option<CanDataValue> {
+        self.with_data(|data| {
+            data.telemetry_values
+                .iter()
+                .flatten()
+                .find(|tel| tel.name == name)
+                .map(|tel| tel.value.clone())
+        })
+    }
+
+    /// Overwrites a telemetry value. Telemetry with a generator, model or expression is
+    /// overwritten again on its next update.
+    pub fn set_telemetry_value(&self, name: &str, value: CanDataValue) -> Result<()> {
+        self.with_data(|data| {
+            let tel = data
+                .telemetry_values
+                .iter_mut()
+                .flatten()
+                .find(|tel| tel.name == name)
+                .ok_or_else(|| anyhow!("Unknown telemetry value {name}"))?;
+            tel.value = typed_from_value(&value, tel.datatype)
+                .ok_or_else(|| anyhow!("{value:?} is not a {:?}", tel.datatype))?;
+            Ok(())
+        })
+    }
+
+    pub fn parameter_value(&self, name: &str) -> Option<CanDataValue> {
+        self.with_data(|data| {
+            data.parameters
+                .iter()
+                .flatten()
+                .find(|param| param.name == name)
+                .map(|param| param.value.clone())
+        })
+    }
+
+    /// Overwrites a parameter value, regardless of its lock state.
+    pub fn set_parameter_value(&self, name: &str, value: CanDataValue) -> Result<()> {
+        self.with_data(|data| {
+            let param = data
+                .parameters
+                .iter_mut()
+                .flatten()
+                .find(|param| param.name == name)
+                .ok_or_else(|| anyhow!("Unknown parameter {name}"))?;
+            param.value = typed_from_value(&value, param.datatype)
+                .ok_or_else(|| anyhow!("{value:?} is not a {:?}", param.datatype))?;
+            actuator::parameter_changed(data, name);
+            Ok(())
+        })
+    }
+
+    pub fn node_id(&self) -> u8 {
+        self.with_data(|data| data.node_id as u8)
+    }
+
+    /// Queues messages for the servers; they go out on the emulator's next step.
+    pub fn send_to_servers(&self, messages: Vec<CanMessage>) {
+        lock(&self.outbox).extend(messages);
+    }
+
+    /// Queues the full registration flow, as if the server had sent a `NodeInfoReq`.
+    pub fn request_registration(&self) {
+        let messages = self.with_data(|data| registration_flow_messages(data));
+        self.send_to_servers(messages);
+    }
+
+    /// Applies a reloaded config to the running emulator. Values and lock states set at runtime
+    /// are kept unless the config changed that field. When the field layout changed, the
+    /// registration flow is sent again.
+    pub fn reload(&self, emulator_data: EmulatorData) -> Result<ConfigChange> {
+        let configured = ConfigValues::of(&emulator_data);
+        let change = self.with_data(|data| {
+            let changes = reload::compare(data, &lock(&self.defaults), &emulator_data)?;
+            reload::apply(data, emulator_data, &changes);
+            anyhow::Ok(changes.kind())
+        })?;
+        *lock(&self.defaults) = configured;
+        self.reloaded.store(true, Ordering::SeqCst);
+        if change == ConfigChange::Layout {
+            self.request_registration();
+        }
+        Ok(change)
+    }
+
+    /// Reboots the node on the emulator's next step. `boot_time` and the alternative `config`
+    /// override the node's `[Reboot]` table.
+    pub fn reboot(&self, boot_time: Option<f64>, config: Option<&Path>) -> Result<()> {
+        let reboot = Reboot::load(&self.data, boot_time, config)?;
+        *lock(&self.reboot) = Some(reboot);
+        Ok(())
+    }
+
+    /// Stops the emulator after its current step.
+    pub fn shutdown(&self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_shut_down(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+
+    pub fn dropped_frames(&self) -> DroppedFrames {
+        *lock(&self.dropped_frames)
+    }
+
+    pub fn heartbeat_stats(&self) -> HeartbeatStats {
+        lock(&self.heartbeats).stats()
+    }
+}
+
+/// Overwrites the parameter defaults with the node's state file, if it has one.
+fn restore_parameter_state(emulator_data: &mut EmulatorData) {
+    let (Some(path), Some(parameters)) = (
+        emulator_data.state_file.as_deref(),
+        emulator_data.parameters.as_deref_mut(),
+    ) else {
+        return;
+    };
+    if let Err(err) = parameter_state::restore(path, parameters) {
+        eprintln!("Error restoring parameter state, using the config defaults: {err:?}");
+    }
+}
+
+/// Puts the parameters back to their configured values and lock states.
+fn reset_parameters(emulator_data: &mut EmulatorData, defaults: &ConfigValues) {
+    let mut changed = Vec::new();
+    for param in emulator_data.parameters.iter_mut().flatten() {
+        let Some((value, locked)) = defaults.parameter(&param.name) else {
+            continue;
+        };
+        if param.value != *value {
+            param.value = value.clone();
+            changed.push(param.name.clone());
+        }
+        param.locked = *locked;
+    }
+    for name in changed {
+        actuator::parameter_changed(emulator_data, &name);
+    }
+}
+
+fn parameter_snapshot(emulator_data: &EmulatorData) -> Vec<(CanDataValue, bool)> {
+    emulator_data
+        .parameters
+        .iter()
+        .flatten()
+        .map(|param| (param.value.clone(), param.locked))
+        .collect()
+}
+
+/// The order `emulator_data`'s telemetry expressions are evaluated in. Loaded configs never
+/// have a cycle; data built otherwise with one leaves its expressions unevaluated.
+fn expression_order(emulator_data: &EmulatorData) -> Vec<usize> {
+    let telemetry = emulator_data
+        .telemetry_values
+        .as_deref()
+        .unwrap_or_default();
+    evaluation_order(telemetry).unwrap_or_else(|err| {
+        eprintln!(
+            "Error on node {}: {err}, not evaluating expressions",
+            emulator_data.node_id
+        );
+        Vec::new()
+    })
+}
+
+pub(crate) fn lock<D>(data: &Mutex<D>) -> MutexGuard<'_, D> {
+    // A panic while holding the lock leaves the data consistent enough to keep emulating.
+    data.lock().unwrap_or_else(|e| e.into_inner())
+}
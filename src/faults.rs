@@ -0,0 +1,439 @@
+//! Faults injected into an emulator's traffic to test how servers cope with a misbehaving node.
+//!
+//! Faults are configured per node in its `[Faults]` table and act from the start, or, with
+//! `enabled = false`, only while a scenario switches them on. Every frame a fault hits is logged.
+
+use crate::can_manager::make_message_id;
+use crate::can_manager::socket_manager::{build_frame, encode_frame};
+use crate::config::config_representation::EmulatorData;
+use crate::message_kind::MessageKind;
+use crate::simulation::rng::SplitMix64;
+use liquidcan::CanMessage;
+use serde::Deserialize;
+use socketcan::{CanFdFrame, EmbeddedFrame};
+use std::collections::HashMap;
+
+/// Seconds a reordered frame waits at most for a later frame to overtake it.
+const REORDER_HOLD: f64 = 0.1;
+/// Node IDs 1 to 30 a wrong sender ID is picked from.
+const NODE_IDS: u64 = 30;
+
+/// Which side of the emulator a fault sits on.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// Frames the emulator sends.
+    #[default]
+    Transmit,
+    /// Frames the emulator receives, before it handles them.
+    Receive,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FaultKind {
+    /// Loses the frame.
+    Drop,
+    /// Sends the frame `delay` seconds late.
+    Delay { delay: f64 },
+    /// Sends or handles the frame twice.
+    Duplicate,
+    /// Lets the next frame overtake this one.
+    Reorder,
+    /// Flips a random bit in `bytes` random payload bytes.
+    Corrupt {
+        #[serde(default = "default_corrupt_bytes")]
+        bytes: usize,
+    },
+    /// Sends the frame as node `sender_id`, or as a random other node.
+    WrongSender { sender_id: Option<u8> },
+    /// Sends nothing, as if the node stopped responding.
+    Silence,
+}
+
+impl FaultKind {
+    /// Whether the fault can act on received frames.
+    pub fn applies_on_receive(&self) -> bool {
+        matches!(self, FaultKind::Drop | FaultKind::Duplicate)
+    }
+}
+
+fn default_corrupt_bytes() -> usize {
+    1
+}
+
+fn default_percent() -> f64 {
+    100.0
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// A named fault from the node's config.
+#[derive(Deserialize, Debug)]
+pub struct Fault {
+    #[serde(skip)]
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: FaultKind,
+    /// Message kinds the fault hits; every kind when empty.
+    #[serde(default)]
+    pub messages: Vec<MessageKind>,
+    #[serde(default)]
+    pub direction: Direction,
+    /// Share of the matching frames the fault hits, in percent.
+    #[serde(default = "default_percent")]
+    pub percent: f64,
+    /// Active from the start. Disabled faults only act while a scenario switches them on.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    pub seed: Option<u64>,
+}
+
+impl Fault {
+    /// An enabled fault that hits every transmitted frame.
+    pub fn new(name: &str, kind: FaultKind) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+            messages: Vec::new(),
+            direction: Direction::Transmit,
+            percent: default_percent(),
+            enabled: true,
+            seed: None,
+        }
+    }
+
+    fn matches(&self, direction: Direction, kind: MessageKind) -> bool {
+        self.direction == direction && (self.messages.is_empty() || self.messages.contains(&kind))
+    }
+
+    fn log(&self, what: &str, kind: MessageKind, node_id: u8) {
+        println!("Fault {}: {what} {kind} for node {node_id}", self.name);
+    }
+}
+
+/// What the injector keeps about a fault between frames.
+#[derive(Debug, Default)]
+struct FaultState {
+    rng: Option<SplitMix64>,
+    /// End of the window a scenario switched the fault on for, in seconds since emulator start.
+    active_until: Option<f64>,
+}
+
+impl FaultState {
+    fn is_active(&self, fault: &Fault, t: f64) -> bool {
+        fault.enabled || self.active_until.is_some_and(|until| t < until)
+    }
+
+    fn rng(&mut self, seed: Option<u64>) -> &mut SplitMix64 {
+        self.rng.get_or_insert_with(|| {
+            seed.map(SplitMix64::new)
+                .unwrap_or_else(SplitMix64::from_time)
+        })
+    }
+
+    /// Rolls whether `fault` hits the current frame.
+    fn hits(&mut self, fault: &Fault) -> bool {
+        fault.percent >= 100.0 || self.rng(fault.seed).next_f64() * 100.0 < fault.percent
+    }
+}
+
+/// The state of `fault`, created on first use.
+fn state<'a>(states: &'a mut HashMap<String, FaultState>, fault: &Fault) -> &'a mut FaultState {
+    states.entry(fault.name.clone()).or_default()
+}
+
+/// A frame a delay or reorder fault keeps back.
+#[derive(Debug)]
+struct HeldFrame {
+    receiver_id: u8,
+    frame: CanFdFrame,
+    /// Seconds since emulator start the frame goes out at the latest.
+    due: f64,
+    /// Goes out right after the next frame that is sent normally.
+    reordered: bool,
+}
+
+/// Applies the faults of a node to the frames it sends and receives, and keeps their state and
+/// the frames they hold back.
+#[derive(Debug, Default)]
+pub struct FaultInjector {
+    /// Runtime state of the faults, by fault name.
+    states: HashMap<String, FaultState>,
+    /// Silence faults a scenario switched on besides the configured faults.
+    silences: Vec<Fault>,
+    /// Silence faults switched on so far, to name the next one.
+    silence_count: usize,
+    held: Vec<HeldFrame>,
+}
+
+impl FaultInjector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Switches the node's fault `name` on until `until` seconds since emulator start. Returns
+    /// `false` if the node has no such fault.
+    pub fn activate(&mut self, emulator_data: &EmulatorData, name: &str, until: f64) -> bool {
+        let fault = emulator_data
+            .faults
+            .iter()
+            .flatten()
+            .find(|fault| fault.name == name);
+        match fault {
+            Some(fault) => {
+                state(&mut self.states, fault).active_until = Some(until);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Switches on a silence fault for messages of `kinds`, or of every kind if it is empty,
+    /// until `until` seconds since emulator start.
+    pub fn silence(&mut self, kinds: Vec<MessageKind>, until: Option<f64>) {
+        self.silence_count += 1;
+        let mut fault = Fault::new(
+            &format!("scenario silence {}", self.silence_count),
+            FaultKind::Silence,
+        );
+        fault.messages = kinds;
+        fault.enabled = false;
+        state(&mut self.states, &fault).active_until = Some(until.unwrap_or(f64::INFINITY));
+        self.silences.push(fault);
+    }
+
+    /// Encodes the messages `sender_id` sends into frames, paired with their receiver, and
+    /// applies the faults active at `t` seconds since emulator start. Held back frames that are
+    /// due come first.
+    pub fn transmit(
+        &mut self,
+        t: f64,
+        emulator_data: &EmulatorData,
+        sender_id: u8,
+        messages: Vec<(u8, CanMessage)>,
+    ) -> Vec<(u8, CanFdFrame)> {
+        let states = &mut self.states;
+        self.silences.retain(|fault| {
+            let active = state(states, fault).is_active(fault, t);
+            if !active {
+                states.remove(&fault.name);
+            }
+            active
+        });
+        let mut frames = self.release_due(t);
+        for (receiver_id, msg) in messages {
+            let kind = MessageKind::of(&msg);
+            let mut frame = match encode_frame(make_message_id(receiver_id, sender_id), msg) {
+                Ok(frame) => frame,
+                Err(err) => {
+                    eprintln!("Error sending CAN FD frame to node {receiver_id}: {err:?}");
+                    continue;
+                }
+            };
+
+            let mut copies = 1;
+            let mut hold = None;
+            let faults = emulator_data.faults.iter().flatten().chain(&self.silences);
+            for fault in faults {
+                let state = state(&mut self.states, fault);
+                if !fault.matches(Direction::Transmit, kind)
+                    || !state.is_active(fault, t)
+                    || !state.hits(fault)
+                {
+                    continue;
+                }
+                match fault.kind {
+                    FaultKind::Drop => {
+                        fault.log("dropped", kind, receiver_id);
+                        copies = 0;
+                    }
+                    FaultKind::Silence => {
+                        fault.log("silenced", kind, receiver_id);
+                        copies = 0;
+                    }
+                    FaultKind::Delay { delay } => {
+                        fault.log(&format!("delayed by {delay}s"), kind, receiver_id);
+                        hold = Some((t + delay, false));
+                    }
+                    FaultKind::Duplicate => {
+                        fault.log("duplicated", kind, receiver_id);
+                        copies += 1;
+                    }
+                    FaultKind::Reorder => {
+                        fault.log("reordered", kind, receiver_id);
+                        hold = Some((t + REORDER_HOLD, true));
+                    }
+                    FaultKind::Corrupt { bytes } => {
+                        fault.log(&format!("corrupted {bytes} byte(s) of"), kind, receiver_id);
+                        frame = corrupt(&frame, bytes, state.rng(fault.seed));
+                    }
+                    FaultKind::WrongSender {
+                        sender_id: wrong_id,
+                    } => {
+                        let wrong_id = wrong_id
+                            .unwrap_or_else(|| other_node(sender_id, state.rng(fault.seed)));
+                        fault.log(&format!("sent as node {wrong_id}"), kind, receiver_id);
+                        let id = make_message_id(receiver_id, wrong_id);
+                        if let Ok(rebuilt) = build_frame(id, frame.data()) {
+                            frame = rebuilt;
+                        }
+                    }
+                }
+                if copies == 0 {
+                    break;
+                }
+            }
+
+            for _ in 0..copies {
+                match hold {
+                    Some((due, reordered)) => self.held.push(HeldFrame {
+                        receiver_id,
+                        frame,
+                        due,
+                        reordered,
+                    }),
+                    None => {
+                        frames.push((receiver_id, frame));
+                        frames.extend(self.release_reordered());
+                    }
+                }
+            }
+        }
+        frames
+    }
+
+    /// How many times a received message is handled under the faults active at `t` seconds
+    /// since emulator start: 0 when it is dropped, more than once when it is duplicated.
+    pub fn receive(
+        &mut self,
+        t: f64,
+        emulator_data: &EmulatorData,
+        sender_id: u8,
+        msg: &CanMessage,
+    ) -> usize {
+        let kind = MessageKind::of(msg);
+        let mut copies = 1;
+        for fault in emulator_data.faults.iter().flatten() {
+            let state = state(&mut self.states, fault);
+            if !fault.matches(Direction::Receive, kind)
+                || !state.is_active(fault, t)
+                || !state.hits(fault)
+            {
+                continue;
+            }
+            match fault.kind {
+                FaultKind::Drop => {
+                    fault.log("dropped received", kind, sender_id);
+                    return 0;
+                }
+                FaultKind::Duplicate => {
+                    fault.log("duplicated received", kind, sender_id);
+                    copies += 1;
+                }
+                _ => {}
+            }
+        }
+        copies
+    }
+
+    /// Held back frames due at `t` seconds since emulator start.
+    pub fn release_due(&mut self, t: f64) -> Vec<(u8, CanFdFrame)> {
+        let (due, held) = std::mem::take(&mut self.held)
+            .into_iter()
+            .partition(|held| held.due <= t);
+        self.held = held;
+        due.into_iter()
+            .map(|held: HeldFrame| (held.receiver_id, held.frame))
+            .collect()
+    }
+
+    /// Seconds from `t` until the next held back frame is due.
+    pub fn time_until_next(&self, t: f64) -> Option<f64> {
+        self.held
+            .iter()
+            .map(|held| (held.due - t).max(0.0))
+            .min_by(f64::total_cmp)
+    }
+
+    fn release_reordered(&mut self) -> Vec<(u8, CanFdFrame)> {
+        let (reordered, held) = std::mem::take(&mut self.held)
+            .into_iter()
+            .partition(|held| held.reordered);
+        self.held = held;
+        reordered
+            .into_iter()
+            .map(|held: HeldFrame| (held.receiver_id, held.frame))
+            .collect()
+    }
+}
+
+/// A copy of `frame` with a random bit flipped in `bytes` random payload bytes.
+fn corrupt(frame: &CanFdFrame, bytes: usize, rng: &mut SplitMix64) -> CanFdFrame {
+    let mut data = frame.data().to_vec();
+    if data.is_empty() {
+        return *frame;
+    }
+    // Distinct bytes, so two flips never cancel out.
+    let mut indices: Vec<usize> = (0..data.len()).collect();
+    for n in 0..bytes.min(data.len()) {
+        let pick = n + (rng.next_u64() % (data.len() - n) as u64) as usize;
+        indices.swap(n, pick);
+        data[indices[n]] ^= 1 << (rng.next_u64() % 8);
+    }
+    CanFdFrame::new(frame.id(), &data).unwrap_or(*frame)
+}
+
+/// A random node ID other than `node_id`.
+fn other_node(node_id: u8, rng: &mut SplitMix64) -> u8 {
+    let offset = 1 + rng.next_u64() % (NODE_IDS - 1);
+    (1 + (u64::from(node_id.saturating_sub(1)) + offset) % NODE_IDS) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use liquidcan::payloads;
+
+    #[test]
+    fn corruption_flips_one_bit_per_byte() {
+        let msg = CanMessage::HeartbeatRes {
+            payload: payloads::HeartbeatPayload { counter: 0 },
+        };
+        let frame = encode_frame(make_message_id(1, 3), msg).unwrap();
+        let mut rng = SplitMix64::new(7);
+        for _ in 0..20 {
+            let corrupted = corrupt(&frame, 1, &mut rng);
+            let flipped: u32 = frame
+                .data()
+                .iter()
+                .zip(corrupted.data())
+                .map(|(a, b)| (a ^ b).count_ones())
+                .sum();
+            assert_eq!(flipped, 1);
+            assert_eq!(corrupted.id(), frame.id());
+
+            let corrupted = corrupt(&frame, 2, &mut rng);
+            let changed = frame
+                .data()
+                .iter()
+                .zip(corrupted.data())
+                .filter(|(a, b)| a != b)
+                .count();
+            assert_eq!(changed, 2);
+        }
+    }
+
+    #[test]
+    fn wrong_senders_are_other_valid_nodes() {
+        let mut rng = SplitMix64::new(1);
+        for node_id in [2, 3, 30] {
+            for _ in 0..100 {
+                let other = other_node(node_id, &mut rng);
+                assert!((1..=30).contains(&other) && other != node_id, "{other}");
+            }
+        }
+    }
+}
@@ -1,5 +1,19 @@
 #![allow(non_snake_case)]
 
+pub mod alarms;
 pub mod can_manager;
 pub mod config;
+pub mod control;
+pub mod emulator;
+pub mod faults;
 pub mod message_handling;
+pub mod message_kind;
+pub mod reboot;
+pub mod reload;
+pub mod replay;
+pub mod scenario;
+pub mod server;
+pub mod simulation;
+pub mod sniffer;
+pub mod value_format;
+pub mod watchdog;
@@ -1,74 +1,346 @@
-use socketcan::{ShouldRetry, Socket};
-use std::env;
-use std::time::{Duration, Instant};
-use ECUEmulator::can_manager::{self, send_messages, socket_manager};
+use clap::{Args, Parser, Subcommand};
+use socketcan::ShouldRetry;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+use ECUEmulator::can_manager::candump;
+use ECUEmulator::can_manager::recording::{Recorder, RecordingTransport};
+use ECUEmulator::can_manager::socket_manager::{self, SocketCanTransport};
+use ECUEmulator::can_manager::transport::CanTransport;
 use ECUEmulator::config;
-use ECUEmulator::message_handling::{
-    build_telemetry_group_updates, handle_message, parse_can_message, registration_flow_messages,
-};
+use ECUEmulator::control;
+use ECUEmulator::emulator::Emulator;
+use ECUEmulator::message_handling::parse_can_message;
+use ECUEmulator::message_kind::MessageKind;
+use ECUEmulator::reboot;
+use ECUEmulator::reload;
+use ECUEmulator::replay::{replay, ReplayOptions};
+use ECUEmulator::scenario::Scenario;
+use ECUEmulator::server::{check_node, ServerOptions};
+use ECUEmulator::sniffer::{SniffFilter, Sniffer};
+
+#[derive(Parser)]
+#[command(about = "Emulates LiquidCAN ECUs on a CAN FD bus")]
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    run: RunArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the nodes of a config file (the default)
+    Run(RunArgs),
+    /// Play a candump log back onto the bus
+    Replay(ReplayArgs),
+    /// Print decoded LiquidCAN traffic of an interface
+    Sniff(SniffArgs),
+    /// Act as the LLServer towards one node and report its protocol conformance
+    Server(ServerArgs),
+}
+
+#[derive(Args)]
+struct RunArgs {
+    /// Config file with one node or a list of `[[nodes]]`
+    config: Option<String>,
+
+    /// Log every frame sent and received to this file in `candump -L` format
+    #[arg(long, value_name = "FILE")]
+    record: Option<PathBuf>,
+
+    /// Accept JSON control requests on this Unix socket
+    #[arg(long, value_name = "SOCKET")]
+    control: Option<PathBuf>,
+
+    /// Run the timed and event-triggered steps of this scenario file
+    #[arg(long, value_name = "FILE")]
+    scenario: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct ReplayArgs {
+    /// Log file in `candump -L` format
+    log: PathBuf,
+
+    /// Send every frame on this interface instead of the one it was recorded on
+    #[arg(long)]
+    interface: Option<String>,
+
+    /// Playback speed relative to the recording
+    #[arg(long, default_value_t = 1.0)]
+    speed: f64,
+
+    /// Only replay frames sent by this node ID
+    #[arg(long, value_name = "NODE_ID")]
+    node: Option<u8>,
+}
+
+#[derive(Args)]
+struct SniffArgs {
+    /// CAN interface to listen on
+    interface: String,
+
+    /// Only show frames sent by or addressed to this node ID
+    #[arg(long, value_name = "NODE_ID")]
+    node: Option<u8>,
+
+    /// Only show these message kinds, e.g. `telemetry_group_update` (repeatable)
+    #[arg(long, value_name = "KIND")]
+    kind: Vec<MessageKind>,
+
+    /// Print one JSON object per line instead of text
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Args)]
+struct ServerArgs {
+    /// CAN interface the node is on
+    interface: String,
+
+    /// ID of the node under test
+    #[arg(long, value_name = "NODE_ID")]
+    node: u8,
+
+    /// Node ID to send the requests from
+    #[arg(long, default_value_t = 1)]
+    server_id: u8,
+
+    /// Seconds to wait for each response
+    #[arg(long, default_value_t = 0.5)]
+    timeout: f64,
+
+    /// Number of heartbeat requests to send
+    #[arg(long, default_value_t = 3)]
+    heartbeats: u32,
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        eprintln!("Usage: {} <config_file_path>", args[0]);
-        std::process::exit(1);
+    let cli = Cli::parse();
+    match cli.command {
+        Some(Command::Run(args)) => run(args),
+        Some(Command::Replay(args)) => run_replay(args),
+        Some(Command::Sniff(args)) => run_sniff(args),
+        Some(Command::Server(args)) => run_server(args),
+        None => run(cli.run),
     }
-    let res = config::config_loader::load_config((args[1]).as_ref());
-    let Ok(mut config) = res else {
+}
+
+fn run(args: RunArgs) {
+    let Some(config_path) = args.config else {
+        eprintln!("Missing config file path, see --help");
+        std::process::exit(1);
+    };
+    let res = config::config_loader::load_nodes(&config_path);
+    let Ok(nodes) = res else {
         eprintln!("Error loading config file: {:?}", res.err().unwrap());
         return;
     };
 
-    let sender_id = config.node_id as u8;
+    let scenario = match args.scenario {
+        Some(path) => match Scenario::load(&path, &nodes) {
+            Ok(scenario) => Some(scenario),
+            Err(err) => {
+                eprintln!("Error loading scenario file: {err:?}");
+                return;
+            }
+        },
+        None => None,
+    };
 
-    let res = can_manager::socket_manager::open_socket(&config.can_interface);
+    let recorder = match args.record {
+        Some(path) => {
+            let local_senders = nodes.iter().map(|node| node.node_id as u8).collect();
+            match Recorder::create(&path, local_senders) {
+                Ok(recorder) => Some(recorder),
+                Err(err) => {
+                    eprintln!("Error creating CAN log {}: {err:?}", path.display());
+                    return;
+                }
+            }
+        }
+        None => None,
+    };
 
-    let Ok(mut socket) = res else {
-        eprintln!("Error opening CAN FD socket: :{:?}", res.err().unwrap());
+    // Every node gets its own socket, so nodes on the same interface see each other's frames
+    // just like separate ECUs on one bus.
+    let mut emulators = Vec::new();
+    for node in nodes {
+        let res = socket_manager::open_socket(&node.can_interface);
+        let Ok(socket) = res else {
+            eprintln!(
+                "Error opening CAN FD socket on {} for node {}: :{:?}",
+                node.can_interface,
+                node.node_id,
+                res.err().unwrap()
+            );
+            return;
+        };
+        let transport: Box<dyn CanTransport + Send> = match &recorder {
+            Some(recorder) => Box::new(RecordingTransport::new(
+                socket,
+                recorder.clone(),
+                &node.can_interface,
+            )),
+            None => Box::new(socket),
+        };
+        let node_id = node.node_id as u8;
+        let mut emulator = Emulator::new(node, transport);
+        if let Some(scenario) = &scenario {
+            emulator.set_scenario(scenario.runner_for(node_id));
+        }
+        emulators.push(emulator);
+    }
+
+    if let Some(path) = args.control {
+        let handles = emulators.iter().map(Emulator::handle).collect();
+        if let Err(err) = control::spawn(&path, handles) {
+            eprintln!("Error opening control socket {}: {err:?}", path.display());
+            return;
+        }
+    }
+
+    let handles = emulators.iter().map(Emulator::handle).collect();
+    if let Err(err) = reload::spawn(&config_path, handles) {
+        eprintln!("Error watching config file {config_path}: {err:?}");
         return;
-    };
+    }
 
-    if let Err(err) = socket.set_read_timeout(Some(Duration::from_millis(50))) {
-        eprintln!("Error setting CAN FD socket timeout: {err:?}");
+    let handles = emulators.iter().map(Emulator::handle).collect();
+    if let Err(err) = reboot::spawn(handles) {
+        eprintln!("Error installing the SIGUSR1 reboot handler: {err:?}");
         return;
     }
 
-    let registration_messages = registration_flow_messages(&config);
-    send_messages(&mut socket, sender_id, 1, registration_messages);
+    println!("Starting ECUEmulator with {} node(s)", emulators.len());
+    let runners: Vec<_> = emulators
+        .into_iter()
+        .map(|mut emulator| thread::spawn(move || emulator.run()))
+        .collect();
+    for runner in runners {
+        if runner.join().is_err() {
+            eprintln!("An emulated node stopped unexpectedly");
+        }
+    }
+}
 
-    let update_interval = if config.frequency == 0 {
-        None
-    } else {
-        Some(Duration::from_secs_f64(1.0 / config.frequency as f64))
+fn run_replay(args: ReplayArgs) {
+    if !(args.speed.is_finite() && args.speed > 0.0) {
+        eprintln!("Invalid speed {} (must be > 0)", args.speed);
+        std::process::exit(1);
+    }
+    let entries = match fs::read_to_string(&args.log)
+        .map_err(candump::CandumpError::from)
+        .and_then(|contents| candump::parse_log(&contents))
+    {
+        Ok(entries) => entries,
+        Err(err) => {
+            eprintln!("Error reading CAN log {}: {err}", args.log.display());
+            std::process::exit(1);
+        }
     };
-    let mut last_update = Instant::now();
 
-    println!("Starting ECUEmulator");
-    loop {
-        if let Some(interval) = update_interval {
-            if last_update.elapsed() >= interval {
-                let updates = build_telemetry_group_updates(&config);
-                send_messages(&mut socket, sender_id, 1, updates);
-                last_update = Instant::now();
-            }
+    let options = ReplayOptions {
+        speed: args.speed,
+        sender_id: args.node,
+    };
+    let mut sockets: HashMap<String, SocketCanTransport> = HashMap::new();
+    let stats = replay(&entries, &options, |entry, frame| {
+        let interface = args.interface.as_ref().unwrap_or(&entry.interface);
+        if !sockets.contains_key(interface) {
+            let socket = socket_manager::open_socket(interface)?;
+            sockets.insert(interface.clone(), socket);
         }
+        sockets.get_mut(interface).unwrap().send_frame(frame)
+    });
+    println!(
+        "Replayed {} frame(s); {} filtered, {} not CAN FD, {} failed",
+        stats.sent, stats.filtered, stats.skipped, stats.failed
+    );
+}
+
+fn run_sniff(args: SniffArgs) {
+    let res = socket_manager::open_socket(&args.interface);
+    let Ok(mut socket) = res else {
+        eprintln!(
+            "Error opening CAN FD socket on {}: {:?}",
+            args.interface,
+            res.err().unwrap()
+        );
+        std::process::exit(1);
+    };
 
-        let res = socket_manager::read_frame(&mut socket);
+    let filter = SniffFilter {
+        node_id: args.node,
+        kinds: args.kind,
+    };
+    let mut sniffer = Sniffer::new();
+    loop {
+        let res = socket_manager::read_frame(&mut socket, Duration::from_secs(1));
         let Ok(frame) = res else {
-            if res.should_retry() {
-                continue;
+            if !res.should_retry() {
+                eprintln!("Error reading CAN FD frame: {:?}", res.err().unwrap());
             }
-            eprintln!("Error reading CAN FD frame: {:?}", res.err().unwrap());
             continue;
         };
-        let res = parse_can_message(frame);
-        let Ok((id, msg)) = res else {
-            println!("Error during parsing frame: {}", res.err().unwrap());
-            continue;
+        let (id, msg) = match parse_can_message(frame) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                eprintln!("Undecodable frame: {err}");
+                continue;
+            }
         };
+        let decoded = sniffer.decode(id, &msg);
+        if !filter.matches(&decoded) {
+            continue;
+        }
+        if args.json {
+            println!("{}", decoded.to_json());
+        } else {
+            println!("{}", decoded.to_text());
+        }
+    }
+}
+
+fn run_server(args: ServerArgs) {
+    if !(args.timeout.is_finite() && args.timeout > 0.0) {
+        eprintln!("Invalid timeout {} (must be > 0)", args.timeout);
+        std::process::exit(1);
+    }
+    if args.node == args.server_id {
+        eprintln!(
+            "The server needs an ID other than the node's ({})",
+            args.node
+        );
+        std::process::exit(1);
+    }
+    let res = socket_manager::open_socket(&args.interface);
+    let Ok(mut socket) = res else {
+        eprintln!(
+            "Error opening CAN FD socket on {}: {:?}",
+            args.interface,
+            res.err().unwrap()
+        );
+        std::process::exit(1);
+    };
+    if let Err(err) = socket.set_receiver_filter(&[args.server_id, 0]) {
+        eprintln!("Error setting the CAN receive filter: {err:?}");
+    }
 
-        let responses = handle_message(&msg, &mut config);
-        let receiver_id = id.sender_id();
-        send_messages(&mut socket, sender_id, receiver_id, responses);
+    let options = ServerOptions {
+        server_id: args.server_id,
+        node_id: args.node,
+        timeout: Duration::from_secs_f64(args.timeout),
+        heartbeats: args.heartbeats,
+    };
+    let report = check_node(&mut socket, &options);
+    println!("{}", report.to_text());
+    if !report.passed() {
+        std::process::exit(1);
     }
 }
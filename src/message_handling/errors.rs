@@ -0,0 +1,13 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ParseFrameError {
+    #[error("{kind} frame is not a CAN FD frame")]
+    NonFdFrame { kind: &'static str },
+
+    #[error("extended CAN ID {raw_id:#x} is not a LiquidCAN ID")]
+    ExtendedId { raw_id: u32 },
+
+    #[error("failed to parse CAN message data: {reason}")]
+    Malformed { reason: String },
+}
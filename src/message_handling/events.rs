@@ -0,0 +1,38 @@
+use crate::config::config_representation::EmulatorData;
+use crate::message_handling::parameter_name;
+use liquidcan::payloads::ParameterSetStatus;
+use liquidcan::CanMessage;
+use serde::Deserialize;
+
+/// Something that happens on an emulated node. Events trigger scenario steps and alarm rules.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Event {
+    /// A server set the parameter over the bus.
+    ParameterSet(String),
+}
+
+impl Event {
+    /// The event a received message and the emulator's responses to it amount to, if any.
+    pub fn of_exchange(
+        msg: &CanMessage,
+        responses: &[CanMessage],
+        emulator_data: &EmulatorData,
+    ) -> Option<Self> {
+        let CanMessage::ParameterSetReq { payload } = msg else {
+            return None;
+        };
+        let accepted = responses.iter().any(|response| {
+            matches!(
+                response,
+                CanMessage::ParameterSetConfirmation { payload }
+                    if matches!(payload.status, ParameterSetStatus::Success)
+            )
+        });
+        if !accepted {
+            return None;
+        }
+        let name = parameter_name(emulator_data, payload.parameter_id)?;
+        Some(Event::ParameterSet(name.to_string()))
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+
+/// Counters further ahead than this count as going backwards (with wrapping arithmetic).
+const MAX_GAP: u32 = u32::MAX / 2;
+
+/// How a heartbeat counter differed from the one expected from its sender.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeartbeatAnomaly {
+    /// Counters were skipped, e.g. because the server sent heartbeats late or lost them.
+    Gap { expected: u32, received: u32 },
+    /// The previous counter was sent again.
+    Repeat { counter: u32 },
+    /// The counter went backwards, e.g. because the server restarted.
+    Reset { expected: u32, received: u32 },
+}
+
+impl HeartbeatAnomaly {
+    /// Status message text; fits into a status message for every sender ID and counter.
+    pub fn describe(&self, sender_id: u8) -> String {
+        match self {
+            HeartbeatAnomaly::Gap { expected, received } => {
+                format!("Heartbeat gap from node {sender_id}: expected {expected}, got {received}")
+            }
+            HeartbeatAnomaly::Repeat { counter } => {
+                format!("Heartbeat repeat from node {sender_id}: counter {counter}")
+            }
+            HeartbeatAnomaly::Reset { expected, received } => {
+                format!("Heartbeat reset by node {sender_id}: expected {expected}, got {received}")
+            }
+        }
+    }
+}
+
+/// Heartbeat requests received, and the anomalies among them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HeartbeatStats {
+    pub received: u64,
+    pub gaps: u64,
+    /// Counters skipped over all gaps.
+    pub missed: u64,
+    pub repeats: u64,
+    pub resets: u64,
+}
+
+/// Tracks the heartbeat counter of every sender and checks that it counts up by one.
+#[derive(Default)]
+pub struct HeartbeatTracker {
+    last_counters: HashMap<u8, u32>,
+    stats: HeartbeatStats,
+}
+
+impl HeartbeatTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forgets the last counter of every sender, as a rebooted node does. The stats are kept.
+    pub fn forget_counters(&mut self) {
+        self.last_counters.clear();
+    }
+
+    /// Records a heartbeat request. The first counter of a sender is taken as it is.
+    pub fn track(&mut self, sender_id: u8, counter: u32) -> Option<HeartbeatAnomaly> {
+        self.stats.received += 1;
+        let last = self.last_counters.insert(sender_id, counter)?;
+        let expected = last.wrapping_add(1);
+        let ahead = counter.wrapping_sub(expected);
+        let anomaly = if ahead == 0 {
+            return None;
+        } else if counter == last {
+            self.stats.repeats += 1;
+            HeartbeatAnomaly::Repeat { counter }
+        } else if ahead <= MAX_GAP {
+            self.stats.gaps += 1;
+            self.stats.missed += u64::from(ahead);
+            HeartbeatAnomaly::Gap {
+                expected,
+                received: counter,
+            }
+        } else {
+            self.stats.resets += 1;
+            HeartbeatAnomaly::Reset {
+                expected,
+                received: counter,
+            }
+        };
+        Some(anomaly)
+    }
+
+    pub fn stats(&self) -> HeartbeatStats {
+        self.stats
+    }
+}
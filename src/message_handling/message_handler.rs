@@ -1,16 +1,23 @@
 use crate::config::config_representation::EmulatorData;
 use crate::config::config_representation::{Parameter, TelemetryValue};
+use crate::simulation::actuator;
 use liquidcan::payloads;
 use liquidcan::CanMessage;
+use serde::Deserialize;
 
-const TELEMETRY_ID_BIT: u8 = 0b1000_0000;
+/// Set in the field ID of telemetry values, clear for parameters.
+pub const TELEMETRY_ID_BIT: u8 = 0b1000_0000;
+/// The field ID without the telemetry bit.
+pub const FIELD_ID_MASK: u8 = 0b0111_1111;
+/// Receiver ID of frames addressed to every node.
+pub const BROADCAST_ID: u8 = 0;
 
 pub fn handle_message(msg: &CanMessage, emulator_data: &mut EmulatorData) -> Vec<CanMessage> {
     match msg {
         CanMessage::NodeInfoReq => registration_flow_messages(emulator_data),
         CanMessage::HeartbeatReq { payload } => vec![CanMessage::HeartbeatRes {
             payload: payloads::HeartbeatPayload {
-                counter: payload.counter + 1,
+                counter: payload.counter.wrapping_add(1),
             },
         }],
         CanMessage::ParameterSetReq { payload } => {
@@ -23,10 +30,8 @@ pub fn handle_message(msg: &CanMessage, emulator_data: &mut EmulatorData) -> Vec
                     },
                 }];
             };
-            let indices = sorted_parameter_indices(parameters);
-            let field_index =
-                index_from_field_id(payload.parameter_id).and_then(|idx| indices.get(idx).copied());
-            let Some(param_index) = field_index else {
+            let ids = parameter_field_ids(parameters);
+            let Some(param_index) = index_for_field_id(&ids, payload.parameter_id) else {
                 return vec![CanMessage::ParameterSetConfirmation {
                     payload: payloads::ParameterSetConfirmationPayload {
                         parameter_id: payload.parameter_id,
@@ -36,8 +41,8 @@ pub fn handle_message(msg: &CanMessage, emulator_data: &mut EmulatorData) -> Vec
                 }];
             };
             let param = &mut parameters[param_index];
-            if param.locked {
-                let current_value = value_from_u32(param.value, param.datatype);
+            if param.locked || param.read_only {
+                let current_value = param.value.clone();
                 return vec![CanMessage::ParameterSetConfirmation {
                     payload: payloads::ParameterSetConfirmationPayload {
                         parameter_id: payload.parameter_id,
@@ -46,8 +51,10 @@ pub fn handle_message(msg: &CanMessage, emulator_data: &mut EmulatorData) -> Vec
                     },
                 }];
             }
-            let Some(new_value) = u32_from_value(&payload.value, param.datatype) else {
-                let current_value = value_from_u32(param.value, param.datatype);
+            let new_value = typed_from_value(&payload.value, param.datatype)
+                .filter(|value| param.check_constraints(value).is_ok());
+            let Some(new_value) = new_value else {
+                let current_value = param.value.clone();
                 return vec![CanMessage::ParameterSetConfirmation {
                     payload: payloads::ParameterSetConfirmationPayload {
                         parameter_id: payload.parameter_id,
@@ -57,7 +64,9 @@ pub fn handle_message(msg: &CanMessage, emulator_data: &mut EmulatorData) -> Vec
                 }];
             };
             param.value = new_value;
-            let confirmed_value = value_from_u32(param.value, param.datatype);
+            let confirmed_value = param.value.clone();
+            let name = param.name.clone();
+            actuator::parameter_changed(emulator_data, &name);
             vec![CanMessage::ParameterSetConfirmation {
                 payload: payloads::ParameterSetConfirmationPayload {
                     parameter_id: payload.parameter_id,
@@ -77,10 +86,8 @@ pub fn handle_message(msg: &CanMessage, emulator_data: &mut EmulatorData) -> Vec
                     },
                 }];
             };
-            let indices = sorted_parameter_indices(parameters);
-            let field_index =
-                index_from_field_id(payload.parameter_id).and_then(|idx| indices.get(idx).copied());
-            let Some(param_index) = field_index else {
+            let ids = parameter_field_ids(parameters);
+            let Some(param_index) = index_for_field_id(&ids, payload.parameter_id) else {
                 return vec![CanMessage::ParameterSetLockConfirmation {
                     payload: payloads::ParameterSetLockConfirmationPayload {
                         parameter_id: payload.parameter_id,
@@ -115,10 +122,8 @@ pub fn handle_message(msg: &CanMessage, emulator_data: &mut EmulatorData) -> Vec
                         },
                     }];
                 };
-                let indices = sorted_telemetry_indices(telemetry);
-                let field_index =
-                    index_from_field_id(field_id).and_then(|idx| indices.get(idx).copied());
-                let Some(tel_index) = field_index else {
+                let ids = telemetry_field_ids(telemetry);
+                let Some(tel_index) = index_for_field_id(&ids, field_id) else {
                     return vec![CanMessage::FieldGetRes {
                         payload: payloads::FieldGetResPayload {
                             field_id,
@@ -128,7 +133,7 @@ pub fn handle_message(msg: &CanMessage, emulator_data: &mut EmulatorData) -> Vec
                     }];
                 };
                 let tel = &telemetry[tel_index];
-                let value = value_from_u32(tel.value, tel.datatype);
+                let value = tel.value.clone();
                 vec![CanMessage::FieldGetRes {
                     payload: payloads::FieldGetResPayload {
                         field_id,
@@ -146,10 +151,8 @@ pub fn handle_message(msg: &CanMessage, emulator_data: &mut EmulatorData) -> Vec
                         },
                     }];
                 };
-                let indices = sorted_parameter_indices(parameters);
-                let field_index =
-                    index_from_field_id(field_id).and_then(|idx| indices.get(idx).copied());
-                let Some(param_index) = field_index else {
+                let ids = parameter_field_ids(parameters);
+                let Some(param_index) = index_for_field_id(&ids, field_id) else {
                     return vec![CanMessage::FieldGetRes {
                         payload: payloads::FieldGetResPayload {
                             field_id,
@@ -159,7 +162,7 @@ pub fn handle_message(msg: &CanMessage, emulator_data: &mut EmulatorData) -> Vec
                     }];
                 };
                 let param = &parameters[param_index];
-                let value = value_from_u32(param.value, param.datatype);
+                let value = param.value.clone();
                 vec![CanMessage::FieldGetRes {
                     payload: payloads::FieldGetResPayload {
                         field_id,
@@ -173,12 +176,12 @@ pub fn handle_message(msg: &CanMessage, emulator_data: &mut EmulatorData) -> Vec
         CanMessage::FieldIDLookupReq { payload } => {
             let field_name: String = payload.field_name.clone().into();
             if let Some(telemetry) = emulator_data.telemetry_values.as_ref() {
-                let indices = sorted_telemetry_indices(telemetry);
-                if let Some((pos, tel)) = indices.iter().enumerate().find_map(|(pos, &idx)| {
+                let ids = telemetry_field_ids(telemetry);
+                if let Some((id, tel)) = ids.iter().find_map(|&(id, idx)| {
                     let tel = &telemetry[idx];
-                    (tel.name == field_name).then_some((pos, tel))
+                    (tel.name == field_name).then_some((id, tel))
                 }) {
-                    let field_id = telemetry_id_for_index(pos).unwrap_or(0);
+                    let field_id = id | TELEMETRY_ID_BIT;
                     return vec![CanMessage::FieldIDLookupRes {
                         payload: payloads::FieldIDLookupResPayload {
                             field_id,
@@ -189,12 +192,11 @@ pub fn handle_message(msg: &CanMessage, emulator_data: &mut EmulatorData) -> Vec
                 }
             }
             if let Some(parameters) = emulator_data.parameters.as_ref() {
-                let indices = sorted_parameter_indices(parameters);
-                if let Some((pos, param)) = indices.iter().enumerate().find_map(|(pos, &idx)| {
+                let ids = parameter_field_ids(parameters);
+                if let Some((field_id, param)) = ids.iter().find_map(|&(id, idx)| {
                     let param = &parameters[idx];
-                    (param.name == field_name).then_some((pos, param))
+                    (param.name == field_name).then_some((id, param))
                 }) {
-                    let field_id = parameter_id_for_index(pos).unwrap_or(0);
                     return vec![CanMessage::FieldIDLookupRes {
                         payload: payloads::FieldIDLookupResPayload {
                             field_id,
@@ -217,74 +219,97 @@ pub fn handle_message(msg: &CanMessage, emulator_data: &mut EmulatorData) -> Vec
     }
 }
 
-fn sorted_parameter_indices(parameters: &[Parameter]) -> Vec<usize> {
-    let mut indices: Vec<usize> = (0..parameters.len()).collect();
-    indices.sort_by(|&a, &b| parameters[a].name.cmp(&parameters[b].name));
-    indices
+/// Pairs every field with its 7-bit field ID, ordered by ID.
+/// Explicit IDs from the config are kept; the remaining fields get the lowest free IDs in
+/// alphabetical order. Fields that do not fit into the 7-bit ID space are left out.
+fn assign_field_ids<'a>(fields: impl Iterator<Item = (&'a str, Option<u8>)>) -> Vec<(u8, usize)> {
+    let mut assigned: Vec<(u8, usize)> = Vec::new();
+    let mut unassigned: Vec<(&str, usize)> = Vec::new();
+    for (idx, (name, id)) in fields.enumerate() {
+        match id {
+            Some(id) => assigned.push((id, idx)),
+            None => unassigned.push((name, idx)),
+        }
+    }
+    unassigned.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut next_id: u8 = 1;
+    for (_name, idx) in unassigned {
+        while assigned.iter().any(|&(id, _)| id == next_id) {
+            next_id += 1;
+        }
+        if next_id > FIELD_ID_MASK {
+            break;
+        }
+        assigned.push((next_id, idx));
+        next_id += 1;
+    }
+    assigned.sort_by_key(|&(id, _)| id);
+    assigned
 }
 
-fn sorted_telemetry_indices(telemetry: &[TelemetryValue]) -> Vec<usize> {
-    let mut indices: Vec<usize> = (0..telemetry.len()).collect();
-    indices.sort_by(|&a, &b| telemetry[a].name.cmp(&telemetry[b].name));
-    indices
+fn parameter_field_ids(parameters: &[Parameter]) -> Vec<(u8, usize)> {
+    assign_field_ids(parameters.iter().map(|p| (p.name.as_str(), p.id)))
 }
 
-fn parameter_id_for_index(index: usize) -> Option<u8> {
-    let id = index.checked_add(1)?;
-    if id > 0x7F {
-        return None;
-    }
-    Some(id as u8)
+/// Telemetry IDs without the telemetry bit set.
+fn telemetry_field_ids(telemetry: &[TelemetryValue]) -> Vec<(u8, usize)> {
+    assign_field_ids(telemetry.iter().map(|t| (t.name.as_str(), t.id)))
 }
 
-fn telemetry_id_for_index(index: usize) -> Option<u8> {
-    parameter_id_for_index(index).map(|id| id | TELEMETRY_ID_BIT)
+fn index_for_field_id(ids: &[(u8, usize)], field_id: u8) -> Option<usize> {
+    let raw = field_id & FIELD_ID_MASK;
+    ids.iter().find(|&&(id, _)| id == raw).map(|&(_, idx)| idx)
 }
 
-fn index_from_field_id(field_id: u8) -> Option<usize> {
-    let raw = field_id & 0x7F;
-    if raw == 0 {
-        return None;
-    }
-    Some((raw - 1) as usize)
+/// The name of the parameter a `parameter_id` from the bus refers to.
+pub fn parameter_name(emulator_data: &EmulatorData, parameter_id: u8) -> Option<&str> {
+    let parameters = emulator_data.parameters.as_deref()?;
+    let idx = index_for_field_id(&parameter_field_ids(parameters), parameter_id)?;
+    Some(&parameters[idx].name)
 }
 
-fn u32_from_value(value: &payloads::CanDataValue, data_type: payloads::CanDataType) -> Option<u32> {
+pub(crate) fn typed_from_value(
+    value: &payloads::CanDataValue,
+    data_type: payloads::CanDataType,
+) -> Option<payloads::CanDataValue> {
     let typed = match value {
         payloads::CanDataValue::Raw(_) => value.convert_from_raw(data_type).ok()?,
         _ => value.clone(),
     };
-    let v = match typed {
-        payloads::CanDataValue::Float32(v) => v.to_bits(),
-        payloads::CanDataValue::Int32(v) => v as u32,
-        payloads::CanDataValue::Int16(v) => (v as u16) as u32,
-        payloads::CanDataValue::Int8(v) => (v as u8) as u32,
-        payloads::CanDataValue::UInt32(v) => v,
-        payloads::CanDataValue::UInt16(v) => v as u32,
-        payloads::CanDataValue::UInt8(v) => v as u32,
-        payloads::CanDataValue::Boolean(v) => {
-            if v {
-                1
-            } else {
-                0
-            }
-        }
-        payloads::CanDataValue::Raw(_) => return None,
-    };
-    Some(v)
+    matches_data_type(&typed, data_type).then_some(typed)
 }
 
-fn value_from_u32(value: u32, data_type: payloads::CanDataType) -> payloads::CanDataValue {
-    match data_type {
-        payloads::CanDataType::Float32 => payloads::CanDataValue::Float32(f32::from_bits(value)),
-        payloads::CanDataType::Int32 => payloads::CanDataValue::Int32(value as i32),
-        payloads::CanDataType::Int16 => payloads::CanDataValue::Int16((value as u16) as i16),
-        payloads::CanDataType::Int8 => payloads::CanDataValue::Int8((value as u8) as i8),
-        payloads::CanDataType::UInt32 => payloads::CanDataValue::UInt32(value),
-        payloads::CanDataType::UInt16 => payloads::CanDataValue::UInt16(value as u16),
-        payloads::CanDataType::UInt8 => payloads::CanDataValue::UInt8(value as u8),
-        payloads::CanDataType::Boolean => payloads::CanDataValue::Boolean(value != 0),
-    }
+fn matches_data_type(value: &payloads::CanDataValue, data_type: payloads::CanDataType) -> bool {
+    matches!(
+        (value, data_type),
+        (
+            payloads::CanDataValue::Float32(_),
+            payloads::CanDataType::Float32
+        ) | (
+            payloads::CanDataValue::Int32(_),
+            payloads::CanDataType::Int32
+        ) | (
+            payloads::CanDataValue::Int16(_),
+            payloads::CanDataType::Int16
+        ) | (payloads::CanDataValue::Int8(_), payloads::CanDataType::Int8)
+            | (
+                payloads::CanDataValue::UInt32(_),
+                payloads::CanDataType::UInt32
+            )
+            | (
+                payloads::CanDataValue::UInt16(_),
+                payloads::CanDataType::UInt16
+            )
+            | (
+                payloads::CanDataValue::UInt8(_),
+                payloads::CanDataType::UInt8
+            )
+            | (
+                payloads::CanDataValue::Boolean(_),
+                payloads::CanDataType::Boolean
+            )
+    )
 }
 
 fn node_info_announcement(emulator_data: &EmulatorData) -> CanMessage {
@@ -313,54 +338,51 @@ fn node_info_announcement(emulator_data: &EmulatorData) -> CanMessage {
 }
 
 fn telemetry_registrations(telemetry: &[TelemetryValue]) -> Vec<CanMessage> {
-    let indices = sorted_telemetry_indices(telemetry);
-    indices
-        .iter()
-        .enumerate()
-        .filter_map(|(pos, &idx)| {
-            let field_id = telemetry_id_for_index(pos)?;
+    telemetry_field_ids(telemetry)
+        .into_iter()
+        .map(|(id, idx)| {
             let tel = &telemetry[idx];
             let field_name = payloads::CanString::<61>::try_from(tel.name.as_str())
                 .expect("Telemetry field name too long (max 61 bytes)");
-            Some(CanMessage::TelemetryValueRegistration {
+            CanMessage::TelemetryValueRegistration {
                 payload: payloads::FieldRegistrationPayload {
-                    field_id,
+                    field_id: id | TELEMETRY_ID_BIT,
                     field_type: tel.datatype,
                     field_name,
                 },
-            })
+            }
         })
         .collect()
 }
 
 fn parameter_registrations(parameters: &[Parameter]) -> Vec<CanMessage> {
-    let indices = sorted_parameter_indices(parameters);
-    indices
-        .iter()
-        .enumerate()
-        .filter_map(|(pos, &idx)| {
-            let field_id = parameter_id_for_index(pos)?;
+    parameter_field_ids(parameters)
+        .into_iter()
+        .map(|(field_id, idx)| {
             let param = &parameters[idx];
             let field_name = payloads::CanString::<61>::try_from(param.name.as_str())
                 .expect("Parameter name too long (max 61 bytes)");
-            Some(CanMessage::ParameterRegistration {
+            CanMessage::ParameterRegistration {
                 payload: payloads::FieldRegistrationPayload {
                     field_id,
                     field_type: param.datatype,
                     field_name,
                 },
-            })
+            }
         })
         .collect()
 }
 
-fn telemetry_group_entries(telemetry: &[TelemetryValue]) -> Vec<Vec<(usize, usize)>> {
-    let indices = sorted_telemetry_indices(telemetry);
-    let mut groups: Vec<Vec<(usize, usize)>> = Vec::new();
-    let mut current: Vec<(usize, usize)> = Vec::new();
+/// Splits telemetry into groups of (field ID, index) pairs that each fit into one update frame.
+fn pack_telemetry_groups(
+    telemetry: &[TelemetryValue],
+    ids: Vec<(u8, usize)>,
+) -> Vec<Vec<(u8, usize)>> {
+    let mut groups: Vec<Vec<(u8, usize)>> = Vec::new();
+    let mut current: Vec<(u8, usize)> = Vec::new();
     let mut current_size: usize = 0;
 
-    for (pos, &idx) in indices.iter().enumerate() {
+    for (id, idx) in ids {
         let field_size = telemetry[idx].datatype.get_size();
         let would_overflow = current.len() >= 62 || current_size + field_size > 62;
         if would_overflow {
@@ -370,7 +392,7 @@ fn telemetry_group_entries(telemetry: &[TelemetryValue]) -> Vec<Vec<(usize, usiz
             current = Vec::new();
             current_size = 0;
         }
-        current.push((pos, idx));
+        current.push((id, idx));
         current_size += field_size;
     }
 
@@ -381,64 +403,125 @@ fn telemetry_group_entries(telemetry: &[TelemetryValue]) -> Vec<Vec<(usize, usiz
     groups
 }
 
-fn telemetry_group_definitions(telemetry: &[TelemetryValue]) -> Vec<CanMessage> {
-    let groups = telemetry_group_entries(telemetry);
-    let mut messages = Vec::new();
-    let mut group_id: u8 = 1;
+/// A telemetry group as announced in the registration flow.
+pub struct TelemetryGroupLayout {
+    pub group_id: u8,
+    /// Update rate in Hz; 0 disables periodic updates of this group.
+    pub rate: f64,
+    /// (field ID, telemetry index) pairs in the order their values are packed.
+    entries: Vec<(u8, usize)>,
+}
 
-    for group in groups {
-        let mut ids = Vec::new();
-        for (pos, _idx) in group {
-            if let Some(field_id) = telemetry_id_for_index(pos) {
-                ids.push(field_id);
-            }
-        }
-        if ids.is_empty() {
+/// Groups from the `[TelemetryGroups]` section come first, ordered by name, followed by the
+/// remaining telemetry packed automatically and sent at the global `frequency`.
+pub fn telemetry_group_layout(emulator_data: &EmulatorData) -> Vec<TelemetryGroupLayout> {
+    let Some(telemetry) = emulator_data.telemetry_values.as_ref() else {
+        return Vec::new();
+    };
+    let ids = telemetry_field_ids(telemetry);
+    let mut grouped = vec![false; telemetry.len()];
+    let mut groups: Vec<(f64, Vec<(u8, usize)>)> = Vec::new();
+
+    let mut explicit: Vec<_> = emulator_data.telemetry_groups.iter().flatten().collect();
+    explicit.sort_by(|a, b| a.name.cmp(&b.name));
+    for group in explicit {
+        let entries: Vec<(u8, usize)> = group
+            .members
+            .iter()
+            .filter_map(|member| {
+                ids.iter()
+                    .find(|&&(_, idx)| telemetry[idx].name == *member)
+                    .copied()
+            })
+            .collect();
+        if entries.is_empty() {
             continue;
         }
-        let field_ids = payloads::NonNullCanBytes::<62>::try_from(ids.as_slice())
-            .expect("Telemetry group field IDs must be <= 62 bytes and non-zero");
-        messages.push(CanMessage::TelemetryGroupDefinition {
-            payload: payloads::TelemetryGroupDefinitionPayload {
-                group_id,
-                field_ids,
-            },
-        });
-        group_id = group_id.saturating_add(1);
+        for &(_, idx) in &entries {
+            grouped[idx] = true;
+        }
+        groups.push((group.rate, entries));
     }
 
-    messages
+    let remaining: Vec<(u8, usize)> = ids.into_iter().filter(|&(_, idx)| !grouped[idx]).collect();
+    for entries in pack_telemetry_groups(telemetry, remaining) {
+        groups.push((emulator_data.frequency as f64, entries));
+    }
+
+    let mut group_id: u8 = 1;
+    groups
+        .into_iter()
+        .map(|(rate, entries)| {
+            let layout = TelemetryGroupLayout {
+                group_id,
+                rate,
+                entries,
+            };
+            group_id = group_id.saturating_add(1);
+            layout
+        })
+        .collect()
+}
+
+fn telemetry_group_definitions(emulator_data: &EmulatorData) -> Vec<CanMessage> {
+    telemetry_group_layout(emulator_data)
+        .into_iter()
+        .map(|group| {
+            let ids: Vec<u8> = group
+                .entries
+                .iter()
+                .map(|&(id, _idx)| id | TELEMETRY_ID_BIT)
+                .collect();
+            let field_ids = payloads::NonNullCanBytes::<62>::try_from(ids.as_slice())
+                .expect("Telemetry group field IDs must be <= 62 bytes and non-zero");
+            CanMessage::TelemetryGroupDefinition {
+                payload: payloads::TelemetryGroupDefinitionPayload {
+                    group_id: group.group_id,
+                    field_ids,
+                },
+            }
+        })
+        .collect()
+}
+
+pub(super) fn telemetry_group_update(
+    telemetry: &[TelemetryValue],
+    group: &TelemetryGroupLayout,
+) -> CanMessage {
+    let values: Vec<payloads::CanDataValue> = group
+        .entries
+        .iter()
+        .map(|&(_id, idx)| telemetry[idx].value.clone())
+        .collect();
+    let packed = payloads::PackedCanDataValues::<62>::try_from(values.as_slice())
+        .expect("Telemetry group update values must fit into 62 bytes");
+    CanMessage::TelemetryGroupUpdate {
+        payload: payloads::TelemetryGroupUpdatePayload {
+            group_id: group.group_id,
+            values: packed,
+        },
+    }
 }
 
 pub fn build_telemetry_group_updates(emulator_data: &EmulatorData) -> Vec<CanMessage> {
     let Some(telemetry) = emulator_data.telemetry_values.as_ref() else {
         return Vec::new();
     };
-    let groups = telemetry_group_entries(telemetry);
-    let mut messages = Vec::new();
-    let mut group_id: u8 = 1;
-
-    for group in groups {
-        let mut values = Vec::new();
-        for (_pos, idx) in group {
-            let tel = &telemetry[idx];
-            values.push(value_from_u32(tel.value, tel.datatype));
-        }
-        if values.is_empty() {
-            continue;
-        }
-        let packed = payloads::PackedCanDataValues::<62>::try_from(values.as_slice())
-            .expect("Telemetry group update values must fit into 62 bytes");
-        messages.push(CanMessage::TelemetryGroupUpdate {
-            payload: payloads::TelemetryGroupUpdatePayload {
-                group_id,
-                values: packed,
-            },
-        });
-        group_id = group_id.saturating_add(1);
-    }
+    telemetry_group_layout(emulator_data)
+        .iter()
+        .map(|group| telemetry_group_update(telemetry, group))
+        .collect()
+}
 
-    messages
+pub fn build_telemetry_group_update(
+    emulator_data: &EmulatorData,
+    group_id: u8,
+) -> Option<CanMessage> {
+    let telemetry = emulator_data.telemetry_values.as_ref()?;
+    telemetry_group_layout(emulator_data)
+        .iter()
+        .find(|group| group.group_id == group_id)
+        .map(|group| telemetry_group_update(telemetry, group))
 }
 
 pub fn registration_flow_messages(emulator_data: &EmulatorData) -> Vec<CanMessage> {
@@ -451,23 +534,88 @@ pub fn registration_flow_messages(emulator_data: &EmulatorData) -> Vec<CanMessag
     if let Some(parameters) = emulator_data.parameters.as_ref() {
         messages.extend(parameter_registrations(parameters));
     }
-    if let Some(telemetry) = emulator_data.telemetry_values.as_ref() {
-        messages.extend(telemetry_group_definitions(telemetry));
-    }
+    messages.extend(telemetry_group_definitions(emulator_data));
 
     messages
 }
 
-#[allow(dead_code)]
+/// What the registration flow tells the servers about a node, without any values or rates.
+/// Servers have to be sent the registration flow again when it changes.
+#[derive(PartialEq)]
+pub struct RegistrationLayout {
+    device_name: String,
+    firmware_hash: u32,
+    liquid_hash: u32,
+    /// (field ID, name, data type) of every telemetry value and parameter.
+    telemetry: Vec<(u8, String, u8)>,
+    parameters: Vec<(u8, String, u8)>,
+    /// Field IDs of every telemetry group, by group ID.
+    groups: Vec<(u8, Vec<u8>)>,
+}
+
+pub fn registration_layout(emulator_data: &EmulatorData) -> RegistrationLayout {
+    let telemetry = emulator_data
+        .telemetry_values
+        .as_deref()
+        .unwrap_or_default();
+    let parameters = emulator_data.parameters.as_deref().unwrap_or_default();
+    RegistrationLayout {
+        device_name: emulator_data.device_name.clone(),
+        firmware_hash: emulator_data.firmware_hash,
+        liquid_hash: emulator_data.liquid_hash,
+        telemetry: telemetry_field_ids(telemetry)
+            .into_iter()
+            .map(|(id, idx)| {
+                (
+                    id,
+                    telemetry[idx].name.clone(),
+                    telemetry[idx].datatype as u8,
+                )
+            })
+            .collect(),
+        parameters: parameter_field_ids(parameters)
+            .into_iter()
+            .map(|(id, idx)| {
+                (
+                    id,
+                    parameters[idx].name.clone(),
+                    parameters[idx].datatype as u8,
+                )
+            })
+            .collect(),
+        groups: telemetry_group_layout(emulator_data)
+            .into_iter()
+            .map(|group| {
+                let ids = group.entries.iter().map(|&(id, _idx)| id).collect();
+                (group.group_id, ids)
+            })
+            .collect(),
+    }
+}
+
+/// Longest text a status message can carry, in bytes.
+pub const MAX_STATUS_LEN: usize = 63;
+
+/// Ordered by severity.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
 pub enum StatusMessageKind {
     Info,
     Warning,
     Error,
 }
 
-#[allow(dead_code)]
+/// Cuts `text` to at most `MAX_STATUS_LEN` bytes without splitting a character.
+pub fn truncate_status_text(text: &str) -> &str {
+    let mut end = text.len().min(MAX_STATUS_LEN);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
 pub fn build_status_message(kind: StatusMessageKind, message: &str) -> CanMessage {
-    let msg = payloads::CanString::<63>::try_from(message)
+    let msg = payloads::CanString::<MAX_STATUS_LEN>::try_from(message)
         .expect("Status message too long (max 63 bytes)");
     let payload = payloads::StatusPayload { msg };
     match kind {
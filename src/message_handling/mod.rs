@@ -1,31 +1,56 @@
+pub mod errors;
+mod events;
+mod heartbeat_tracker;
 mod message_handler;
+pub mod routing;
+mod telemetry_schedule;
 
 use crate::config::config_representation::EmulatorData;
-use anyhow::{anyhow, Result};
+use crate::message_handling::errors::ParseFrameError;
 use liquidcan::{CanMessage, CanMessageId};
 use socketcan::{CanAnyFrame, EmbeddedFrame, Id};
 
+pub use events::Event;
+pub use heartbeat_tracker::{HeartbeatAnomaly, HeartbeatStats, HeartbeatTracker};
+pub(crate) use message_handler::typed_from_value;
 #[allow(unused_imports)]
 pub use message_handler::{
-    build_status_message, build_telemetry_group_updates, registration_flow_messages,
-    StatusMessageKind,
+    build_status_message, build_telemetry_group_update, build_telemetry_group_updates,
+    parameter_name, registration_flow_messages, registration_layout, telemetry_group_layout,
+    truncate_status_text, RegistrationLayout, StatusMessageKind, TelemetryGroupLayout,
+    BROADCAST_ID, FIELD_ID_MASK, MAX_STATUS_LEN, TELEMETRY_ID_BIT,
 };
+pub use telemetry_schedule::TelemetrySchedule;
 
 pub fn handle_message(msg: &CanMessage, emulator_data: &mut EmulatorData) -> Vec<CanMessage> {
     message_handler::handle_message(msg, emulator_data)
 }
 
-pub fn parse_can_message(frame: CanAnyFrame) -> Result<(CanMessageId, CanMessage)> {
-    let CanAnyFrame::Fd(frame) = frame else {
-        panic!("Only CAN FD frames are supported");
+pub fn parse_can_message(
+    frame: CanAnyFrame,
+) -> Result<(CanMessageId, CanMessage), ParseFrameError> {
+    let frame = match frame {
+        CanAnyFrame::Fd(frame) => frame,
+        CanAnyFrame::Normal(_) => {
+            return Err(ParseFrameError::NonFdFrame {
+                kind: "Classic CAN",
+            })
+        }
+        CanAnyFrame::Remote(_) => return Err(ParseFrameError::NonFdFrame { kind: "Remote" }),
+        CanAnyFrame::Error(_) => return Err(ParseFrameError::NonFdFrame { kind: "Error" }),
     };
-    let Id::Standard(raw_id) = frame.id() else {
-        panic!("Only standard CAN IDs are supported");
+    let raw_id = match frame.id() {
+        Id::Standard(raw_id) => raw_id,
+        Id::Extended(raw_id) => {
+            return Err(ParseFrameError::ExtendedId {
+                raw_id: raw_id.as_raw(),
+            })
+        }
     };
     let id = CanMessageId::from_bytes(raw_id.as_raw().to_le_bytes());
-    let message: CanMessage = frame
-        .try_into()
-        .map_err(|e| anyhow!("Failed to parse CAN message data: {}", e))?;
+    let message = CanMessage::try_from(frame).map_err(|e| ParseFrameError::Malformed {
+        reason: e.to_string(),
+    })?;
 
     Ok((id, message))
 }
@@ -0,0 +1,45 @@
+use crate::config::config_representation::EmulatorData;
+use crate::message_kind::MessageKind;
+use liquidcan::CanMessage;
+
+/// Message kinds mirrored to the servers unless the `Routing` table lists them.
+const DEFAULT_SERVER_ROUTES: [MessageKind; 2] = [
+    MessageKind::ParameterSetConfirmation,
+    MessageKind::ParameterSetLockConfirmation,
+];
+
+/// Node IDs that receive a copy of every `kind` message on top of its addressed receiver. The
+/// `Routing` table overrides the default recipients of the kinds it lists.
+pub fn extra_recipients(emulator_data: &EmulatorData, kind: MessageKind) -> &[u8] {
+    let configured = emulator_data
+        .routing
+        .as_ref()
+        .and_then(|routing| routing.get(&kind));
+    match configured {
+        Some(recipients) => recipients,
+        None if DEFAULT_SERVER_ROUTES.contains(&kind) => &emulator_data.server_ids,
+        None => &[],
+    }
+}
+
+/// Pairs each message with every node it goes to: the given receivers plus the extra
+/// recipients its kind is routed to. Each node gets a message at most once.
+pub fn route_messages(
+    emulator_data: &EmulatorData,
+    receivers: &[u8],
+    messages: Vec<CanMessage>,
+) -> Vec<(u8, CanMessage)> {
+    let mut routed = Vec::new();
+    for msg in messages {
+        let mut recipients = receivers.to_vec();
+        for &extra in extra_recipients(emulator_data, MessageKind::of(&msg)) {
+            if !recipients.contains(&extra) {
+                recipients.push(extra);
+            }
+        }
+        for receiver in recipients {
+            routed.push((receiver, msg.clone()));
+        }
+    }
+    routed
+}
@@ -0,0 +1,72 @@
+use crate::config::config_representation::EmulatorData;
+use crate::message_handling::message_handler::{
+    telemetry_group_layout, telemetry_group_update, TelemetryGroupLayout,
+};
+use liquidcan::CanMessage;
+use std::time::{Duration, Instant};
+
+struct ScheduledGroup {
+    layout: TelemetryGroupLayout,
+    interval: Duration,
+    next_due: Instant,
+}
+
+/// Tracks when each telemetry group is due for its next `TelemetryGroupUpdate`. The group
+/// layout is worked out once; build a new schedule when the config changes.
+pub struct TelemetrySchedule {
+    groups: Vec<ScheduledGroup>,
+}
+
+impl TelemetrySchedule {
+    /// Groups with rate 0, or a rate so low that their first update would never be due, are
+    /// not scheduled.
+    pub fn new(emulator_data: &EmulatorData, now: Instant) -> Self {
+        let groups = telemetry_group_layout(emulator_data)
+            .into_iter()
+            .filter(|group| group.rate > 0.0)
+            .filter_map(|layout| {
+                let interval = Duration::try_from_secs_f64(1.0 / layout.rate).ok()?;
+                Some(ScheduledGroup {
+                    layout,
+                    interval,
+                    next_due: now.checked_add(interval)?,
+                })
+            })
+            .collect();
+        Self { groups }
+    }
+
+    /// The `TelemetryGroupUpdate` of a scheduled group with the current telemetry values.
+    pub fn update(&self, emulator_data: &EmulatorData, group_id: u8) -> Option<CanMessage> {
+        let telemetry = emulator_data.telemetry_values.as_ref()?;
+        self.groups
+            .iter()
+            .find(|group| group.layout.group_id == group_id)
+            .map(|group| telemetry_group_update(telemetry, &group.layout))
+    }
+
+    /// Returns the IDs of all groups due at `now` and schedules their next update.
+    /// A group that fell behind by more than one interval skips the missed updates.
+    pub fn due_groups(&mut self, now: Instant) -> Vec<u8> {
+        let mut due = Vec::new();
+        for group in &mut self.groups {
+            if now < group.next_due {
+                continue;
+            }
+            due.push(group.layout.group_id);
+            group.next_due = match group.next_due.checked_add(group.interval) {
+                Some(next_due) if next_due > now => next_due,
+                _ => now.checked_add(group.interval).unwrap_or(group.next_due),
+            };
+        }
+        due
+    }
+
+    /// Time until the next group is due, if any group is scheduled at all.
+    pub fn time_until_next(&self, now: Instant) -> Option<Duration> {
+        self.groups
+            .iter()
+            .map(|group| group.next_due.saturating_duration_since(now))
+            .min()
+    }
+}
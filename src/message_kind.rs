@@ -0,0 +1,131 @@
+//! The kinds of LiquidCAN messages, by which config tables, faults and the sniffer select
+//! messages.
+
+use liquidcan::CanMessage;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// The type of a LiquidCAN message, independent of its payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MessageKind {
+    NodeInfoReq,
+    NodeInfoAnnouncement,
+    InfoStatus,
+    WarningStatus,
+    ErrorStatus,
+    TelemetryValueRegistration,
+    ParameterRegistration,
+    TelemetryGroupDefinition,
+    TelemetryGroupUpdate,
+    HeartbeatReq,
+    HeartbeatRes,
+    ParameterSetReq,
+    ParameterSetConfirmation,
+    ParameterSetLockReq,
+    ParameterSetLockConfirmation,
+    FieldGetReq,
+    FieldGetRes,
+    FieldIdLookupReq,
+    FieldIdLookupRes,
+    /// Any message this emulator does not know by name.
+    Other,
+}
+
+/// Config names of every routable message kind.
+const NAMES: [(MessageKind, &str); 19] = [
+    (MessageKind::NodeInfoReq, "node_info_req"),
+    (MessageKind::NodeInfoAnnouncement, "node_info_announcement"),
+    (MessageKind::InfoStatus, "info_status"),
+    (MessageKind::WarningStatus, "warning_status"),
+    (MessageKind::ErrorStatus, "error_status"),
+    (
+        MessageKind::TelemetryValueRegistration,
+        "telemetry_value_registration",
+    ),
+    (MessageKind::ParameterRegistration, "parameter_registration"),
+    (
+        MessageKind::TelemetryGroupDefinition,
+        "telemetry_group_definition",
+    ),
+    (MessageKind::TelemetryGroupUpdate, "telemetry_group_update"),
+    (MessageKind::HeartbeatReq, "heartbeat_req"),
+    (MessageKind::HeartbeatRes, "heartbeat_res"),
+    (MessageKind::ParameterSetReq, "parameter_set_req"),
+    (
+        MessageKind::ParameterSetConfirmation,
+        "parameter_set_confirmation",
+    ),
+    (MessageKind::ParameterSetLockReq, "parameter_set_lock_req"),
+    (
+        MessageKind::ParameterSetLockConfirmation,
+        "parameter_set_lock_confirmation",
+    ),
+    (MessageKind::FieldGetReq, "field_get_req"),
+    (MessageKind::FieldGetRes, "field_get_res"),
+    (MessageKind::FieldIdLookupReq, "field_id_lookup_req"),
+    (MessageKind::FieldIdLookupRes, "field_id_lookup_res"),
+];
+
+impl MessageKind {
+    pub fn of(msg: &CanMessage) -> Self {
+        match msg {
+            CanMessage::NodeInfoReq => MessageKind::NodeInfoReq,
+            CanMessage::NodeInfoAnnouncement { .. } => MessageKind::NodeInfoAnnouncement,
+            CanMessage::InfoStatus { .. } => MessageKind::InfoStatus,
+            CanMessage::WarningStatus { .. } => MessageKind::WarningStatus,
+            CanMessage::ErrorStatus { .. } => MessageKind::ErrorStatus,
+            CanMessage::TelemetryValueRegistration { .. } => {
+                MessageKind::TelemetryValueRegistration
+            }
+            CanMessage::ParameterRegistration { .. } => MessageKind::ParameterRegistration,
+            CanMessage::TelemetryGroupDefinition { .. } => MessageKind::TelemetryGroupDefinition,
+            CanMessage::TelemetryGroupUpdate { .. } => MessageKind::TelemetryGroupUpdate,
+            CanMessage::HeartbeatReq { .. } => MessageKind::HeartbeatReq,
+            CanMessage::HeartbeatRes { .. } => MessageKind::HeartbeatRes,
+            CanMessage::ParameterSetReq { .. } => MessageKind::ParameterSetReq,
+            CanMessage::ParameterSetConfirmation { .. } => MessageKind::ParameterSetConfirmation,
+            CanMessage::ParameterSetLockReq { .. } => MessageKind::ParameterSetLockReq,
+            CanMessage::ParameterSetLockConfirmation { .. } => {
+                MessageKind::ParameterSetLockConfirmation
+            }
+            CanMessage::FieldGetReq { .. } => MessageKind::FieldGetReq,
+            CanMessage::FieldGetRes { .. } => MessageKind::FieldGetRes,
+            CanMessage::FieldIDLookupReq { .. } => MessageKind::FieldIdLookupReq,
+            CanMessage::FieldIDLookupRes { .. } => MessageKind::FieldIdLookupRes,
+            #[allow(unreachable_patterns)]
+            _ => MessageKind::Other,
+        }
+    }
+}
+
+impl fmt::Display for MessageKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = NAMES
+            .iter()
+            .find(|(kind, _)| kind == self)
+            .map_or("other", |&(_, name)| name);
+        f.write_str(name)
+    }
+}
+
+impl FromStr for MessageKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        NAMES
+            .iter()
+            .find(|(_, name)| *name == s)
+            .map(|&(kind, _)| kind)
+            .ok_or_else(|| format!("unknown message kind {s}"))
+    }
+}
+
+impl<'de> Deserialize<'de> for MessageKind {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
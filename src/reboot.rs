@@ -0,0 +1,144 @@
+//! Rebooting emulated nodes, as when an ECU resets mid-session or restarts with new firmware.
+//!
+//! A rebooting node goes silent for its boot time. Its parameters fall back to their defaults,
+//! unless a state file keeps them, or the node swaps to an alternative config with its own
+//! hashes and fields. Then it sends the registration flow again. A reboot is started from the
+//! control API, a scenario step or SIGUSR1, which reboots every node with its `[Reboot]` table.
+
+use crate::config::config_loader::load_nodes;
+use crate::config::config_representation::EmulatorData;
+use crate::emulator::{lock, EmulatorHandle};
+use crate::reload;
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use signal_hook::consts::SIGUSR1;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A node's `[Reboot]` table: how it reboots unless the trigger says otherwise.
+#[derive(Deserialize, Debug, Clone)]
+pub struct RebootConfig {
+    /// Seconds the node stays silent.
+    #[serde(default = "default_boot_time")]
+    pub boot_time: f64,
+    /// Config file the node comes back with, e.g. with another `firmware_hash` or other fields.
+    pub config: Option<PathBuf>,
+}
+
+impl Default for RebootConfig {
+    fn default() -> Self {
+        Self {
+            boot_time: default_boot_time(),
+            config: None,
+        }
+    }
+}
+
+fn default_boot_time() -> f64 {
+    1.0
+}
+
+/// A reboot waiting for the emulator's next step.
+#[derive(Debug)]
+pub struct Reboot {
+    pub boot_time: Duration,
+    /// The config to come back with; `None` keeps the running one.
+    pub config: Option<EmulatorData>,
+}
+
+impl Reboot {
+    /// A reboot of the node whose data is `data`. `boot_time` and `config` override the node's
+    /// `[Reboot]` table. The data is only locked to read that table and to check the alternative
+    /// config against the running one; the file loads without it, so the node keeps running.
+    pub fn load(
+        data: &Mutex<EmulatorData>,
+        boot_time: Option<f64>,
+        config: Option<&Path>,
+    ) -> Result<Self> {
+        let (node_id, (boot_time, path)) = {
+            let data = lock(data);
+            (data.node_id, settings(&data, boot_time, config)?)
+        };
+        let config = match path {
+            Some(path) => {
+                let node = load_node(&path, node_id)?;
+                reload::check_compatible(&lock(data), &node)?;
+                Some(node)
+            }
+            None => None,
+        };
+        Ok(Self { boot_time, config })
+    }
+}
+
+/// A reboot a scenario step asked for, before its alternative config is loaded.
+#[derive(Debug, Clone)]
+pub struct RebootRequest {
+    pub boot_time: Option<f64>,
+    pub config: Option<PathBuf>,
+}
+
+/// The boot time and alternative config path of a reboot, from the node's `[Reboot]` table
+/// unless `boot_time` or `config` override it.
+fn settings(
+    emulator_data: &EmulatorData,
+    boot_time: Option<f64>,
+    config: Option<&Path>,
+) -> Result<(Duration, Option<PathBuf>)> {
+    let defaults = emulator_data.reboot.clone().unwrap_or_default();
+    let boot_time = boot_time.unwrap_or(defaults.boot_time);
+    if !(boot_time.is_finite() && boot_time >= 0.0) {
+        return Err(anyhow!("Invalid boot time {boot_time} (must be >= 0)"));
+    }
+    let path = config.map(Path::to_path_buf).or(defaults.config);
+    Ok((Duration::from_secs_f64(boot_time), path))
+}
+
+/// Loads the config a node comes back with after a reboot. A config with several nodes has to
+/// list the rebooting node; everything may change but the node's ID and interface.
+pub fn load_alternative_config(path: &Path, running: &EmulatorData) -> Result<EmulatorData> {
+    let node = load_node(path, running.node_id)?;
+    reload::check_compatible(running, &node)?;
+    Ok(node)
+}
+
+/// Loads the node `node_id` from the config file at `path`.
+fn load_node(path: &Path, node_id: u32) -> Result<EmulatorData> {
+    let mut nodes = load_nodes(&path.to_string_lossy())
+        .with_context(|| format!("Failed to load reboot config {}", path.display()))?;
+    let idx = nodes
+        .iter()
+        .position(|node| node.node_id == node_id)
+        .ok_or_else(|| {
+            anyhow!(
+                "Node {node_id} is missing from reboot config {}",
+                path.display()
+            )
+        })?;
+    Ok(nodes.swap_remove(idx))
+}
+
+/// Reboots every node of `handles` with its `[Reboot]` table each time the process receives
+/// SIGUSR1.
+pub fn spawn(handles: Vec<EmulatorHandle>) -> io::Result<JoinHandle<()>> {
+    let signaled = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(SIGUSR1, Arc::clone(&signaled))?;
+    Ok(thread::spawn(move || loop {
+        thread::sleep(POLL_INTERVAL);
+        if !signaled.swap(false, Ordering::SeqCst) {
+            continue;
+        }
+        for handle in &handles {
+            match handle.reboot(None, None) {
+                Ok(()) => println!("Rebooting node {}", handle.node_id()),
+                Err(err) => eprintln!("Error rebooting node {}: {err:?}", handle.node_id()),
+            }
+        }
+    }))
+}
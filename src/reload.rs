@@ -0,0 +1,338 @@
+//! Reloading the config file of running emulators, when it changes on disk or the process
+//! receives SIGHUP.
+
+use crate::config::config_loader::load_nodes;
+use crate::config::config_representation::{EmulatorData, Parameter, TelemetryValue};
+use crate::emulator::EmulatorHandle;
+use crate::message_handling::{build_status_message, registration_layout, StatusMessageKind};
+use crate::simulation::actuator;
+use crate::simulation::generator::Generator;
+use anyhow::{bail, Result};
+use liquidcan::payloads::CanDataValue;
+use signal_hook::consts::SIGHUP;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+const RELOAD_FAILED: &str = "Config reload failed, keeping the old config";
+
+/// How a reloaded config differs from the running one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigChange {
+    /// Only values, lock states, generators, rates or routing changed.
+    Values,
+    /// Fields or telemetry groups were added, removed or changed; servers have to be sent the
+    /// registration flow again.
+    Layout,
+}
+
+/// The values and lock states a config file gives its fields. A reload compares the file with
+/// them, so that only values the file changed overwrite those set at runtime.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigValues {
+    telemetry: HashMap<String, CanDataValue>,
+    parameters: HashMap<String, (CanDataValue, bool)>,
+}
+
+impl ConfigValues {
+    /// The values of `emulator_data` before anything changed them at runtime.
+    pub fn of(emulator_data: &EmulatorData) -> Self {
+        Self {
+            telemetry: emulator_data
+                .telemetry_values
+                .iter()
+                .flatten()
+                .map(|tel| (tel.name.clone(), tel.value.clone()))
+                .collect(),
+            parameters: emulator_data
+                .parameters
+                .iter()
+                .flatten()
+                .map(|param| (param.name.clone(), (param.value.clone(), param.locked)))
+                .collect(),
+        }
+    }
+
+    /// The configured value and lock state of the parameter `name`.
+    pub fn parameter(&self, name: &str) -> Option<&(CanDataValue, bool)> {
+        self.parameters.get(name)
+    }
+}
+
+/// What a reloaded config changes, field by field.
+#[derive(Debug, Default, PartialEq)]
+pub struct ConfigChanges {
+    /// Fields or telemetry groups were added, removed or changed.
+    pub layout: bool,
+    /// Telemetry values that are new or whose configured value, generator or expression changed.
+    pub telemetry: Vec<String>,
+    /// Parameters that are new or whose configured value changed.
+    pub parameter_values: Vec<String>,
+    /// Parameters that are new or whose configured lock state changed.
+    pub parameter_locks: Vec<String>,
+}
+
+impl ConfigChanges {
+    pub fn kind(&self) -> ConfigChange {
+        if self.layout {
+            ConfigChange::Layout
+        } else {
+            ConfigChange::Values
+        }
+    }
+}
+
+/// Fails if `reloaded` cannot replace the running config without a restart.
+pub fn check_compatible(running: &EmulatorData, reloaded: &EmulatorData) -> Result<()> {
+    if running.node_id != reloaded.node_id {
+        bail!(
+            "node_id cannot change from {} to {} while running",
+            running.node_id,
+            reloaded.node_id
+        );
+    }
+    if running.can_interface != reloaded.can_interface {
+        bail!(
+            "can_interface of node {} cannot change while running",
+            running.node_id
+        );
+    }
+    if running.kernel_filter != reloaded.kernel_filter {
+        bail!(
+            "kernel_filter of node {} cannot change while running",
+            running.node_id
+        );
+    }
+    Ok(())
+}
+
+fn find_telemetry<'a>(emulator_data: &'a EmulatorData, name: &str) -> Option<&'a TelemetryValue> {
+    emulator_data
+        .telemetry_values
+        .iter()
+        .flatten()
+        .find(|tel| tel.name == name)
+}
+
+fn find_parameter<'a>(emulator_data: &'a EmulatorData, name: &str) -> Option<&'a Parameter> {
+    emulator_data
+        .parameters
+        .iter()
+        .flatten()
+        .find(|param| param.name == name)
+}
+
+fn same_generator(a: Option<&Generator>, b: Option<&Generator>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.same_settings(b),
+        (a, b) => a.is_none() && b.is_none(),
+    }
+}
+
+/// Compares a reloaded config with the running one, which was loaded with the values in
+/// `configured`. Fails for changes that need a restart.
+pub fn compare(
+    running: &EmulatorData,
+    configured: &ConfigValues,
+    reloaded: &EmulatorData,
+) -> Result<ConfigChanges> {
+    check_compatible(running, reloaded)?;
+    let mut changes = ConfigChanges {
+        layout: registration_layout(running) != registration_layout(reloaded),
+        ..ConfigChanges::default()
+    };
+    for tel in reloaded.telemetry_values.iter().flatten() {
+        let unchanged = find_telemetry(running, &tel.name).is_some_and(|run| {
+            run.datatype == tel.datatype
+                && configured.telemetry.get(&tel.name) == Some(&tel.value)
+                && same_generator(run.generator.as_ref(), tel.generator.as_ref())
+                && run.expr.as_ref().map(ToString::to_string)
+                    == tel.expr.as_ref().map(ToString::to_string)
+        });
+        if !unchanged {
+            changes.telemetry.push(tel.name.clone());
+        }
+    }
+    for param in reloaded.parameters.iter().flatten() {
+        let configured = find_parameter(running, &param.name)
+            .filter(|run| run.datatype == param.datatype)
+            .and(configured.parameter(&param.name));
+        if configured.is_none_or(|(value, _)| *value != param.value) {
+            changes.parameter_values.push(param.name.clone());
+        }
+        if configured.is_none_or(|(_, locked)| *locked != param.locked) {
+            changes.parameter_locks.push(param.name.clone());
+        }
+    }
+    Ok(changes)
+}
+
+/// Applies a reloaded config to the running one. The fields in `changes` take their new value,
+/// lock state or generator. All others keep what they have at runtime, and generators, actuator
+/// models and alarms carry on where they are.
+pub fn apply(running: &mut EmulatorData, mut reloaded: EmulatorData, changes: &ConfigChanges) {
+    for tel in reloaded.telemetry_values.iter_mut().flatten() {
+        if changes.telemetry.contains(&tel.name) {
+            continue;
+        }
+        let run = running
+            .telemetry_values
+            .iter_mut()
+            .flatten()
+            .find(|run| run.name == tel.name);
+        if let Some(run) = run {
+            tel.value = run.value.clone();
+            tel.generator = run.generator.take();
+        }
+    }
+
+    let mut changed = Vec::new();
+    for param in reloaded.parameters.iter_mut().flatten() {
+        let Some(run) = find_parameter(running, &param.name) else {
+            continue;
+        };
+        // A runtime value the new constraints forbid falls back to the configured one.
+        if !changes.parameter_values.contains(&param.name)
+            && param.check_constraints(&run.value).is_ok()
+        {
+            param.value = run.value.clone();
+        }
+        if !changes.parameter_locks.contains(&param.name) {
+            param.locked = run.locked;
+        }
+        if param.value != run.value {
+            changed.push(param.name.clone());
+        }
+    }
+
+    for model in reloaded.models.iter_mut().flatten() {
+        let run = running
+            .models
+            .iter_mut()
+            .flatten()
+            .find(|run| run.name == model.name);
+        if let Some(run) = run {
+            model.keep_state(run);
+        }
+    }
+    for alarm in reloaded.alarms.iter_mut().flatten() {
+        let run = running
+            .alarms
+            .iter()
+            .flatten()
+            .find(|run| run.name == alarm.name);
+        if let Some(run) = run {
+            alarm.keep_level(run);
+        }
+    }
+
+    *running = reloaded;
+    for name in changed {
+        actuator::parameter_changed(running, &name);
+    }
+}
+
+/// Loads the config file again and applies it to the emulators of its nodes. Nothing is
+/// applied unless every node can take its new config.
+pub fn reload_nodes(path: &str, handles: &[EmulatorHandle]) -> Result<Vec<ConfigChange>> {
+    let mut nodes = load_nodes(path)?;
+    if nodes.len() != handles.len() {
+        bail!(
+            "The number of nodes cannot change while running ({} running, {} in {path})",
+            handles.len(),
+            nodes.len()
+        );
+    }
+    let mut updates = Vec::new();
+    for handle in handles {
+        let node_id = handle.node_id();
+        let Some(idx) = nodes.iter().position(|node| node.node_id == node_id as u32) else {
+            bail!("Node {node_id} is missing from {path}");
+        };
+        let node = nodes.swap_remove(idx);
+        handle.with_data(|data| check_compatible(data, &node))?;
+        updates.push((handle, node));
+    }
+    updates
+        .into_iter()
+        .map(|(handle, node)| handle.reload(node))
+        .collect()
+}
+
+/// Reloads the config file like [`reload_nodes`]. On failure the old config stays in place and
+/// every node reports an `ErrorStatus` to its servers.
+pub fn reload_or_report(path: &str, handles: &[EmulatorHandle]) {
+    match reload_nodes(path, handles) {
+        Ok(changes) => {
+            let layout_changes = changes
+                .iter()
+                .filter(|&&change| change == ConfigChange::Layout)
+                .count();
+            println!(
+                "Reloaded {path}; {layout_changes} of {} node(s) registered again",
+                changes.len()
+            );
+        }
+        Err(err) => {
+            eprintln!("Error reloading config file {path}: {err:?}");
+            for handle in handles {
+                handle.send_to_servers(vec![build_status_message(
+                    StatusMessageKind::Error,
+                    RELOAD_FAILED,
+                )]);
+            }
+        }
+    }
+}
+
+/// Notices modifications of a config file and SIGHUP.
+pub struct ConfigWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    hangup: Arc<AtomicBool>,
+}
+
+impl ConfigWatcher {
+    /// Starts watching `path`. From now on, SIGHUP no longer terminates the process.
+    pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let hangup = Arc::new(AtomicBool::new(false));
+        signal_hook::flag::register(SIGHUP, Arc::clone(&hangup))?;
+        Ok(Self {
+            modified: modified(&path),
+            path,
+            hangup,
+        })
+    }
+
+    /// Returns whether the file was modified or SIGHUP was received since the last call.
+    pub fn poll(&mut self) -> bool {
+        let hangup = self.hangup.swap(false, Ordering::SeqCst);
+        let modified = modified(&self.path);
+        let changed = modified != self.modified;
+        self.modified = modified;
+        hangup || changed
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// Watches the config file at `path` and reloads it into the emulators of `handles`.
+pub fn spawn(path: &str, handles: Vec<EmulatorHandle>) -> io::Result<JoinHandle<()>> {
+    let mut watcher = ConfigWatcher::new(path)?;
+    let path = path.to_string();
+    Ok(thread::spawn(move || loop {
+        thread::sleep(POLL_INTERVAL);
+        if watcher.poll() {
+            reload_or_report(&path, &handles);
+        }
+    }))
+}
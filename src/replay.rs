@@ -0,0 +1,70 @@
+use crate::can_manager::candump::LogEntry;
+use liquidcan::CanMessageId;
+use socketcan::{CanFdFrame, EmbeddedFrame, Id};
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub struct ReplayOptions {
+    /// Playback speed relative to the recording; 2.0 plays twice as fast.
+    pub speed: f64,
+    /// Only replay frames sent by this node.
+    pub sender_id: Option<u8>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ReplayStats {
+    pub sent: usize,
+    /// Frames left out by the sender filter.
+    pub filtered: usize,
+    /// Classic CAN and remote frames, which cannot be replayed.
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+fn sender_of(frame: &CanFdFrame) -> Option<u8> {
+    let Id::Standard(raw_id) = frame.id() else {
+        return None;
+    };
+    Some(CanMessageId::from_bytes(raw_id.as_raw().to_le_bytes()).sender_id())
+}
+
+/// Plays `entries` back through `send`, keeping the recorded gaps between frames scaled by
+/// `options.speed`.
+pub fn replay<F>(entries: &[LogEntry], options: &ReplayOptions, mut send: F) -> ReplayStats
+where
+    F: FnMut(&LogEntry, &CanFdFrame) -> io::Result<()>,
+{
+    let mut stats = ReplayStats::default();
+    let Some(first) = entries.first() else {
+        return stats;
+    };
+    let start = Instant::now();
+    for entry in entries {
+        let Some(frame) = entry.frame.as_ref() else {
+            stats.skipped += 1;
+            continue;
+        };
+        if options
+            .sender_id
+            .is_some_and(|sender_id| sender_of(frame) != Some(sender_id))
+        {
+            stats.filtered += 1;
+            continue;
+        }
+        let offset = entry.timestamp.saturating_sub(first.timestamp);
+        let due = start + Duration::from_secs_f64(offset.as_secs_f64() / options.speed);
+        let now = Instant::now();
+        if due > now {
+            thread::sleep(due - now);
+        }
+        match send(entry, frame) {
+            Ok(()) => stats.sent += 1,
+            Err(err) => {
+                eprintln!("Error replaying frame on {}: {err:?}", entry.interface);
+                stats.failed += 1;
+            }
+        }
+    }
+    stats
+}
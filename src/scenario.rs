@@ -0,0 +1,536 @@
+//! Scenarios: a timeline of actions run against the emulated nodes, like a pressure rising after
+//! a valve was opened or a node going quiet for a while.
+//!
+//! A scenario file is a TOML list of `[[steps]]`. Each step fires `at` a time in seconds since
+//! the emulator started, or `on` an event such as `{ parameter_set = "valve_target" }` and
+//! optionally `delay` seconds later, and runs one `action`:
+//!
+//! ```toml
+//! [[steps]]
+//! on = { parameter_set = "valve_target" }
+//! delay = 0.5
+//! action = "ramp"
+//! field = "chamber_pressure"
+//! to = 35.0
+//! duration = 4.0
+//! ```
+
+use crate::config::config_representation::{ConfigScalar, EmulatorData};
+use crate::config::serde_deserializer::{typed_value, value_to_f64};
+use crate::faults::FaultInjector;
+use crate::message_handling::{
+    build_status_message, registration_flow_messages, Event, StatusMessageKind, MAX_STATUS_LEN,
+};
+use crate::message_kind::MessageKind;
+use crate::reboot::{self, RebootRequest};
+use crate::simulation::actuator;
+use crate::simulation::generator::value_from_f64;
+use anyhow::{anyhow, bail, Context, Result};
+use liquidcan::payloads::{CanDataType, CanDataValue};
+use liquidcan::CanMessage;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A parsed scenario file, checked against the nodes it runs on.
+#[derive(Deserialize, Debug)]
+pub struct Scenario {
+    steps: Vec<Step>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct Step {
+    /// Seconds since emulator start.
+    at: Option<f64>,
+    on: Option<Event>,
+    /// Seconds between the event and the action.
+    #[serde(default)]
+    delay: f64,
+    /// The node the step applies to; may be left out while only one node runs.
+    node: Option<u8>,
+    #[serde(flatten)]
+    action: Action,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "action", rename_all = "snake_case")]
+enum Action {
+    /// Overwrites a telemetry value or parameter. Telemetry stops following its generator or
+    /// expression.
+    Set {
+        field: String,
+        value: ConfigScalar,
+    },
+    /// Moves a telemetry value or parameter linearly to `to` within `duration` seconds,
+    /// starting at `from` or at its current value.
+    Ramp {
+        field: String,
+        from: Option<f64>,
+        to: f64,
+        duration: f64,
+    },
+    Lock {
+        parameter: String,
+    },
+    Unlock {
+        parameter: String,
+    },
+    /// Sends a status message to the servers.
+    Status {
+        level: StatusMessageKind,
+        message: String,
+    },
+    /// Stops sending messages of `kinds`, or any message, for `duration` seconds or for good.
+    Silence {
+        #[serde(default)]
+        kinds: Vec<MessageKind>,
+        duration: Option<f64>,
+    },
+    /// Switches on the node's configured fault `fault` for `duration` seconds or for good.
+    Fault {
+        fault: String,
+        duration: Option<f64>,
+    },
+    /// Sends the registration flow again, as if a server had sent a `NodeInfoReq`.
+    Reregister,
+    /// Reboots the node, optionally with another `boot_time` or into another `config` than its
+    /// `[Reboot]` table has.
+    Reboot {
+        boot_time: Option<f64>,
+        config: Option<PathBuf>,
+    },
+}
+
+impl Scenario {
+    /// Reads the scenario at `path` and checks it against `nodes`.
+    pub fn load(path: &Path, nodes: &[EmulatorData]) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&contents, nodes)
+            .with_context(|| format!("Invalid scenario {}", path.display()))
+    }
+
+    /// Parses a scenario and checks every step against the node it applies to.
+    pub fn parse(contents: &str, nodes: &[EmulatorData]) -> Result<Self> {
+        let scenario: Scenario = toml::from_str(contents)?;
+        for (idx, step) in scenario.steps.iter().enumerate() {
+            validate_step(step, nodes).with_context(|| format!("steps[{idx}]"))?;
+        }
+        Ok(scenario)
+    }
+
+    /// A runner for the steps that apply to `node_id`.
+    pub fn runner_for(&self, node_id: u8) -> ScenarioRunner {
+        let steps: Vec<Step> = self
+            .steps
+            .iter()
+            .filter(|step| step.node.is_none_or(|node| node == node_id))
+            .cloned()
+            .collect();
+        let due = steps
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, step)| Some((step.at?, idx)))
+            .collect();
+        ScenarioRunner {
+            node_id,
+            steps,
+            due,
+            ramps: Vec::new(),
+            reboot: None,
+        }
+    }
+}
+
+fn validate_step(step: &Step, nodes: &[EmulatorData]) -> Result<()> {
+    let node = match (step.node, nodes) {
+        (None, [node]) => node,
+        (None, _) => bail!("several nodes are running, the step needs a `node`"),
+        (Some(node_id), _) => nodes
+            .iter()
+            .find(|node| node.node_id == u32::from(node_id))
+            .ok_or_else(|| anyhow!("no node with id {node_id}"))?,
+    };
+    match (step.at, &step.on) {
+        (Some(at), None) => {
+            if !(at.is_finite() && at >= 0.0) {
+                bail!("invalid time {at}");
+            }
+            if step.delay != 0.0 {
+                bail!("`delay` only applies to steps triggered `on` an event");
+            }
+        }
+        (None, Some(Event::ParameterSet(name))) => {
+            if !has_parameter(node, name) {
+                bail!("unknown parameter {name}");
+            }
+            if !(step.delay.is_finite() && step.delay >= 0.0) {
+                bail!("invalid delay {}", step.delay);
+            }
+        }
+        (None, None) => bail!("needs either `at` or `on`"),
+        (Some(_), Some(_)) => bail!("cannot have both `at` and `on`"),
+    }
+
+    match &step.action {
+        Action::Set { field, value } => {
+            let datatype = writable_field(node, field)?;
+            typed_value(value, datatype).map_err(|e| anyhow!("{field}: {e}"))?;
+        }
+        Action::Ramp {
+            field,
+            from,
+            to,
+            duration,
+        } => {
+            writable_field(node, field)?;
+            if !to.is_finite() || from.is_some_and(|from| !from.is_finite()) {
+                bail!("invalid ramp bounds for {field}");
+            }
+            if !(duration.is_finite() && *duration > 0.0) {
+                bail!("invalid duration {duration}");
+            }
+        }
+        Action::Lock { parameter } | Action::Unlock { parameter } => {
+            if !has_parameter(node, parameter) {
+                bail!("unknown parameter {parameter}");
+            }
+        }
+        Action::Status { message, .. } => {
+            if message.len() > MAX_STATUS_LEN {
+                bail!("status message is longer than {MAX_STATUS_LEN} bytes");
+            }
+        }
+        Action::Silence { duration, .. } => check_duration(*duration)?,
+        Action::Fault { fault, duration } => {
+            if !node.faults.iter().flatten().any(|f| f.name == *fault) {
+                bail!("unknown fault {fault}");
+            }
+            check_duration(*duration)?;
+        }
+        Action::Reregister => {}
+        Action::Reboot { boot_time, config } => {
+            if let Some(boot_time) = boot_time.filter(|t| !(t.is_finite() && *t >= 0.0)) {
+                bail!("invalid boot time {boot_time}");
+            }
+            if let Some(config) = config {
+                reboot::load_alternative_config(config, node)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_duration(duration: Option<f64>) -> Result<()> {
+    if let Some(duration) = duration.filter(|d| !(d.is_finite() && *d > 0.0)) {
+        bail!("invalid duration {duration}");
+    }
+    Ok(())
+}
+
+fn has_parameter(emulator_data: &EmulatorData, name: &str) -> bool {
+    emulator_data
+        .parameters
+        .iter()
+        .flatten()
+        .any(|param| param.name == name)
+}
+
+/// The datatype of the telemetry value or parameter `name`, if a scenario may write it.
+fn writable_field(emulator_data: &EmulatorData, name: &str) -> Result<CanDataType> {
+    if let Some(tel) = emulator_data
+        .telemetry_values
+        .iter()
+        .flatten()
+        .find(|tel| tel.name == name)
+    {
+        if let Some(model) = actuator::driving_model(emulator_data, name) {
+            bail!("telemetry value {name} is driven by model {}", model.name);
+        }
+        return Ok(tel.datatype);
+    }
+    emulator_data
+        .parameters
+        .iter()
+        .flatten()
+        .find(|param| param.name == name)
+        .map(|param| param.datatype)
+        .ok_or_else(|| anyhow!("unknown field {name}"))
+}
+
+/// Overwrites the telemetry value or parameter `name` with the value `value_for` gives for its
+/// datatype.
+fn write_field(
+    emulator_data: &mut EmulatorData,
+    name: &str,
+    value_for: impl FnOnce(CanDataType) -> Result<CanDataValue, String>,
+) -> Result<(), String> {
+    if let Some(tel) = emulator_data
+        .telemetry_values
+        .iter_mut()
+        .flatten()
+        .find(|tel| tel.name == name)
+    {
+        tel.value = value_for(tel.datatype)?;
+        tel.generator = None;
+        tel.expr = None;
+        return Ok(());
+    }
+    let param = emulator_data
+        .parameters
+        .iter_mut()
+        .flatten()
+        .find(|param| param.name == name)
+        .ok_or_else(|| format!("unknown field {name}"))?;
+    param.value = value_for(param.datatype)?;
+    actuator::parameter_changed(emulator_data, name);
+    Ok(())
+}
+
+fn set_locked(emulator_data: &mut EmulatorData, name: &str, locked: bool) {
+    for param in emulator_data.parameters.iter_mut().flatten() {
+        if param.name == name {
+            param.locked = locked;
+        }
+    }
+}
+
+fn field_value(emulator_data: &EmulatorData, name: &str) -> Option<f64> {
+    let tel = emulator_data
+        .telemetry_values
+        .iter()
+        .flatten()
+        .find(|tel| tel.name == name)
+        .map(|tel| &tel.value);
+    let param = || {
+        emulator_data
+            .parameters
+            .iter()
+            .flatten()
+            .find(|param| param.name == name)
+            .map(|param| &param.value)
+    };
+    value_to_f64(tel.or_else(param)?)
+}
+
+#[derive(Debug)]
+struct Ramp {
+    field: String,
+    from: f64,
+    to: f64,
+    start: f64,
+    duration: f64,
+}
+
+/// Runs the scenario steps of one node against its data, driven by the emulator's clock.
+#[derive(Debug)]
+pub struct ScenarioRunner {
+    node_id: u8,
+    steps: Vec<Step>,
+    /// Steps waiting to run, with their time in seconds since emulator start. Steps with `at`
+    /// run once; steps with `on` are queued again every time their event happens.
+    due: Vec<(f64, usize)>,
+    ramps: Vec<Ramp>,
+    /// A reboot a step asked for, until the emulator takes it.
+    reboot: Option<RebootRequest>,
+}
+
+impl ScenarioRunner {
+    /// Queues the steps triggered by `event`, which happened at `t` seconds since emulator start.
+    pub fn notify(&mut self, event: &Event, t: f64) {
+        for (idx, step) in self.steps.iter().enumerate() {
+            if step.on.as_ref() == Some(event) {
+                self.due.push((t + step.delay, idx));
+            }
+        }
+    }
+
+    /// Seconds from `t` until the next queued step is due.
+    pub fn time_until_next(&self, t: f64) -> Option<f64> {
+        self.due
+            .iter()
+            .map(|&(at, _)| (at - t).max(0.0))
+            .min_by(f64::total_cmp)
+    }
+
+    /// Runs the steps due at `t` seconds since emulator start and moves the running ramps.
+    /// Returns the messages to send to the servers.
+    pub fn advance(
+        &mut self,
+        t: f64,
+        emulator_data: &mut EmulatorData,
+        faults: &mut FaultInjector,
+    ) -> Vec<CanMessage> {
+        let (mut due, pending): (Vec<_>, Vec<_>) = self.due.drain(..).partition(|&(at, _)| at <= t);
+        self.due = pending;
+        due.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+        let mut messages = Vec::new();
+        for (at, idx) in due {
+            let action = self.steps[idx].action.clone();
+            println!("Scenario: node {} at {at:.3}s: {action:?}", self.node_id);
+            self.run(action, at, emulator_data, faults, &mut messages);
+        }
+        self.move_ramps(t, emulator_data);
+        messages
+    }
+
+    fn run(
+        &mut self,
+        action: Action,
+        at: f64,
+        emulator_data: &mut EmulatorData,
+        faults: &mut FaultInjector,
+        messages: &mut Vec<CanMessage>,
+    ) {
+        match action {
+            Action::Set { field, value } => {
+                self.ramps.retain(|ramp| ramp.field != field);
+                let res = write_field(emulator_data, &field, |datatype| {
+                    typed_value(&value, datatype)
+                });
+                if let Err(err) = res {
+                    eprintln!(
+                        "Error running scenario step on node {}: {err}",
+                        self.node_id
+                    );
+                }
+            }
+            Action::Ramp {
+                field,
+                from,
+                to,
+                duration,
+            } => {
+                let Some(from) = from.or_else(|| field_value(emulator_data, &field)) else {
+                    eprintln!(
+                        "Error running scenario step on node {}: {field} has no numeric value",
+                        self.node_id
+                    );
+                    return;
+                };
+                self.ramps.retain(|ramp| ramp.field != field);
+                self.ramps.push(Ramp {
+                    field,
+                    from,
+                    to,
+                    start: at,
+                    duration,
+                });
+            }
+            Action::Lock { parameter } => set_locked(emulator_data, &parameter, true),
+            Action::Unlock { parameter } => set_locked(emulator_data, &parameter, false),
+            Action::Status { level, message } => {
+                messages.push(build_status_message(level, &message));
+            }
+            Action::Silence { kinds, duration } => {
+                faults.silence(kinds, duration.map(|duration| at + duration));
+            }
+            Action::Fault { fault, duration } => {
+                let until = duration.map_or(f64::INFINITY, |duration| at + duration);
+                if !faults.activate(emulator_data, &fault, until) {
+                    eprintln!(
+                        "Error running scenario step on node {}: unknown fault {fault}",
+                        self.node_id
+                    );
+                }
+            }
+            Action::Reregister => messages.extend(registration_flow_messages(emulator_data)),
+            Action::Reboot { boot_time, config } => {
+                // The emulator loads the config once it no longer holds the node's data.
+                self.reboot = Some(RebootRequest { boot_time, config });
+            }
+        }
+    }
+
+    /// The reboot the steps run so far asked for, if any.
+    pub fn take_reboot(&mut self) -> Option<RebootRequest> {
+        self.reboot.take()
+    }
+
+    fn move_ramps(&mut self, t: f64, emulator_data: &mut EmulatorData) {
+        let node_id = self.node_id;
+        self.ramps.retain(|ramp| {
+            let progress = ((t - ramp.start) / ramp.duration).clamp(0.0, 1.0);
+            let value = ramp.from + (ramp.to - ramp.from) * progress;
+            let res = write_field(emulator_data, &ramp.field, |datatype| {
+                Ok(value_from_f64(value, datatype))
+            });
+            if let Err(err) = &res {
+                eprintln!("Error running scenario step on node {node_id}: {err}");
+            }
+            res.is_ok() && progress < 1.0
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_parse_with_their_action() {
+        let scenario: Scenario = toml::from_str(
+            r#"
+            [[steps]]
+            at = 2
+            action = "ramp"
+            field = "pressure"
+            to = 35
+            duration = 4
+
+            [[steps]]
+            on = { parameter_set = "valve_target" }
+            delay = 0.5
+            node = 3
+            action = "silence"
+            kinds = ["heartbeat_res"]
+            "#,
+        )
+        .unwrap();
+
+        let step = &scenario.steps[0];
+        assert_eq!(step.at, Some(2.0));
+        assert!(matches!(
+            step.action,
+            Action::Ramp {
+                from: None,
+                to: 35.0,
+                duration: 4.0,
+                ..
+            }
+        ));
+        let step = &scenario.steps[1];
+        assert_eq!(
+            step.on,
+            Some(Event::ParameterSet("valve_target".to_string()))
+        );
+        assert_eq!(step.node, Some(3));
+        let Action::Silence { kinds, duration } = &step.action else {
+            panic!("expected a silence action, got {:?}", step.action);
+        };
+        assert_eq!(kinds, &vec![MessageKind::HeartbeatRes]);
+        assert_eq!(*duration, None);
+    }
+
+    #[test]
+    fn runner_picks_the_steps_of_its_node() {
+        let scenario: Scenario = toml::from_str(
+            r#"
+            [[steps]]
+            at = 1
+            node = 3
+            action = "reregister"
+
+            [[steps]]
+            at = 2
+            node = 4
+            action = "reregister"
+            "#,
+        )
+        .unwrap();
+        let runner = scenario.runner_for(4);
+        assert_eq!(runner.steps.len(), 1);
+        assert_eq!(runner.time_until_next(0.5), Some(1.5));
+    }
+}
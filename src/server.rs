@@ -0,0 +1,733 @@
+//! The LLServer side of LiquidCAN: registers a node, polls its fields, runs heartbeats and
+//! exercises parameter sets and locks, reporting every deviation from the protocol.
+
+use crate::can_manager::make_message_id;
+use crate::can_manager::socket_manager;
+use crate::can_manager::transport::CanTransport;
+use crate::message_handling::{
+    parse_can_message, typed_from_value, BROADCAST_ID, FIELD_ID_MASK, TELEMETRY_ID_BIT,
+};
+use crate::value_format::value_json;
+use liquidcan::payloads::{self, CanDataType, CanDataValue};
+use liquidcan::CanMessage;
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+pub struct ServerOptions {
+    /// Node ID the requests are sent from; the node answers to it.
+    pub server_id: u8,
+    /// The node under test.
+    pub node_id: u8,
+    /// How long to wait for each response, and for the registration flow to go quiet.
+    pub timeout: Duration,
+    /// Number of heartbeat requests to send.
+    pub heartbeats: u32,
+}
+
+/// A field as the node registered it.
+pub struct Field {
+    /// On-wire field ID; telemetry IDs carry the telemetry bit.
+    pub field_id: u8,
+    pub name: String,
+    pub data_type: CanDataType,
+    /// The value last read with a `FieldGetReq`.
+    pub value: Option<CanDataValue>,
+}
+
+/// Everything the node's registration flow announced.
+#[derive(Default)]
+pub struct FieldDictionary {
+    pub device_name: String,
+    pub telemetry: Vec<Field>,
+    pub parameters: Vec<Field>,
+    /// Field IDs by telemetry group ID, in the order the groups were defined.
+    pub groups: Vec<(u8, Vec<u8>)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Passed,
+    Failed,
+    /// The node offers nothing to run the check against, e.g. no writable parameter.
+    Skipped,
+}
+
+pub struct Check {
+    pub name: String,
+    pub outcome: Outcome,
+    pub detail: String,
+}
+
+pub struct ConformanceReport {
+    pub node_id: u8,
+    pub dictionary: FieldDictionary,
+    pub checks: Vec<Check>,
+}
+
+impl ConformanceReport {
+    pub fn passed(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| check.outcome != Outcome::Failed)
+    }
+
+    pub fn check(&self, name: &str) -> Option<&Check> {
+        self.checks.iter().find(|check| check.name == name)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("Conformance report for node {}", self.node_id);
+        if !self.dictionary.device_name.is_empty() {
+            text.push_str(&format!(" ({})", self.dictionary.device_name));
+        }
+        text.push('\n');
+        for check in &self.checks {
+            let outcome = match check.outcome {
+                Outcome::Passed => "PASS",
+                Outcome::Failed => "FAIL",
+                Outcome::Skipped => "SKIP",
+            };
+            text.push_str(&format!("{outcome} {}: {}\n", check.name, check.detail));
+        }
+        let count = |outcome| {
+            self.checks
+                .iter()
+                .filter(|check| check.outcome == outcome)
+                .count()
+        };
+        text.push_str(&format!(
+            "{} passed, {} failed, {} skipped: {}",
+            count(Outcome::Passed),
+            count(Outcome::Failed),
+            count(Outcome::Skipped),
+            if self.passed() { "PASS" } else { "FAIL" }
+        ));
+        text
+    }
+}
+
+/// Plays the LLServer against `options.node_id` and reports how the node answered.
+/// Only the current values of parameters are written back, so the node's state is kept.
+pub fn check_node<T: CanTransport + ?Sized>(
+    transport: &mut T,
+    options: &ServerOptions,
+) -> ConformanceReport {
+    let mut session = Session {
+        transport,
+        options,
+        dictionary: FieldDictionary::default(),
+        checks: Vec::new(),
+        updates: Vec::new(),
+        undecodable: 0,
+    };
+    if session.register() {
+        session.get_fields();
+        session.heartbeats();
+        let writable = session.set_parameters();
+        session.locks(writable);
+        session.group_updates();
+    }
+    let undecodable = session.undecodable;
+    session.check(
+        "frames decode",
+        undecodable == 0,
+        format!("{undecodable} undecodable frame(s)"),
+    );
+    ConformanceReport {
+        node_id: options.node_id,
+        dictionary: session.dictionary,
+        checks: session.checks,
+    }
+}
+
+struct Session<'a, T: ?Sized> {
+    transport: &'a mut T,
+    options: &'a ServerOptions,
+    dictionary: FieldDictionary,
+    checks: Vec<Check>,
+    /// Telemetry group updates received while waiting for responses.
+    updates: Vec<payloads::TelemetryGroupUpdatePayload>,
+    undecodable: usize,
+}
+
+fn field_of(payload: &payloads::FieldRegistrationPayload) -> Field {
+    Field {
+        field_id: payload.field_id,
+        name: payload.field_name.clone().into(),
+        data_type: payload.field_type,
+        value: None,
+    }
+}
+
+/// Field IDs that appear more than once.
+fn duplicates(ids: impl Iterator<Item = u8>) -> Vec<u8> {
+    let mut seen = HashSet::new();
+    ids.filter(|&id| !seen.insert(id)).collect()
+}
+
+impl<T: CanTransport + ?Sized> Session<'_, T> {
+    fn check(&mut self, name: impl Into<String>, passed: bool, detail: impl Into<String>) {
+        self.record(
+            name,
+            if passed {
+                Outcome::Passed
+            } else {
+                Outcome::Failed
+            },
+            detail,
+        );
+    }
+
+    fn record(&mut self, name: impl Into<String>, outcome: Outcome, detail: impl Into<String>) {
+        self.checks.push(Check {
+            name: name.into(),
+            outcome,
+            detail: detail.into(),
+        });
+    }
+
+    fn send(&mut self, msg: CanMessage) -> Result<(), String> {
+        let id = make_message_id(self.options.node_id, self.options.server_id);
+        socket_manager::send_frame(self.transport, id, msg)
+            .map_err(|err| format!("sending failed: {err:?}"))
+    }
+
+    /// The next message the node sends to this server, or `None` once `deadline` has passed.
+    /// Telemetry group updates are collected on the way instead of being returned.
+    fn next_message(&mut self, deadline: Instant) -> Option<CanMessage> {
+        loop {
+            let timeout = deadline.checked_duration_since(Instant::now())?;
+            let frame = socket_manager::read_frame(self.transport, timeout).ok()?;
+            let Ok((id, msg)) = parse_can_message(frame) else {
+                self.undecodable += 1;
+                continue;
+            };
+            let to_us =
+                id.receiver_id() == self.options.server_id || id.receiver_id() == BROADCAST_ID;
+            if id.sender_id() != self.options.node_id || !to_us {
+                continue;
+            }
+            match msg {
+                CanMessage::TelemetryGroupUpdate { payload } => self.updates.push(payload),
+                msg => return Some(msg),
+            }
+        }
+    }
+
+    /// Sends `msg` and waits for the first response `extract` accepts.
+    fn request<R>(
+        &mut self,
+        msg: CanMessage,
+        extract: impl Fn(CanMessage) -> Option<R>,
+    ) -> Result<R, String> {
+        self.send(msg)?;
+        let deadline = Instant::now() + self.options.timeout;
+        while let Some(msg) = self.next_message(deadline) {
+            if let Some(response) = extract(msg) {
+                return Ok(response);
+            }
+        }
+        Err(format!(
+            "no response within {:.3} s",
+            self.options.timeout.as_secs_f64()
+        ))
+    }
+
+    /// Requests the registration flow and builds the field dictionary from it. Returns whether
+    /// the node announced itself, as nothing else can be checked otherwise.
+    fn register(&mut self) -> bool {
+        const NAME: &str = "node info announcement";
+        if let Err(err) = self.send(CanMessage::NodeInfoReq) {
+            self.check(NAME, false, err);
+            return false;
+        }
+        let mut announcement = None;
+        let mut deadline = Instant::now() + self.options.timeout;
+        while let Some(msg) = self.next_message(deadline) {
+            match msg {
+                // A new announcement starts a new registration flow.
+                CanMessage::NodeInfoAnnouncement { payload } => {
+                    self.dictionary = FieldDictionary {
+                        device_name: payload.device_name.clone().into(),
+                        ..FieldDictionary::default()
+                    };
+                    announcement = Some(payload);
+                }
+                CanMessage::TelemetryValueRegistration { payload } => {
+                    self.dictionary.telemetry.push(field_of(&payload));
+                }
+                CanMessage::ParameterRegistration { payload } => {
+                    self.dictionary.parameters.push(field_of(&payload));
+                }
+                CanMessage::TelemetryGroupDefinition { payload } => {
+                    let field_ids: &[u8] = (&payload.field_ids).into();
+                    self.dictionary
+                        .groups
+                        .push((payload.group_id, field_ids.to_vec()));
+                }
+                _ => continue,
+            }
+            deadline = Instant::now() + self.options.timeout;
+        }
+        let Some(announcement) = announcement else {
+            self.check(NAME, false, "no announcement after NodeInfoReq");
+            return false;
+        };
+        self.check(
+            NAME,
+            true,
+            format!(
+                "{}, firmware {:#010x}, LiquidCAN {:#010x}",
+                self.dictionary.device_name, announcement.firmware_hash, announcement.liquid_hash
+            ),
+        );
+
+        let telemetry = self.dictionary.telemetry.len();
+        let parameters = self.dictionary.parameters.len();
+        self.check(
+            "registration counts",
+            telemetry == usize::from(announcement.tel_count)
+                && parameters == usize::from(announcement.par_count),
+            format!(
+                "announced {} telemetry value(s) and {} parameter(s), registered {telemetry} and {parameters}",
+                announcement.tel_count, announcement.par_count
+            ),
+        );
+        self.check_field_ids();
+        self.check_field_names();
+        self.check_groups();
+        true
+    }
+
+    fn check_field_ids(&mut self) {
+        let dictionary = &self.dictionary;
+        let mut problems = Vec::new();
+        for field in &dictionary.telemetry {
+            if field.field_id & TELEMETRY_ID_BIT == 0 {
+                problems.push(format!("telemetry {} lacks the telemetry bit", field.name));
+            }
+        }
+        for field in &dictionary.parameters {
+            if field.field_id & TELEMETRY_ID_BIT != 0 {
+                problems.push(format!("parameter {} has the telemetry bit", field.name));
+            }
+        }
+        let all = dictionary.telemetry.iter().chain(&dictionary.parameters);
+        for field_id in duplicates(all.map(|field| field.field_id)) {
+            problems.push(format!("field ID {field_id:#04x} registered twice"));
+        }
+        self.check(
+            "field ids",
+            problems.is_empty(),
+            if problems.is_empty() {
+                "unique, telemetry bit set on telemetry only".to_string()
+            } else {
+                problems.join("; ")
+            },
+        );
+    }
+
+    fn check_field_names(&mut self) {
+        let mut seen = HashSet::new();
+        let repeated: Vec<&str> = self
+            .dictionary
+            .telemetry
+            .iter()
+            .chain(&self.dictionary.parameters)
+            .map(|field| field.name.as_str())
+            .filter(|name| !seen.insert(*name))
+            .collect();
+        let empty = seen.contains("");
+        let detail = if repeated.is_empty() && !empty {
+            "unique and non-empty".to_string()
+        } else if empty {
+            "a field has an empty name".to_string()
+        } else {
+            format!("registered twice: {}", repeated.join(", "))
+        };
+        self.check("field names", repeated.is_empty() && !empty, detail);
+    }
+
+    fn check_groups(&mut self) {
+        let mut problems = Vec::new();
+        for group_id in duplicates(self.dictionary.groups.iter().map(|&(id, _)| id)) {
+            problems.push(format!("group {group_id} defined twice"));
+        }
+        for (group_id, field_ids) in &self.dictionary.groups {
+            for field_id in field_ids {
+                let registered = self
+                    .dictionary
+                    .telemetry
+                    .iter()
+                    .any(|field| field.field_id == *field_id);
+                if !registered {
+                    problems.push(format!(
+                        "group {group_id} contains unregistered field {field_id:#04x}"
+                    ));
+                }
+            }
+        }
+        let detail = if problems.is_empty() {
+            format!("{} group(s)", self.dictionary.groups.len())
+        } else {
+            problems.join("; ")
+        };
+        self.check("telemetry groups", problems.is_empty(), detail);
+    }
+
+    /// A parameter ID the node did not register, if there is one left.
+    fn unused_parameter_id(&self) -> Option<u8> {
+        (1..=FIELD_ID_MASK).find(|id| {
+            !self
+                .dictionary
+                .parameters
+                .iter()
+                .any(|field| field.field_id == *id)
+        })
+    }
+
+    /// Reads every registered field and looks every name up.
+    fn get_fields(&mut self) {
+        // The requests need `self`, so the fields are taken out of the dictionary meanwhile.
+        let mut lists = [
+            std::mem::take(&mut self.dictionary.telemetry),
+            std::mem::take(&mut self.dictionary.parameters),
+        ];
+        for field in lists.iter_mut().flatten() {
+            field.value = self.get_field(field.field_id, &field.name, field.data_type);
+            self.lookup_field(field.field_id, &field.name, field.data_type);
+        }
+        [self.dictionary.telemetry, self.dictionary.parameters] = lists;
+
+        let Some(field_id) = self.unused_parameter_id() else {
+            self.record("get unknown field", Outcome::Skipped, "no unused field ID");
+            return;
+        };
+        let res = self.request(
+            CanMessage::FieldGetReq {
+                payload: payloads::FieldGetReqPayload { field_id },
+            },
+            |msg| match msg {
+                CanMessage::FieldGetRes { payload } => Some(payload),
+                _ => None,
+            },
+        );
+        let (passed, detail) = match res {
+            Ok(res) => (
+                res.field_id == field_id
+                    && matches!(res.field_status, payloads::FieldStatus::NotFound),
+                format!("field {field_id:#04x} answered with {:?}", res.field_status),
+            ),
+            Err(err) => (false, err),
+        };
+        self.check("get unknown field", passed, detail);
+    }
+
+    fn get_field(
+        &mut self,
+        field_id: u8,
+        name: &str,
+        data_type: CanDataType,
+    ) -> Option<CanDataValue> {
+        let res = self.request(
+            CanMessage::FieldGetReq {
+                payload: payloads::FieldGetReqPayload { field_id },
+            },
+            |msg| match msg {
+                CanMessage::FieldGetRes { payload } => Some(payload),
+                _ => None,
+            },
+        );
+        let check = format!("get {name}");
+        let res = match res {
+            Ok(res) => res,
+            Err(err) => {
+                self.check(check, false, err);
+                return None;
+            }
+        };
+        if res.field_id != field_id || !matches!(res.field_status, payloads::FieldStatus::Ok) {
+            let detail = format!(
+                "answered for field {:#04x} with {:?}",
+                res.field_id, res.field_status
+            );
+            self.check(check, false, detail);
+            return None;
+        }
+        let Some(value) = typed_from_value(&res.value, data_type) else {
+            self.check(check, false, format!("value is not a {data_type:?}"));
+            return None;
+        };
+        self.check(check, true, value_json(&value).to_string());
+        Some(value)
+    }
+
+    fn lookup_field(&mut self, field_id: u8, name: &str, data_type: CanDataType) {
+        let check = format!("lookup {name}");
+        let Ok(field_name) = payloads::CanString::<61>::try_from(name) else {
+            self.check(check, false, "name too long to look up");
+            return;
+        };
+        let res = self.request(
+            CanMessage::FieldIDLookupReq {
+                payload: payloads::FieldIDLookupReqPayload { field_name },
+            },
+            |msg| match msg {
+                CanMessage::FieldIDLookupRes { payload } => Some(payload),
+                _ => None,
+            },
+        );
+        let (passed, detail) = match res {
+            Ok(res) => (
+                res.field_id == field_id
+                    && matches!(res.field_status, payloads::FieldStatus::Ok)
+                    && res.field_type == data_type,
+                format!(
+                    "field {:#04x}, {:?}, {:?}",
+                    res.field_id, res.field_type, res.field_status
+                ),
+            ),
+            Err(err) => (false, err),
+        };
+        self.check(check, passed, detail);
+    }
+
+    fn heartbeats(&mut self) {
+        const NAME: &str = "heartbeat";
+        if self.options.heartbeats == 0 {
+            self.record(NAME, Outcome::Skipped, "no heartbeats requested");
+            return;
+        }
+        for counter in 1..=self.options.heartbeats {
+            let res = self.request(
+                CanMessage::HeartbeatReq {
+                    payload: payloads::HeartbeatPayload { counter },
+                },
+                |msg| match msg {
+                    CanMessage::HeartbeatRes { payload } => Some(payload.counter),
+                    _ => None,
+                },
+            );
+            let problem = match res {
+                Ok(answer) if answer == counter.wrapping_add(1) => continue,
+                Ok(answer) => format!("answered counter {counter} with {answer}"),
+                Err(err) => format!("counter {counter}: {err}"),
+            };
+            self.check(NAME, false, problem);
+            return;
+        }
+        let detail = format!("{} of {0} answered", self.options.heartbeats);
+        self.check(NAME, true, detail);
+    }
+
+    fn set_parameter(
+        &mut self,
+        parameter_id: u8,
+        value: CanDataValue,
+    ) -> Result<payloads::ParameterSetConfirmationPayload, String> {
+        self.request(
+            CanMessage::ParameterSetReq {
+                payload: payloads::ParameterSetReqPayload {
+                    parameter_id,
+                    value,
+                },
+            },
+            |msg| match msg {
+                CanMessage::ParameterSetConfirmation { payload } => Some(payload),
+                _ => None,
+            },
+        )
+    }
+
+    /// Writes every parameter's current value back. Returns the first parameter that accepted
+    /// the write, with its ID and value.
+    fn set_parameters(&mut self) -> Option<(String, u8, CanDataValue)> {
+        let parameters: Vec<(u8, String, CanDataType, CanDataValue)> = self
+            .dictionary
+            .parameters
+            .iter()
+            .filter_map(|field| {
+                let value = field.value.clone()?;
+                Some((field.field_id, field.name.clone(), field.data_type, value))
+            })
+            .collect();
+        let mut writable = None;
+        for (field_id, name, data_type, value) in parameters {
+            let check = format!("set {name}");
+            let res = match self.set_parameter(field_id, value.clone()) {
+                Ok(res) => res,
+                Err(err) => {
+                    self.check(check, false, err);
+                    continue;
+                }
+            };
+            let confirmed = typed_from_value(&res.value, data_type);
+            let (passed, detail) = if res.parameter_id != field_id {
+                (false, format!("confirmed parameter {}", res.parameter_id))
+            } else if confirmed.as_ref() != Some(&value) {
+                (
+                    false,
+                    format!("confirmed {:?} with another value", res.status),
+                )
+            } else {
+                match res.status {
+                    payloads::ParameterSetStatus::Success => {
+                        writable.get_or_insert((name.clone(), field_id, value.clone()));
+                        (true, "accepted".to_string())
+                    }
+                    payloads::ParameterSetStatus::ParameterLocked => {
+                        (true, "rejected as locked or read-only".to_string())
+                    }
+                    status => (false, format!("answered with {status:?}")),
+                }
+            };
+            self.check(check, passed, detail);
+        }
+
+        const NAME: &str = "set unknown parameter";
+        let Some(parameter_id) = self.unused_parameter_id() else {
+            self.record(NAME, Outcome::Skipped, "no unused parameter ID");
+            return writable;
+        };
+        let (passed, detail) = match self.set_parameter(parameter_id, CanDataValue::UInt8(0)) {
+            Ok(res) => (
+                res.parameter_id == parameter_id
+                    && matches!(res.status, payloads::ParameterSetStatus::InvalidParameterID),
+                format!("parameter {parameter_id} answered with {:?}", res.status),
+            ),
+            Err(err) => (false, err),
+        };
+        self.check(NAME, passed, detail);
+        writable
+    }
+
+    fn lock_parameter(
+        &mut self,
+        parameter_id: u8,
+        parameter_lock: payloads::ParameterLockStatus,
+    ) -> Result<payloads::ParameterSetLockConfirmationPayload, String> {
+        self.request(
+            CanMessage::ParameterSetLockReq {
+                payload: payloads::ParameterSetLockPayload {
+                    parameter_id,
+                    parameter_lock,
+                },
+            },
+            |msg| match msg {
+                CanMessage::ParameterSetLockConfirmation { payload } => Some(payload),
+                _ => None,
+            },
+        )
+    }
+
+    /// Locks a writable parameter, checks that writes are refused, and unlocks it again.
+    fn locks(&mut self, writable: Option<(String, u8, CanDataValue)>) {
+        use payloads::ParameterLockStatus::{Locked, Unlocked};
+
+        let Some((name, field_id, value)) = writable else {
+            self.record("parameter locks", Outcome::Skipped, "no writable parameter");
+            return;
+        };
+        for (check, lock) in [
+            (format!("lock {name}"), Locked),
+            (format!("unlock {name}"), Unlocked),
+        ] {
+            let locking = matches!(lock, Locked);
+            let (passed, detail) = match self.lock_parameter(field_id, lock) {
+                Ok(res) => (
+                    res.parameter_id == field_id
+                        && matches!(res.parameter_lock, Locked) == locking
+                        && matches!(res.field_status, payloads::FieldStatus::Ok),
+                    format!("{:?}, {:?}", res.parameter_lock, res.field_status),
+                ),
+                Err(err) => (false, err),
+            };
+            self.check(check, passed, detail);
+
+            let (check, expected) = if locking {
+                (format!("set {name} while locked"), "ParameterLocked")
+            } else {
+                (format!("set {name} after unlock"), "Success")
+            };
+            let (passed, detail) = match self.set_parameter(field_id, value.clone()) {
+                Ok(res) => {
+                    let passed = if locking {
+                        matches!(res.status, payloads::ParameterSetStatus::ParameterLocked)
+                    } else {
+                        matches!(res.status, payloads::ParameterSetStatus::Success)
+                    };
+                    (
+                        passed,
+                        format!("answered with {:?}, expected {expected}", res.status),
+                    )
+                }
+                Err(err) => (false, err),
+            };
+            self.check(check, passed, detail);
+        }
+
+        const NAME: &str = "lock unknown parameter";
+        let Some(parameter_id) = self.unused_parameter_id() else {
+            self.record(NAME, Outcome::Skipped, "no unused parameter ID");
+            return;
+        };
+        let (passed, detail) = match self.lock_parameter(parameter_id, Locked) {
+            Ok(res) => (
+                res.parameter_id == parameter_id
+                    && matches!(res.field_status, payloads::FieldStatus::NotFound),
+                format!(
+                    "parameter {parameter_id} answered with {:?}",
+                    res.field_status
+                ),
+            ),
+            Err(err) => (false, err),
+        };
+        self.check(NAME, passed, detail);
+    }
+
+    /// Every telemetry group update seen must belong to a defined group and unpack with the
+    /// types of its members.
+    fn group_updates(&mut self) {
+        const NAME: &str = "telemetry group updates";
+        if self.updates.is_empty() {
+            self.record(NAME, Outcome::Skipped, "none received");
+            return;
+        }
+        let mut problems = Vec::new();
+        for update in &self.updates {
+            let types: Option<Vec<CanDataType>> = self
+                .dictionary
+                .groups
+                .iter()
+                .find(|(group_id, _)| *group_id == update.group_id)
+                .and_then(|(_, field_ids)| {
+                    field_ids
+                        .iter()
+                        .map(|field_id| {
+                            self.dictionary
+                                .telemetry
+                                .iter()
+                                .find(|field| field.field_id == *field_id)
+                                .map(|field| field.data_type)
+                        })
+                        .collect()
+                });
+            let decodes =
+                types.map(|types| update.values.unpack(types.into_iter()).all(|v| v.is_ok()));
+            let problem = match decodes {
+                Some(true) => continue,
+                Some(false) => format!("group {} does not match its definition", update.group_id),
+                None => format!("update for undefined group {}", update.group_id),
+            };
+            if !problems.contains(&problem) {
+                problems.push(problem);
+            }
+        }
+        let detail = if problems.is_empty() {
+            format!("{} update(s) decoded", self.updates.len())
+        } else {
+            problems.join("; ")
+        };
+        self.check(NAME, problems.is_empty(), detail);
+    }
+}
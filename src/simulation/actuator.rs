@@ -0,0 +1,228 @@
+use crate::config::config_representation::EmulatorData;
+use crate::config::serde_deserializer::value_to_f64;
+use crate::simulation::generator::value_from_f64;
+use serde::Deserialize;
+use std::collections::VecDeque;
+
+/// How the output of an actuator model follows its target.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Dynamics {
+    /// Jumps to the target immediately.
+    Copy,
+    /// Approaches the target exponentially; covers ~63% of the distance per `time_constant`
+    /// seconds.
+    FirstOrderLag { time_constant: f64 },
+    /// Moves towards the target by at most `rate` units per second.
+    RateLimit { rate: f64 },
+}
+
+impl Dynamics {
+    /// The output `dt` seconds after it was `output`, following a constant `target`.
+    fn advance(&self, output: f64, target: f64, dt: f64) -> f64 {
+        let dt = dt.max(0.0);
+        match *self {
+            Dynamics::Copy => target,
+            Dynamics::FirstOrderLag { time_constant } if time_constant > 0.0 => {
+                output + (target - output) * (1.0 - (-dt / time_constant).exp())
+            }
+            Dynamics::FirstOrderLag { .. } => target,
+            Dynamics::RateLimit { rate } => {
+                let max_step = rate.abs() * dt;
+                output + (target - output).clamp(-max_step, max_step)
+            }
+        }
+    }
+}
+
+/// Links a parameter to telemetry values that follow it over time, like a valve position
+/// readback follows its target position.
+#[derive(Deserialize, Debug)]
+pub struct ActuatorModel {
+    #[serde(skip)]
+    pub name: String,
+    /// The parameter that sets the target.
+    pub parameter: String,
+    /// Telemetry values that report the model output.
+    pub telemetry: Vec<String>,
+    pub dynamics: Dynamics,
+    /// Seconds before a new target starts to take effect.
+    #[serde(default)]
+    pub dead_time: f64,
+    #[serde(skip)]
+    state: ModelState,
+}
+
+#[derive(Debug, Default)]
+struct ModelState {
+    /// Set when the parameter changed since the last update.
+    changed: bool,
+    /// Targets waiting out the dead time, with the time they take effect.
+    pending: VecDeque<(f64, f64)>,
+    target: Option<f64>,
+    output: Option<f64>,
+    last_update: Option<f64>,
+}
+
+impl ActuatorModel {
+    /// A model named after its parameter, without dead time.
+    pub fn new(parameter: &str, telemetry: &[&str], dynamics: Dynamics) -> Self {
+        Self {
+            name: parameter.to_string(),
+            parameter: parameter.to_string(),
+            telemetry: telemetry.iter().map(|name| name.to_string()).collect(),
+            dynamics,
+            dead_time: 0.0,
+            state: ModelState::default(),
+        }
+    }
+
+    /// Takes over where `running` is, if it models the same actuator. Used when a reloaded config
+    /// replaces the running model.
+    pub(crate) fn keep_state(&mut self, running: &mut ActuatorModel) {
+        if self.parameter == running.parameter
+            && self.telemetry == running.telemetry
+            && self.dynamics == running.dynamics
+            && self.dead_time == running.dead_time
+        {
+            self.state = std::mem::take(&mut running.state);
+        }
+    }
+
+    /// Advances the model to `t` seconds since emulator start, given the parameter's current
+    /// value. The first update starts the model at rest at that value.
+    fn update(&mut self, t: f64, parameter: f64) -> f64 {
+        let state = &mut self.state;
+        let (Some(mut output), Some(last_update)) = (state.output, state.last_update) else {
+            state.changed = false;
+            state.target = Some(parameter);
+            state.output = Some(parameter);
+            state.last_update = Some(t);
+            return parameter;
+        };
+
+        if std::mem::take(&mut state.changed) {
+            state
+                .pending
+                .push_back((t + self.dead_time.max(0.0), parameter));
+        }
+        // Follow the old target until each pending target takes effect.
+        let mut target = state.target.unwrap_or(parameter);
+        let mut now = last_update;
+        while let Some(&(effective, next)) = state.pending.front() {
+            if effective > t {
+                break;
+            }
+            let effective = effective.max(now);
+            output = self.dynamics.advance(output, target, effective - now);
+            now = effective;
+            target = next;
+            state.pending.pop_front();
+        }
+        output = self.dynamics.advance(output, target, t - now);
+
+        state.target = Some(target);
+        state.output = Some(output);
+        state.last_update = Some(t);
+        output
+    }
+}
+
+/// The model that writes the telemetry value `telemetry`, if any.
+pub fn driving_model<'a>(
+    emulator_data: &'a EmulatorData,
+    telemetry: &str,
+) -> Option<&'a ActuatorModel> {
+    emulator_data
+        .models
+        .iter()
+        .flatten()
+        .find(|model| model.telemetry.iter().any(|name| name == telemetry))
+}
+
+/// Tells the models driven by `parameter` that its value changed. The new target is picked up
+/// on the next telemetry update.
+pub fn parameter_changed(emulator_data: &mut EmulatorData, parameter: &str) {
+    for model in emulator_data.models.iter_mut().flatten() {
+        if model.parameter == parameter {
+            model.state.changed = true;
+        }
+    }
+}
+
+/// Advances every actuator model to `t` seconds since emulator start and writes the outputs to
+/// their telemetry values.
+pub fn update_models(emulator_data: &mut EmulatorData, t: f64) {
+    let Some(models) = emulator_data.models.as_mut() else {
+        return;
+    };
+    let parameters = emulator_data.parameters.as_deref().unwrap_or_default();
+    let telemetry = emulator_data
+        .telemetry_values
+        .as_deref_mut()
+        .unwrap_or_default();
+    for model in models {
+        let Some(parameter) = parameters
+            .iter()
+            .find(|param| param.name == model.parameter)
+            .and_then(|param| value_to_f64(&param.value))
+        else {
+            continue;
+        };
+        let output = model.update(t, parameter);
+        for tel in telemetry
+            .iter_mut()
+            .filter(|tel| model.telemetry.contains(&tel.name))
+        {
+            tel.value = value_from_f64(output, tel.datatype);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Starts `model` at rest at 0, then sets the target to 10 at t = 1.
+    fn step_response(mut model: ActuatorModel, times: &[f64]) -> Vec<f64> {
+        model.update(0.0, 0.0);
+        model.update(1.0, 0.0);
+        model.state.changed = true;
+        times.iter().map(|&t| model.update(t, 10.0)).collect()
+    }
+
+    #[test]
+    fn copy_follows_immediately() {
+        let model = ActuatorModel::new("target", &["position"], Dynamics::Copy);
+        assert_eq!(step_response(model, &[1.0, 2.0]), vec![10.0, 10.0]);
+    }
+
+    #[test]
+    fn first_order_lag_approaches_the_target() {
+        let dynamics = Dynamics::FirstOrderLag { time_constant: 1.0 };
+        let model = ActuatorModel::new("target", &["position"], dynamics);
+        let outputs = step_response(model, &[1.0, 2.0, 6.0]);
+        assert_eq!(outputs[0], 0.0);
+        assert!((outputs[1] - 10.0 * (1.0 - (-1.0f64).exp())).abs() < 1e-9);
+        assert!(outputs[2] > 9.9 && outputs[2] < 10.0, "{outputs:?}");
+    }
+
+    #[test]
+    fn rate_limit_moves_at_constant_speed() {
+        let model = ActuatorModel::new("target", &["position"], Dynamics::RateLimit { rate: 4.0 });
+        assert_eq!(
+            step_response(model, &[1.0, 1.5, 2.0, 3.0, 4.0]),
+            vec![0.0, 2.0, 4.0, 8.0, 10.0]
+        );
+    }
+
+    #[test]
+    fn dead_time_delays_new_targets() {
+        let mut model = ActuatorModel::new("target", &["position"], Dynamics::Copy);
+        model.dead_time = 0.5;
+        assert_eq!(
+            step_response(model, &[1.0, 1.4, 1.5, 2.0]),
+            vec![0.0, 0.0, 10.0, 10.0]
+        );
+    }
+}
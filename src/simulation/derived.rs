@@ -0,0 +1,94 @@
+use crate::config::config_representation::{EmulatorData, Parameter, TelemetryValue};
+use crate::config::serde_deserializer::value_to_f64;
+use crate::simulation::expression::TIME;
+use crate::simulation::generator::value_from_f64;
+
+/// Indices of the telemetry values with an expression, ordered so that every value comes after
+/// the derived values it reads. Fails if expressions depend on each other in a cycle.
+pub fn evaluation_order(telemetry: &[TelemetryValue]) -> Result<Vec<usize>, String> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Mark {
+        Unvisited,
+        InProgress,
+        Done,
+    }
+
+    fn visit(
+        idx: usize,
+        telemetry: &[TelemetryValue],
+        marks: &mut [Mark],
+        path: &mut Vec<usize>,
+        order: &mut Vec<usize>,
+    ) -> Result<(), String> {
+        match marks[idx] {
+            Mark::Done => return Ok(()),
+            Mark::InProgress => {
+                let start = path.iter().position(|&i| i == idx).unwrap_or(0);
+                let cycle: Vec<&str> = path[start..]
+                    .iter()
+                    .chain([&idx])
+                    .map(|&i| telemetry[i].name.as_str())
+                    .collect();
+                return Err(format!("Expressions form a cycle: {}", cycle.join(" -> ")));
+            }
+            Mark::Unvisited => {}
+        }
+        let Some(expr) = telemetry[idx].expr.as_ref() else {
+            marks[idx] = Mark::Done;
+            return Ok(());
+        };
+        marks[idx] = Mark::InProgress;
+        path.push(idx);
+        for name in expr.names() {
+            if let Some(dep) = telemetry.iter().position(|tel| tel.name == name) {
+                visit(dep, telemetry, marks, path, order)?;
+            }
+        }
+        path.pop();
+        marks[idx] = Mark::Done;
+        order.push(idx);
+        Ok(())
+    }
+
+    let mut marks = vec![Mark::Unvisited; telemetry.len()];
+    let mut order = Vec::new();
+    for idx in 0..telemetry.len() {
+        visit(idx, telemetry, &mut marks, &mut Vec::new(), &mut order)?;
+    }
+    Ok(order)
+}
+
+/// The value of `name` inside an expression: the elapsed time `t`, a telemetry value or a
+/// parameter. Unknown names evaluate to NaN.
+fn value_of(name: &str, t: f64, telemetry: &[TelemetryValue], parameters: &[Parameter]) -> f64 {
+    if name == TIME {
+        return t;
+    }
+    let value = telemetry
+        .iter()
+        .find(|tel| tel.name == name)
+        .map(|tel| &tel.value)
+        .or_else(|| {
+            parameters
+                .iter()
+                .find(|param| param.name == name)
+                .map(|param| &param.value)
+        });
+    value.and_then(value_to_f64).unwrap_or(f64::NAN)
+}
+
+/// Re-evaluates the telemetry expressions in `order`, as [`evaluation_order`] gives it, for `t`
+/// seconds since emulator start and casts the results into the fields' datatypes.
+pub fn update_derived(emulator_data: &mut EmulatorData, order: &[usize], t: f64) {
+    let Some(telemetry) = emulator_data.telemetry_values.as_deref_mut() else {
+        return;
+    };
+    let parameters = emulator_data.parameters.as_deref().unwrap_or_default();
+    for &idx in order {
+        let Some(expr) = telemetry.get(idx).and_then(|tel| tel.expr.as_ref()) else {
+            continue;
+        };
+        let result = expr.eval(&|name| value_of(name, t, telemetry, parameters));
+        telemetry[idx].value = value_from_f64(result, telemetry[idx].datatype);
+    }
+}
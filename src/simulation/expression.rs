@@ -0,0 +1,362 @@
+use std::fmt;
+
+/// Name of the elapsed time since emulator start, in seconds, inside expressions.
+pub const TIME: &str = "t";
+
+/// An arithmetic expression over telemetry values, parameters and the elapsed time `t`, such as
+/// `tel_a - tel_b * param_gain + sin(t)`.
+///
+/// Supports numbers, names, `+ - * / ^`, parentheses and the functions in [`FUNCTIONS`].
+#[derive(Clone)]
+pub struct Expression {
+    source: String,
+    root: Node,
+}
+
+#[derive(Clone)]
+enum Node {
+    Number(f64),
+    Name(String),
+    Negate(Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+    Call(&'static str, Vec<Node>),
+}
+
+#[derive(Clone, Copy)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+}
+
+/// Supported functions and their number of arguments.
+pub const FUNCTIONS: [(&str, usize); 13] = [
+    ("sin", 1),
+    ("cos", 1),
+    ("tan", 1),
+    ("sqrt", 1),
+    ("abs", 1),
+    ("exp", 1),
+    ("ln", 1),
+    ("log10", 1),
+    ("floor", 1),
+    ("ceil", 1),
+    ("round", 1),
+    ("min", 2),
+    ("max", 2),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Op(char),
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let root = parser.sum()?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            return Err(format!("unexpected {} in `{source}`", describe(token)));
+        }
+        Ok(Self {
+            source: source.to_string(),
+            root,
+        })
+    }
+
+    /// Every name the expression reads, including `t`, without duplicates.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        self.root.collect_names(&mut names);
+        names
+    }
+
+    /// Evaluates the expression, looking up names with `value_of`.
+    pub fn eval(&self, value_of: &dyn Fn(&str) -> f64) -> f64 {
+        self.root.eval(value_of)
+    }
+}
+
+impl fmt::Debug for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Expression({:?})", self.source)
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl Node {
+    fn collect_names<'a>(&'a self, names: &mut Vec<&'a str>) {
+        match self {
+            Node::Number(_) => {}
+            Node::Name(name) => {
+                if !names.contains(&name.as_str()) {
+                    names.push(name);
+                }
+            }
+            Node::Negate(inner) => inner.collect_names(names),
+            Node::Binary(_, lhs, rhs) => {
+                lhs.collect_names(names);
+                rhs.collect_names(names);
+            }
+            Node::Call(_, args) => args.iter().for_each(|arg| arg.collect_names(names)),
+        }
+    }
+
+    fn eval(&self, value_of: &dyn Fn(&str) -> f64) -> f64 {
+        match self {
+            Node::Number(value) => *value,
+            Node::Name(name) => value_of(name),
+            Node::Negate(inner) => -inner.eval(value_of),
+            Node::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.eval(value_of), rhs.eval(value_of));
+                match op {
+                    BinaryOp::Add => lhs + rhs,
+                    BinaryOp::Sub => lhs - rhs,
+                    BinaryOp::Mul => lhs * rhs,
+                    BinaryOp::Div => lhs / rhs,
+                    BinaryOp::Pow => lhs.powf(rhs),
+                }
+            }
+            Node::Call(function, args) => {
+                let arg = |idx: usize| args[idx].eval(value_of);
+                match *function {
+                    "sin" => arg(0).sin(),
+                    "cos" => arg(0).cos(),
+                    "tan" => arg(0).tan(),
+                    "sqrt" => arg(0).sqrt(),
+                    "abs" => arg(0).abs(),
+                    "exp" => arg(0).exp(),
+                    "ln" => arg(0).ln(),
+                    "log10" => arg(0).log10(),
+                    "floor" => arg(0).floor(),
+                    "ceil" => arg(0).ceil(),
+                    "round" => arg(0).round(),
+                    "min" => arg(0).min(arg(1)),
+                    "max" => arg(0).max(arg(1)),
+                    _ => unreachable!("functions are checked while parsing"),
+                }
+            }
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut end = start;
+            let mut prev = c;
+            while let Some(&(idx, c)) = chars.peek() {
+                let exponent_sign = (c == '+' || c == '-') && (prev == 'e' || prev == 'E');
+                if !(c.is_ascii_alphanumeric() || c == '.' || exponent_sign) {
+                    break;
+                }
+                end = idx + c.len_utf8();
+                prev = c;
+                chars.next();
+            }
+            let number = &source[start..end];
+            let value = number
+                .parse()
+                .map_err(|_| format!("invalid number {number} in `{source}`"))?;
+            tokens.push(Token::Number(value));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut end = start;
+            while let Some(&(idx, c)) = chars.peek() {
+                if !(c.is_alphanumeric() || c == '_') {
+                    break;
+                }
+                end = idx + c.len_utf8();
+                chars.next();
+            }
+            tokens.push(Token::Name(source[start..end].to_string()));
+        } else if "+-*/^(),".contains(c) {
+            tokens.push(Token::Op(c));
+            chars.next();
+        } else {
+            return Err(format!("unexpected character {c:?} in `{source}`"));
+        }
+    }
+    Ok(tokens)
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Number(value) => format!("number {value}"),
+        Token::Name(name) => format!("name {name}"),
+        Token::Op(op) => format!("`{op}`"),
+    }
+}
+
+/// Recursive descent parser; `^` binds tightest and is right-associative, unary minus binds
+/// looser than `^`, so `-2^2` is `-(2^2)`.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next_if_op(&mut self, ops: &str) -> Option<char> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) if ops.contains(*op) => {
+                self.pos += 1;
+                Some(*op)
+            }
+            _ => None,
+        }
+    }
+
+    fn expect_op(&mut self, op: char) -> Result<(), String> {
+        match self.next_if_op(&op.to_string()) {
+            Some(_) => Ok(()),
+            None => Err(match self.tokens.get(self.pos) {
+                Some(token) => format!("expected `{op}` but found {}", describe(token)),
+                None => format!("expected `{op}` at the end"),
+            }),
+        }
+    }
+
+    fn sum(&mut self) -> Result<Node, String> {
+        let mut node = self.product()?;
+        while let Some(op) = self.next_if_op("+-") {
+            let op = if op == '+' {
+                BinaryOp::Add
+            } else {
+                BinaryOp::Sub
+            };
+            node = Node::Binary(op, Box::new(node), Box::new(self.product()?));
+        }
+        Ok(node)
+    }
+
+    fn product(&mut self) -> Result<Node, String> {
+        let mut node = self.unary()?;
+        while let Some(op) = self.next_if_op("*/") {
+            let op = if op == '*' {
+                BinaryOp::Mul
+            } else {
+                BinaryOp::Div
+            };
+            node = Node::Binary(op, Box::new(node), Box::new(self.unary()?));
+        }
+        Ok(node)
+    }
+
+    fn unary(&mut self) -> Result<Node, String> {
+        if self.next_if_op("-").is_some() {
+            return Ok(Node::Negate(Box::new(self.unary()?)));
+        }
+        let base = self.primary()?;
+        if self.next_if_op("^").is_some() {
+            let exponent = self.unary()?;
+            return Ok(Node::Binary(
+                BinaryOp::Pow,
+                Box::new(base),
+                Box::new(exponent),
+            ));
+        }
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> Result<Node, String> {
+        let Some(token) = self.tokens.get(self.pos).cloned() else {
+            return Err("unexpected end of expression".to_string());
+        };
+        self.pos += 1;
+        match token {
+            Token::Number(value) => Ok(Node::Number(value)),
+            Token::Op('(') => {
+                let node = self.sum()?;
+                self.expect_op(')')?;
+                Ok(node)
+            }
+            Token::Name(name) if self.next_if_op("(").is_some() => {
+                let Some(&(function, arity)) = FUNCTIONS.iter().find(|(f, _)| *f == name) else {
+                    return Err(format!("unknown function {name}"));
+                };
+                let mut args = vec![self.sum()?];
+                while self.next_if_op(",").is_some() {
+                    args.push(self.sum()?);
+                }
+                self.expect_op(')')?;
+                if args.len() != arity {
+                    return Err(format!(
+                        "{function} takes {arity} argument(s) but got {}",
+                        args.len()
+                    ));
+                }
+                Ok(Node::Call(function, args))
+            }
+            Token::Name(name) => Ok(Node::Name(name)),
+            token => Err(format!("unexpected {}", describe(&token))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(source: &str) -> f64 {
+        let values = |name: &str| match name {
+            "a" => 10.0,
+            "b" => 4.0,
+            TIME => 0.5,
+            _ => f64::NAN,
+        };
+        Expression::parse(source).unwrap().eval(&values)
+    }
+
+    #[test]
+    fn operators_follow_precedence() {
+        assert_eq!(eval("a - b * 2"), 2.0);
+        assert_eq!(eval("(a - b) * 2"), 12.0);
+        assert_eq!(eval("a / b / 2"), 1.25);
+        assert_eq!(eval("2 ^ 3 ^ 2"), 512.0);
+        assert_eq!(eval("-2 ^ 2"), -4.0);
+        assert_eq!(eval("-a + --b"), -6.0);
+        assert_eq!(eval("1.5e1 + 2E-1"), 15.2);
+    }
+
+    #[test]
+    fn functions_and_time_are_supported() {
+        assert_eq!(eval("max(a, b) + min(a, b)"), 14.0);
+        assert_eq!(eval("sqrt(abs(-b))"), 2.0);
+        assert!((eval("sin(t * 2)") - 1.0f64.sin()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn names_are_listed_once() {
+        let expr = Expression::parse("a * a + sin(t) - b").unwrap();
+        assert_eq!(expr.names(), vec!["a", "t", "b"]);
+    }
+
+    #[test]
+    fn syntax_errors_are_reported() {
+        for (source, reason) in [
+            ("a +", "unexpected end"),
+            ("(a + b", "expected `)`"),
+            ("a b", "unexpected name b"),
+            ("foo(a)", "unknown function foo"),
+            ("min(a)", "min takes 2 argument(s) but got 1"),
+            ("a % b", "unexpected character '%'"),
+            ("1.2.3", "invalid number 1.2.3"),
+        ] {
+            let err = Expression::parse(source).err().unwrap();
+            assert!(err.contains(reason), "{source}: {err}");
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use crate::simulation::rng::SplitMix64;
+use liquidcan::payloads::{CanDataType, CanDataValue};
+use serde::Deserialize;
+use std::f64::consts::PI;
+
+/// Time-varying signal source for a telemetry value, evaluated on every update tick.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Generator {
+    Sine {
+        amplitude: f64,
+        #[serde(default)]
+        offset: f64,
+        period: f64,
+        #[serde(default)]
+        phase: f64,
+    },
+    /// Sawtooth from `from` to `to`, restarting every `period` seconds.
+    Ramp { from: f64, to: f64, period: f64 },
+    Square {
+        low: f64,
+        high: f64,
+        period: f64,
+        #[serde(default = "default_duty")]
+        duty: f64,
+    },
+    GaussianNoise {
+        #[serde(default)]
+        mean: f64,
+        std_dev: f64,
+        seed: Option<u64>,
+        #[serde(skip)]
+        rng: Option<SplitMix64>,
+    },
+    RandomWalk {
+        #[serde(default)]
+        start: f64,
+        step: f64,
+        min: Option<f64>,
+        max: Option<f64>,
+        seed: Option<u64>,
+        #[serde(skip)]
+        rng: Option<SplitMix64>,
+        #[serde(skip)]
+        position: Option<f64>,
+    },
+}
+
+fn default_duty() -> f64 {
+    0.5
+}
+
+fn seeded(rng: &mut Option<SplitMix64>, seed: Option<u64>) -> &mut SplitMix64 {
+    rng.get_or_insert_with(|| {
+        seed.map(SplitMix64::new)
+            .unwrap_or_else(SplitMix64::from_time)
+    })
+}
+
+/// Position inside the current period in `[0, 1)`. A non-positive period yields 0.
+fn cycle_fraction(t: f64, period: f64) -> f64 {
+    if period <= 0.0 {
+        return 0.0;
+    }
+    (t / period).rem_euclid(1.0)
+}
+
+impl Generator {
+    /// Whether both generators produce the same signal, wherever they are in it.
+    pub fn same_settings(&self, other: &Generator) -> bool {
+        self.restarted() == other.restarted()
+    }
+
+    /// A copy that starts over from its seed.
+    fn restarted(&self) -> Generator {
+        let mut generator = self.clone();
+        match &mut generator {
+            Generator::GaussianNoise { rng, .. } => *rng = None,
+            Generator::RandomWalk { rng, position, .. } => {
+                *rng = None;
+                *position = None;
+            }
+            _ => {}
+        }
+        generator
+    }
+
+    /// Samples the generator at `t` seconds since emulator start.
+    pub fn sample(&mut self, t: f64) -> f64 {
+        match self {
+            Generator::Sine {
+                amplitude,
+                offset,
+                period,
+                phase,
+            } => *offset + *amplitude * (2.0 * PI * cycle_fraction(t, *period) + *phase).sin(),
+            Generator::Ramp { from, to, period } => {
+                *from + (*to - *from) * cycle_fraction(t, *period)
+            }
+            Generator::Square {
+                low,
+                high,
+                period,
+                duty,
+            } => {
+                if cycle_fraction(t, *period) < *duty {
+                    *high
+                } else {
+                    *low
+                }
+            }
+            Generator::GaussianNoise {
+                mean,
+                std_dev,
+                seed,
+                rng,
+            } => *mean + *std_dev * seeded(rng, *seed).next_gaussian(),
+            Generator::RandomWalk {
+                start,
+                step,
+                min,
+                max,
+                seed,
+                rng,
+                position,
+            } => {
+                let next = match *position {
+                    None => *start,
+                    Some(current) => current + *step * seeded(rng, *seed).next_gaussian(),
+                };
+                let next = next.max(min.unwrap_or(f64::NEG_INFINITY));
+                let next = next.min(max.unwrap_or(f64::INFINITY));
+                *position = Some(next);
+                next
+            }
+        }
+    }
+}
+
+/// Converts a generated sample into a value of `data_type`.
+/// Floats stay floats; integer types saturate at their bounds instead of wrapping.
+pub fn value_from_f64(value: f64, data_type: CanDataType) -> CanDataValue {
+    // `as` casts from float to integer saturate (and map NaN to 0).
+    match data_type {
+        CanDataType::Float32 => CanDataValue::Float32(value as f32),
+        CanDataType::Int32 => CanDataValue::Int32(value.round() as i32),
+        CanDataType::Int16 => CanDataValue::Int16(value.round() as i16),
+        CanDataType::Int8 => CanDataValue::Int8(value.round() as i8),
+        CanDataType::UInt32 => CanDataValue::UInt32(value.round() as u32),
+        CanDataType::UInt16 => CanDataValue::UInt16(value.round() as u16),
+        CanDataType::UInt8 => CanDataValue::UInt8(value.round() as u8),
+        CanDataType::Boolean => CanDataValue::Boolean(value >= 0.5),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sine_follows_period_and_offset() {
+        let mut generator = Generator::Sine {
+            amplitude: 2.0,
+            offset: 10.0,
+            period: 4.0,
+            phase: 0.0,
+        };
+        assert!((generator.sample(0.0) - 10.0).abs() < 1e-9);
+        assert!((generator.sample(1.0) - 12.0).abs() < 1e-9);
+        assert!((generator.sample(3.0) - 8.0).abs() < 1e-9);
+        assert!((generator.sample(5.0) - 12.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn ramp_and_square_repeat_every_period() {
+        let mut ramp = Generator::Ramp {
+            from: 0.0,
+            to: 100.0,
+            period: 10.0,
+        };
+        assert_eq!(ramp.sample(2.5), 25.0);
+        assert_eq!(ramp.sample(12.5), 25.0);
+
+        let mut square = Generator::Square {
+            low: -1.0,
+            high: 1.0,
+            period: 2.0,
+            duty: 0.5,
+        };
+        assert_eq!(square.sample(0.5), 1.0);
+        assert_eq!(square.sample(1.5), -1.0);
+    }
+
+    #[test]
+    fn seeded_generators_are_repeatable() {
+        let make = || Generator::RandomWalk {
+            start: 5.0,
+            step: 1.0,
+            min: Some(0.0),
+            max: Some(10.0),
+            seed: Some(42),
+            rng: None,
+            position: None,
+        };
+        let mut a = make();
+        let mut b = make();
+        let run_a: Vec<f64> = (0..20).map(|i| a.sample(i as f64)).collect();
+        let run_b: Vec<f64> = (0..20).map(|i| b.sample(i as f64)).collect();
+        assert_eq!(run_a, run_b);
+        assert_eq!(run_a[0], 5.0);
+        assert!(run_a.iter().all(|v| (0.0..=10.0).contains(v)));
+    }
+
+    #[test]
+    fn integer_conversion_saturates() {
+        assert_eq!(
+            value_from_f64(300.0, CanDataType::UInt8),
+            CanDataValue::UInt8(255)
+        );
+        assert_eq!(
+            value_from_f64(-5.0, CanDataType::UInt16),
+            CanDataValue::UInt16(0)
+        );
+        assert_eq!(
+            value_from_f64(-40_000.0, CanDataType::Int16),
+            CanDataValue::Int16(i16::MIN)
+        );
+        assert_eq!(
+            value_from_f64(1.5, CanDataType::Float32),
+            CanDataValue::Float32(1.5)
+        );
+        assert_eq!(
+            value_from_f64(0.7, CanDataType::Boolean),
+            CanDataValue::Boolean(true)
+        );
+    }
+}
@@ -0,0 +1,26 @@
+pub mod actuator;
+pub mod derived;
+pub mod expression;
+pub mod generator;
+pub(crate) mod rng;
+
+use crate::config::config_representation::EmulatorData;
+use std::time::Duration;
+
+/// Re-evaluates every telemetry generator, actuator model and expression for the given time
+/// since emulator start. Expressions see the values generators and models just produced and
+/// are evaluated in `expression_order`.
+pub fn update_telemetry(
+    emulator_data: &mut EmulatorData,
+    expression_order: &[usize],
+    elapsed: Duration,
+) {
+    let t = elapsed.as_secs_f64();
+    for tel in emulator_data.telemetry_values.iter_mut().flatten() {
+        if let Some(generator) = tel.generator.as_mut() {
+            tel.value = generator::value_from_f64(generator.sample(t), tel.datatype);
+        }
+    }
+    actuator::update_models(emulator_data, t);
+    derived::update_derived(emulator_data, expression_order, t);
+}
@@ -0,0 +1,285 @@
+use crate::message_kind::MessageKind;
+use crate::value_format::value_json;
+use liquidcan::payloads::{CanDataType, CanDataValue, PackedCanDataValues};
+use liquidcan::raw_can_message::CanMessagePriority;
+use liquidcan::{CanMessage, CanMessageId};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+/// What a node announced about itself during registration.
+#[derive(Default)]
+struct NodeCatalog {
+    /// Name and type by on-wire field ID (telemetry IDs carry the telemetry bit).
+    fields: HashMap<u8, (String, CanDataType)>,
+    /// Field IDs by telemetry group ID.
+    groups: HashMap<u8, Vec<u8>>,
+}
+
+/// A frame decoded for display.
+pub struct DecodedFrame {
+    pub priority: &'static str,
+    pub sender_id: u8,
+    pub receiver_id: u8,
+    pub kind: MessageKind,
+    pub fields: Vec<(String, Value)>,
+}
+
+impl DecodedFrame {
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "{:<4} {:>2} -> {:<2} {}",
+            self.priority, self.sender_id, self.receiver_id, self.kind
+        );
+        for (name, value) in &self.fields {
+            text.push_str(&format!(" {name}={value}"));
+        }
+        text
+    }
+
+    pub fn to_json(&self) -> String {
+        let fields: Map<String, Value> = self.fields.iter().cloned().collect();
+        json!({
+            "priority": self.priority,
+            "sender": self.sender_id,
+            "receiver": self.receiver_id,
+            "kind": self.kind.to_string(),
+            "fields": fields,
+        })
+        .to_string()
+    }
+}
+
+/// Which decoded frames to show.
+#[derive(Default)]
+pub struct SniffFilter {
+    /// Only frames sent by or addressed to this node.
+    pub node_id: Option<u8>,
+    /// Only these message kinds; empty shows all.
+    pub kinds: Vec<MessageKind>,
+}
+
+impl SniffFilter {
+    pub fn matches(&self, frame: &DecodedFrame) -> bool {
+        let node_matches = self
+            .node_id
+            .is_none_or(|node_id| frame.sender_id == node_id || frame.receiver_id == node_id);
+        node_matches && (self.kinds.is_empty() || self.kinds.contains(&frame.kind))
+    }
+}
+
+/// Decodes LiquidCAN traffic, learning field names and types from the registrations it sees.
+#[derive(Default)]
+pub struct Sniffer {
+    catalogs: HashMap<u8, NodeCatalog>,
+}
+
+fn priority_name(id: CanMessageId) -> &'static str {
+    let low_bits: u16 = CanMessageId::new()
+        .with_priority(CanMessagePriority::Low)
+        .into();
+    let raw: u16 = id.into();
+    if raw & low_bits == low_bits {
+        "low"
+    } else {
+        "high"
+    }
+}
+
+fn field(name: &str, value: impl Into<Value>) -> (String, Value) {
+    (name.to_string(), value.into())
+}
+
+impl Sniffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decodes one message. Registration messages are remembered so that later values of the
+    /// same node can be shown by name and type.
+    pub fn decode(&mut self, id: CanMessageId, msg: &CanMessage) -> DecodedFrame {
+        let sender_id = id.sender_id();
+        let receiver_id = id.receiver_id();
+        self.learn(sender_id, msg);
+        DecodedFrame {
+            priority: priority_name(id),
+            sender_id,
+            receiver_id,
+            kind: MessageKind::of(msg),
+            fields: self.fields(sender_id, receiver_id, msg),
+        }
+    }
+
+    fn learn(&mut self, sender_id: u8, msg: &CanMessage) {
+        match msg {
+            // A new announcement starts a new registration flow.
+            CanMessage::NodeInfoAnnouncement { .. } => {
+                self.catalogs.insert(sender_id, NodeCatalog::default());
+            }
+            CanMessage::TelemetryValueRegistration { payload }
+            | CanMessage::ParameterRegistration { payload } => {
+                self.catalogs.entry(sender_id).or_default().fields.insert(
+                    payload.field_id,
+                    (payload.field_name.clone().into(), payload.field_type),
+                );
+            }
+            CanMessage::TelemetryGroupDefinition { payload } => {
+                let field_ids: &[u8] = (&payload.field_ids).into();
+                self.catalogs
+                    .entry(sender_id)
+                    .or_default()
+                    .groups
+                    .insert(payload.group_id, field_ids.to_vec());
+            }
+            _ => {}
+        }
+    }
+
+    fn field_info(&self, node_id: u8, field_id: u8) -> Option<&(String, CanDataType)> {
+        self.catalogs.get(&node_id)?.fields.get(&field_id)
+    }
+
+    /// The field ID and, once registered, its name.
+    fn field_id_fields(&self, node_id: u8, name: &str, field_id: u8) -> Vec<(String, Value)> {
+        let mut fields = vec![field(name, field_id)];
+        if let Some((field_name, _)) = self.field_info(node_id, field_id) {
+            fields.push(field("name", field_name.as_str()));
+        }
+        fields
+    }
+
+    /// Values arrive untyped; show them typed when the field's registration was seen.
+    fn field_value(&self, node_id: u8, field_id: u8, value: &CanDataValue) -> Value {
+        let typed = match (value, self.field_info(node_id, field_id)) {
+            (CanDataValue::Raw(_), Some(&(_, data_type))) => value.convert_from_raw(data_type).ok(),
+            _ => None,
+        };
+        value_json(typed.as_ref().unwrap_or(value))
+    }
+
+    fn group_values(
+        &self,
+        node_id: u8,
+        group_id: u8,
+        values: &PackedCanDataValues<62>,
+    ) -> Vec<(String, Value)> {
+        let members: Option<Vec<&(String, CanDataType)>> = self
+            .catalogs
+            .get(&node_id)
+            .and_then(|catalog| catalog.groups.get(&group_id))
+            .and_then(|field_ids| {
+                field_ids
+                    .iter()
+                    .map(|&field_id| self.field_info(node_id, field_id))
+                    .collect()
+            });
+        let Some(members) = members else {
+            return vec![field("resolved", false)];
+        };
+        let values = values.unpack(members.iter().map(|(_, data_type)| *data_type));
+        members
+            .iter()
+            .zip(values)
+            .map(|((name, _), value)| match value {
+                Ok(value) => (name.clone(), value_json(&value)),
+                Err(_) => (name.clone(), Value::Null),
+            })
+            .collect()
+    }
+
+    fn fields(&self, sender_id: u8, receiver_id: u8, msg: &CanMessage) -> Vec<(String, Value)> {
+        match msg {
+            CanMessage::NodeInfoAnnouncement { payload } => vec![
+                field("device_name", String::from(payload.device_name.clone())),
+                field("tel_count", payload.tel_count),
+                field("par_count", payload.par_count),
+                field("firmware_hash", format!("{:#010x}", payload.firmware_hash)),
+                field("liquid_hash", format!("{:#010x}", payload.liquid_hash)),
+            ],
+            CanMessage::InfoStatus { payload }
+            | CanMessage::WarningStatus { payload }
+            | CanMessage::ErrorStatus { payload } => {
+                vec![field("msg", String::from(payload.msg.clone()))]
+            }
+            CanMessage::TelemetryValueRegistration { payload }
+            | CanMessage::ParameterRegistration { payload } => vec![
+                field("field_id", payload.field_id),
+                field("name", String::from(payload.field_name.clone())),
+                field("type", format!("{:?}", payload.field_type)),
+            ],
+            CanMessage::TelemetryGroupDefinition { payload } => {
+                let field_ids: &[u8] = (&payload.field_ids).into();
+                let names: Vec<Value> = field_ids
+                    .iter()
+                    .map(|&field_id| match self.field_info(sender_id, field_id) {
+                        Some((name, _)) => json!(name),
+                        None => json!(field_id),
+                    })
+                    .collect();
+                vec![field("group_id", payload.group_id), field("fields", names)]
+            }
+            CanMessage::TelemetryGroupUpdate { payload } => {
+                let mut fields = vec![field("group_id", payload.group_id)];
+                fields.extend(self.group_values(sender_id, payload.group_id, &payload.values));
+                fields
+            }
+            CanMessage::HeartbeatReq { payload } | CanMessage::HeartbeatRes { payload } => {
+                vec![field("counter", payload.counter)]
+            }
+            CanMessage::ParameterSetReq { payload } => {
+                let mut fields =
+                    self.field_id_fields(receiver_id, "parameter_id", payload.parameter_id);
+                fields.push(field(
+                    "value",
+                    self.field_value(receiver_id, payload.parameter_id, &payload.value),
+                ));
+                fields
+            }
+            CanMessage::ParameterSetConfirmation { payload } => {
+                let mut fields =
+                    self.field_id_fields(sender_id, "parameter_id", payload.parameter_id);
+                fields.push(field("status", format!("{:?}", payload.status)));
+                fields.push(field(
+                    "value",
+                    self.field_value(sender_id, payload.parameter_id, &payload.value),
+                ));
+                fields
+            }
+            CanMessage::ParameterSetLockReq { payload } => {
+                let mut fields =
+                    self.field_id_fields(receiver_id, "parameter_id", payload.parameter_id);
+                fields.push(field("lock", format!("{:?}", payload.parameter_lock)));
+                fields
+            }
+            CanMessage::ParameterSetLockConfirmation { payload } => {
+                let mut fields =
+                    self.field_id_fields(sender_id, "parameter_id", payload.parameter_id);
+                fields.push(field("lock", format!("{:?}", payload.parameter_lock)));
+                fields.push(field("status", format!("{:?}", payload.field_status)));
+                fields
+            }
+            CanMessage::FieldGetReq { payload } => {
+                self.field_id_fields(receiver_id, "field_id", payload.field_id)
+            }
+            CanMessage::FieldGetRes { payload } => {
+                let mut fields = self.field_id_fields(sender_id, "field_id", payload.field_id);
+                fields.push(field("status", format!("{:?}", payload.field_status)));
+                fields.push(field(
+                    "value",
+                    self.field_value(sender_id, payload.field_id, &payload.value),
+                ));
+                fields
+            }
+            CanMessage::FieldIDLookupReq { payload } => {
+                vec![field("name", String::from(payload.field_name.clone()))]
+            }
+            CanMessage::FieldIDLookupRes { payload } => {
+                let mut fields = self.field_id_fields(sender_id, "field_id", payload.field_id);
+                fields.push(field("status", format!("{:?}", payload.field_status)));
+                fields.push(field("type", format!("{:?}", payload.field_type)));
+                fields
+            }
+            #[allow(unreachable_patterns)]
+            _ => Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,38 @@
+//! Field values as the sniffer, the control API, the server check and alarm texts print them.
+
+use liquidcan::payloads::CanDataValue;
+use serde_json::{json, Value};
+
+/// `value` as JSON: numbers and booleans as such, raw bytes as a hex string.
+pub fn value_json(value: &CanDataValue) -> Value {
+    match value {
+        CanDataValue::Float32(v) => json!(v),
+        CanDataValue::Int32(v) => json!(v),
+        CanDataValue::Int16(v) => json!(v),
+        CanDataValue::Int8(v) => json!(v),
+        CanDataValue::UInt32(v) => json!(v),
+        CanDataValue::UInt16(v) => json!(v),
+        CanDataValue::UInt8(v) => json!(v),
+        CanDataValue::Boolean(v) => json!(v),
+        CanDataValue::Raw(bytes) => json!(hex(bytes)),
+    }
+}
+
+/// `value` as plain text, with raw bytes as hex like [`value_json`].
+pub fn value_text(value: &CanDataValue) -> String {
+    match value {
+        CanDataValue::Float32(v) => v.to_string(),
+        CanDataValue::Int32(v) => v.to_string(),
+        CanDataValue::Int16(v) => v.to_string(),
+        CanDataValue::Int8(v) => v.to_string(),
+        CanDataValue::UInt32(v) => v.to_string(),
+        CanDataValue::UInt16(v) => v.to_string(),
+        CanDataValue::UInt8(v) => v.to_string(),
+        CanDataValue::Boolean(v) => v.to_string(),
+        CanDataValue::Raw(bytes) => hex(bytes),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02X}")).collect()
+}
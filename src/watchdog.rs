@@ -0,0 +1,164 @@
+//! Emulates how an ECU reacts to losing its server: when the servers' heartbeat requests stop
+//! for longer than the configured timeout, the node reports it, moves parameters to safe values
+//! and optionally locks them. When heartbeats return, it unlocks them again and can re-register.
+
+use crate::config::config_representation::{ConfigScalar, EmulatorData};
+use crate::config::serde_deserializer::typed_value;
+use crate::message_handling::{
+    build_status_message, registration_flow_messages, StatusMessageKind,
+};
+use crate::simulation::actuator;
+use liquidcan::CanMessage;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// The `[Watchdog]` table of a node.
+#[derive(Deserialize, Debug)]
+pub struct Watchdog {
+    /// Seconds without a heartbeat request before the node enters its safe state.
+    pub timeout: f64,
+    /// Status message kind sent when the timeout expires.
+    #[serde(default = "default_level")]
+    pub level: StatusMessageKind,
+    #[serde(default = "default_message")]
+    pub message: String,
+    /// Info status sent when heartbeats return.
+    #[serde(default = "default_recovery_message")]
+    pub recovery_message: String,
+    /// Parameter values applied when the timeout expires, by parameter name.
+    #[serde(default)]
+    pub(crate) safe_values: BTreeMap<String, ConfigScalar>,
+    /// Lock the safe values until heartbeats return.
+    #[serde(default)]
+    pub lock: bool,
+    /// Send the registration flow again when heartbeats return.
+    #[serde(default)]
+    pub reregister: bool,
+}
+
+fn default_level() -> StatusMessageKind {
+    StatusMessageKind::Error
+}
+
+fn default_message() -> String {
+    "Server heartbeat lost, entering safe state".to_string()
+}
+
+fn default_recovery_message() -> String {
+    "Server heartbeat restored".to_string()
+}
+
+/// Tracks the servers' heartbeats against a node's `[Watchdog]`.
+///
+/// The watchdog is armed by the first heartbeat request, so a node that never had a server
+/// does not enter its safe state.
+#[derive(Default)]
+pub struct HeartbeatMonitor {
+    /// Time of the last heartbeat request, once armed.
+    last_heartbeat: Option<f64>,
+    tripped: bool,
+    /// Parameters the safe state locked, to be unlocked on recovery.
+    locked: Vec<String>,
+}
+
+impl HeartbeatMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_tripped(&self) -> bool {
+        self.tripped
+    }
+
+    /// Records a heartbeat request at time `t`. Returns the recovery messages for the servers
+    /// if the node was in its safe state.
+    pub fn heartbeat(&mut self, t: f64, emulator_data: &mut EmulatorData) -> Vec<CanMessage> {
+        self.last_heartbeat = Some(t);
+        if !self.tripped {
+            return Vec::new();
+        }
+        self.tripped = false;
+        for name in std::mem::take(&mut self.locked) {
+            if let Some(param) = emulator_data
+                .parameters
+                .iter_mut()
+                .flatten()
+                .find(|param| param.name == name)
+            {
+                param.locked = false;
+            }
+        }
+        let Some(watchdog) = emulator_data.watchdog.as_ref() else {
+            return Vec::new();
+        };
+        println!(
+            "Watchdog: node {} received a heartbeat again at {t:.3}s",
+            emulator_data.node_id
+        );
+        let mut messages = vec![build_status_message(
+            StatusMessageKind::Info,
+            &watchdog.recovery_message,
+        )];
+        if watchdog.reregister {
+            messages.extend(registration_flow_messages(emulator_data));
+        }
+        messages
+    }
+
+    /// Enters the safe state once the timeout has expired at time `t`. Returns the status
+    /// message for the servers when it does.
+    pub fn check(&mut self, t: f64, emulator_data: &mut EmulatorData) -> Vec<CanMessage> {
+        let Some(watchdog) = emulator_data.watchdog.as_ref() else {
+            return Vec::new();
+        };
+        let expired = self
+            .last_heartbeat
+            .is_some_and(|last| t - last >= watchdog.timeout);
+        if self.tripped || !expired {
+            return Vec::new();
+        }
+        self.tripped = true;
+        println!(
+            "Watchdog: node {} missed the server heartbeat for {}s at {t:.3}s",
+            emulator_data.node_id, watchdog.timeout
+        );
+        let status = build_status_message(watchdog.level, &watchdog.message);
+        let lock = watchdog.lock;
+        let safe_values: Vec<(String, ConfigScalar)> = watchdog
+            .safe_values
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+
+        for (name, value) in safe_values {
+            let Some(param) = emulator_data
+                .parameters
+                .iter_mut()
+                .flatten()
+                .find(|param| param.name == name)
+            else {
+                continue;
+            };
+            match typed_value(&value, param.datatype) {
+                Ok(value) => param.value = value,
+                Err(err) => {
+                    eprintln!("Watchdog: cannot apply safe value of {name}: {err}");
+                    continue;
+                }
+            }
+            if lock && !param.locked {
+                param.locked = true;
+                self.locked.push(name.clone());
+            }
+            actuator::parameter_changed(emulator_data, &name);
+        }
+        vec![status]
+    }
+
+    /// Seconds from `t` until the timeout expires, while the watchdog is armed and not tripped.
+    pub fn time_until_next(&self, t: f64, emulator_data: &EmulatorData) -> Option<f64> {
+        let watchdog = emulator_data.watchdog.as_ref()?;
+        let last = self.last_heartbeat.filter(|_| !self.tripped)?;
+        Some((last + watchdog.timeout - t).max(0.0))
+    }
+}
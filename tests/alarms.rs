@@ -0,0 +1,216 @@
+mod common;
+
+use common::{emulator_data_with, parameter, receive, telemetry};
+use liquidcan::{payloads, CanMessage};
+use std::time::{Duration, Instant};
+use ECUEmulator::alarms::{self, render_template, Alarm};
+use ECUEmulator::can_manager::in_memory_bus::InMemoryBus;
+use ECUEmulator::can_manager::{make_message_id, socket_manager};
+use ECUEmulator::config::config_representation::EmulatorData;
+use ECUEmulator::emulator::Emulator;
+use ECUEmulator::message_handling::{truncate_status_text, Event, MAX_STATUS_LEN};
+
+const TIMEOUT: Duration = Duration::from_millis(200);
+
+fn alarm(name: &str, rule: &str) -> Alarm {
+    let mut alarm = toml::from_str::<Alarm>(rule).unwrap();
+    alarm.name = name.to_string();
+    alarm
+}
+
+fn tank_node(alarms: Vec<Alarm>) -> EmulatorData {
+    let mut data = emulator_data_with(
+        Some(vec![telemetry(
+            "tank_pressure",
+            payloads::CanDataValue::Float32(10.0),
+        )]),
+        Some(vec![parameter(
+            "valve_target",
+            payloads::CanDataValue::UInt8(50),
+            false,
+        )]),
+    );
+    data.alarms = Some(alarms);
+    data
+}
+
+fn set_pressure(data: &mut EmulatorData, value: f32) {
+    data.telemetry_values.as_mut().unwrap()[0].value = payloads::CanDataValue::Float32(value);
+}
+
+fn status(msg: &CanMessage) -> (&'static str, String) {
+    match msg {
+        CanMessage::InfoStatus { payload } => ("info", String::from(payload.msg.clone())),
+        CanMessage::WarningStatus { payload } => ("warning", String::from(payload.msg.clone())),
+        CanMessage::ErrorStatus { payload } => ("error", String::from(payload.msg.clone())),
+        _ => panic!("expected a status message"),
+    }
+}
+
+fn statuses(messages: &[CanMessage]) -> Vec<(&'static str, String)> {
+    messages.iter().map(status).collect()
+}
+
+fn check(data: &mut EmulatorData, value: f32) -> Vec<(&'static str, String)> {
+    set_pressure(data, value);
+    statuses(&alarms::check_thresholds(data))
+}
+
+#[test]
+fn thresholds_raise_and_clear_with_hysteresis() {
+    let mut data = tank_node(vec![alarm(
+        "pressure",
+        r#"
+        field = "tank_pressure"
+        warn_above = 50
+        error_above = 80
+        hysteresis = 5
+        message = "{level}: {field} at {value} bar"
+        "#,
+    )]);
+    assert!(check(&mut data, 50.0).is_empty());
+    assert_eq!(
+        check(&mut data, 51.0),
+        vec![("warning", "warning: tank_pressure at 51 bar".to_string())]
+    );
+    // Back below the threshold, but not by the hysteresis yet.
+    assert!(check(&mut data, 46.0).is_empty());
+    assert_eq!(
+        check(&mut data, 81.5),
+        vec![("error", "error: tank_pressure at 81.5 bar".to_string())]
+    );
+    assert!(check(&mut data, 76.0).is_empty());
+    assert_eq!(
+        check(&mut data, 74.0),
+        vec![("warning", "warning: tank_pressure at 74 bar".to_string())]
+    );
+    assert_eq!(
+        check(&mut data, 44.0),
+        vec![("info", "pressure cleared: tank_pressure is 44".to_string())]
+    );
+    assert!(check(&mut data, 44.0).is_empty());
+}
+
+#[test]
+fn lower_thresholds_fire_below_the_value() {
+    let mut data = tank_node(vec![alarm(
+        "low_pressure",
+        r#"
+        field = "tank_pressure"
+        error_below = 5
+        clear_message = "{rule} ok"
+        "#,
+    )]);
+    assert!(alarms::check_thresholds(&mut data).is_empty());
+
+    set_pressure(&mut data, 4.5);
+    assert_eq!(
+        statuses(&alarms::check_thresholds(&mut data)),
+        vec![("error", "low_pressure: tank_pressure is 4.5".to_string())]
+    );
+    set_pressure(&mut data, 5.0);
+    assert_eq!(
+        statuses(&alarms::check_thresholds(&mut data)),
+        vec![("info", "low_pressure ok".to_string())]
+    );
+}
+
+#[test]
+fn event_rules_fire_on_their_parameter() {
+    let data = tank_node(vec![alarm(
+        "valve_moved",
+        r#"
+        on = { parameter_set = "valve_target" }
+        level = "warning"
+        message = "{field} set to {value}"
+        "#,
+    )]);
+    let messages = alarms::on_event(&data, &Event::ParameterSet("valve_target".to_string()));
+    assert_eq!(
+        statuses(&messages),
+        vec![("warning", "valve_target set to 50".to_string())]
+    );
+    assert!(alarms::on_event(&data, &Event::ParameterSet("other".to_string())).is_empty());
+}
+
+#[test]
+fn long_texts_are_cut_on_a_character_boundary() {
+    let text = "ü".repeat(40);
+    let cut = truncate_status_text(&text);
+    assert_eq!(cut.len(), MAX_STATUS_LEN - 1);
+    assert!(text.starts_with(cut));
+    assert_eq!(truncate_status_text("short"), "short");
+
+    let mut data = tank_node(vec![alarm(
+        "pressure",
+        r#"
+        field = "tank_pressure"
+        warn_above = 1
+        message = "{rule}: the tank pressure of {field} has risen to {value} bar"
+        "#,
+    )]);
+    let messages = alarms::check_thresholds(&mut data);
+    let (_, text) = status(&messages[0]);
+    assert_eq!(text.len(), MAX_STATUS_LEN);
+    assert!(text.starts_with("pressure: the tank pressure of tank_pressure"));
+}
+
+#[test]
+fn templates_escape_braces_and_reject_unknown_placeholders() {
+    assert_eq!(
+        render_template("{{{rule}}}", &[("rule", "hot")]).unwrap(),
+        "{hot}"
+    );
+    assert!(render_template("{temp}", &[("rule", "hot")]).is_err());
+    assert!(render_template("{rule", &[("rule", "hot")]).is_err());
+    assert!(render_template("rule}", &[("rule", "hot")]).is_err());
+}
+
+#[test]
+fn emulator_sends_alarm_statuses_to_the_servers() {
+    let bus = InMemoryBus::new();
+    let mut server = bus.attach();
+    let mut data = tank_node(vec![
+        alarm(
+            "valve_open",
+            r#"
+            field = "valve_target"
+            warn_above = 80
+            "#,
+        ),
+        alarm(
+            "valve_moved",
+            r#"
+            on = { parameter_set = "valve_target" }
+            message = "{field} set to {value}"
+            "#,
+        ),
+    ]);
+    data.frequency = 0;
+    let mut emulator = Emulator::new(data, bus.attach());
+    emulator.run_until(Instant::now() + Duration::from_millis(20));
+    while receive(&mut server, TIMEOUT).is_some() {}
+
+    let request = CanMessage::ParameterSetReq {
+        payload: payloads::ParameterSetReqPayload {
+            parameter_id: 1,
+            value: payloads::CanDataValue::UInt8(90),
+        },
+    };
+    socket_manager::send_frame(&mut server, make_message_id(1, 1), request).unwrap();
+    emulator.run_until(Instant::now() + Duration::from_millis(100));
+
+    let mut received = Vec::new();
+    while let Some(msg) = receive(&mut server, TIMEOUT) {
+        if !matches!(msg, CanMessage::ParameterSetConfirmation { .. }) {
+            received.push(status(&msg));
+        }
+    }
+    assert_eq!(
+        received,
+        vec![
+            ("info", "valve_target set to 90".to_string()),
+            ("warning", "valve_open: valve_target is 90".to_string()),
+        ]
+    );
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use common::{emulator_data_with, telemetry};
+use liquidcan::{payloads, CanMessage};
+use socketcan::ShouldRetry;
+use std::time::Duration;
+use ECUEmulator::can_manager::in_memory_bus::InMemoryBus;
+use ECUEmulator::can_manager::{make_message_id, send_messages, socket_manager};
+use ECUEmulator::message_handling::routing::route_messages;
+use ECUEmulator::message_handling::{handle_message, parse_can_message};
+
+const TIMEOUT: Duration = Duration::from_millis(100);
+
+#[test]
+fn frames_reach_every_other_node_but_not_the_sender() {
+    let bus = InMemoryBus::new();
+    let mut server = bus.attach();
+    let mut node_a = bus.attach();
+    let mut node_b = bus.attach();
+
+    socket_manager::send_frame(&mut server, make_message_id(0, 1), CanMessage::NodeInfoReq)
+        .unwrap();
+
+    for node in [&mut node_a, &mut node_b] {
+        let frame = socket_manager::read_frame(node, TIMEOUT).unwrap();
+        let (id, msg) = parse_can_message(frame).unwrap();
+        assert_eq!(id.sender_id(), 1);
+        assert_eq!(id.receiver_id(), 0);
+        assert!(matches!(msg, CanMessage::NodeInfoReq));
+    }
+
+    let res = socket_manager::read_frame(&mut server, Duration::from_millis(10));
+    assert!(res.should_retry(), "the sender must not see its own frame");
+}
+
+#[test]
+fn node_info_req_round_trips_over_the_bus() {
+    let bus = InMemoryBus::new();
+    let mut server = bus.attach();
+    let mut emulator = bus.attach();
+    let mut data = emulator_data_with(
+        Some(vec![telemetry("temp", payloads::CanDataValue::UInt8(20))]),
+        None,
+    );
+    data.node_id = 7;
+
+    socket_manager::send_frame(&mut server, make_message_id(7, 1), CanMessage::NodeInfoReq)
+        .unwrap();
+
+    let frame = socket_manager::read_frame(&mut emulator, TIMEOUT).unwrap();
+    let (id, msg) = parse_can_message(frame).unwrap();
+    let responses = handle_message(&msg, &mut data);
+    let expected = responses.len();
+    let routed = route_messages(&data, &[id.sender_id()], responses);
+    send_messages(&mut emulator, 7, routed);
+
+    let mut received = Vec::new();
+    while let Ok(frame) = socket_manager::read_frame(&mut server, TIMEOUT) {
+        let (id, msg) = parse_can_message(frame).unwrap();
+        assert_eq!(id.sender_id(), 7);
+        assert_eq!(id.receiver_id(), 1);
+        received.push(msg);
+    }
+    assert_eq!(received.len(), expected);
+    assert!(matches!(
+        received[0],
+        CanMessage::NodeInfoAnnouncement { .. }
+    ));
+    assert!(received
+        .iter()
+        .any(|msg| matches!(msg, CanMessage::TelemetryValueRegistration { .. })));
+}
+
+#[test]
+fn read_times_out_on_a_quiet_bus() {
+    let bus = InMemoryBus::new();
+    let mut node = bus.attach();
+    let res = socket_manager::read_frame(&mut node, Duration::from_millis(5));
+    assert!(res.should_retry());
+}
@@ -0,0 +1,141 @@
+use liquidcan::{payloads, CanMessage};
+use socketcan::{CanAnyFrame, CanDataFrame, CanFdFrame, EmbeddedFrame, StandardId};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use ECUEmulator::can_manager::candump::{format_line, parse_line, parse_log};
+use ECUEmulator::can_manager::in_memory_bus::InMemoryBus;
+use ECUEmulator::can_manager::recording::{Recorder, RecordingTransport};
+use ECUEmulator::can_manager::transport::CanTransport;
+use ECUEmulator::can_manager::{make_message_id, socket_manager};
+use ECUEmulator::message_handling::parse_can_message;
+use ECUEmulator::replay::{replay, ReplayOptions, ReplayStats};
+
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SharedBuffer {
+    fn contents(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+#[test]
+fn candump_lines_round_trip() {
+    let mut frame = CanFdFrame::new(StandardId::new(0x123).unwrap(), &[0xDE, 0xAD, 0xBE]).unwrap();
+    frame.set_brs(true);
+    let timestamp = Duration::from_micros(1_700_000_000_123_456);
+
+    let line = format_line(timestamp, "vcan0", &CanAnyFrame::Fd(frame)).unwrap();
+    assert_eq!(line, "(1700000000.123456) vcan0 123##1DEADBE");
+
+    let entry = parse_line(1, &line).unwrap();
+    assert_eq!(entry.timestamp, timestamp);
+    assert_eq!(entry.interface, "vcan0");
+    let parsed = entry.frame.unwrap();
+    assert_eq!(parsed.data(), frame.data());
+    assert!(parsed.is_brs());
+    assert!(!parsed.is_esi());
+
+    let classic = CanDataFrame::new(StandardId::new(0x7FF).unwrap(), &[1, 2]).unwrap();
+    let line = format_line(timestamp, "can1", &CanAnyFrame::Normal(classic)).unwrap();
+    assert_eq!(line, "(1700000000.123456) can1 7FF#0102");
+    assert!(parse_line(1, &line).unwrap().frame.is_none());
+}
+
+#[test]
+fn malformed_candump_lines_name_the_line() {
+    let err = parse_log("(1.000000) vcan0 123##0AA\n\n(2.0) vcan0 12Z##0AA\n")
+        .err()
+        .unwrap();
+    assert!(err.to_string().starts_with("line 3:"), "{err}");
+}
+
+#[test]
+fn recording_transport_logs_sent_and_received_frames() {
+    let bus = InMemoryBus::new();
+    let mut server = bus.attach();
+    let buffer = SharedBuffer::default();
+    let recorder = Recorder::to_writer(buffer.clone(), vec![7]);
+    let mut node = RecordingTransport::new(bus.attach(), recorder, "vcan0");
+
+    socket_manager::send_frame(&mut server, make_message_id(7, 1), CanMessage::NodeInfoReq)
+        .unwrap();
+    socket_manager::read_frame(&mut node, Duration::from_millis(100)).unwrap();
+    let response = CanMessage::HeartbeatRes {
+        payload: payloads::HeartbeatPayload { counter: 3 },
+    };
+    socket_manager::send_frame(&mut node, make_message_id(1, 7), response).unwrap();
+
+    let entries = parse_log(&buffer.contents()).unwrap();
+    assert_eq!(entries.len(), 2);
+    let messages: Vec<_> = entries
+        .iter()
+        .map(|entry| parse_can_message(CanAnyFrame::Fd(entry.frame.unwrap())).unwrap())
+        .collect();
+    assert_eq!(messages[0].0.sender_id(), 1);
+    assert!(matches!(messages[0].1, CanMessage::NodeInfoReq));
+    assert_eq!(messages[1].0.sender_id(), 7);
+    assert!(matches!(messages[1].1, CanMessage::HeartbeatRes { .. }));
+}
+
+#[test]
+fn replay_keeps_scaled_timing_and_filters_by_sender() {
+    let line = |secs: f64, sender: u8| {
+        let frame: CanFdFrame = CanMessage::NodeInfoReq.into();
+        let id = u16::from(make_message_id(1, sender));
+        let frame = CanFdFrame::new(StandardId::new(id).unwrap(), frame.data()).unwrap();
+        format_line(
+            Duration::from_secs_f64(secs),
+            "vcan0",
+            &CanAnyFrame::Fd(frame),
+        )
+        .unwrap()
+    };
+    let log = [
+        line(100.0, 3),
+        line(100.1, 4),
+        line(100.2, 3),
+        "(100.300000) vcan0 123#00".to_string(),
+    ]
+    .join("\n");
+    let entries = parse_log(&log).unwrap();
+
+    let bus = InMemoryBus::new();
+    let mut player = bus.attach();
+    let mut listener = bus.attach();
+    let options = ReplayOptions {
+        speed: 2.0,
+        sender_id: Some(3),
+    };
+    let start = Instant::now();
+    let stats = replay(&entries, &options, |_, frame| player.send_frame(frame));
+    let elapsed = start.elapsed();
+
+    assert_eq!(
+        stats,
+        ReplayStats {
+            sent: 2,
+            filtered: 1,
+            skipped: 1,
+            failed: 0,
+        }
+    );
+    assert!(elapsed >= Duration::from_millis(100), "{elapsed:?}");
+    assert!(elapsed < Duration::from_millis(500), "{elapsed:?}");
+    for _ in 0..2 {
+        let frame = socket_manager::read_frame(&mut listener, Duration::from_millis(10)).unwrap();
+        assert_eq!(parse_can_message(frame).unwrap().0.sender_id(), 3);
+    }
+}
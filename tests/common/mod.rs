@@ -1,27 +1,59 @@
-use liquidcan::payloads;
+use liquidcan::{payloads, CanMessage};
+use std::time::Duration;
+use ECUEmulator::can_manager::in_memory_bus::InMemoryTransport;
+use ECUEmulator::can_manager::socket_manager;
 use ECUEmulator::config::config_representation::{EmulatorData, Parameter, TelemetryValue};
+use ECUEmulator::message_handling::parse_can_message;
 
 #[allow(dead_code)]
-pub fn telemetry(name: &str, value: u32, datatype: payloads::CanDataType) -> TelemetryValue {
+pub fn telemetry(name: &str, value: payloads::CanDataValue) -> TelemetryValue {
     TelemetryValue {
         name: name.to_string(),
+        id: None,
+        datatype: data_type_of(&value),
         value,
-        datatype,
+        generator: None,
+        expr: None,
     }
 }
 
+/// The current value of the telemetry value `name`; panics if there is none.
 #[allow(dead_code)]
-pub fn parameter(
-    name: &str,
-    value: u32,
-    datatype: payloads::CanDataType,
-    locked: bool,
-) -> Parameter {
+pub fn telemetry_value(data: &EmulatorData, name: &str) -> payloads::CanDataValue {
+    data.telemetry_values
+        .iter()
+        .flatten()
+        .find(|tel| tel.name == name)
+        .map(|tel| tel.value.clone())
+        .unwrap()
+}
+
+#[allow(dead_code)]
+pub fn parameter(name: &str, value: payloads::CanDataValue, locked: bool) -> Parameter {
     Parameter {
         name: name.to_string(),
+        id: None,
+        datatype: data_type_of(&value),
         value,
         locked,
-        datatype,
+        min: None,
+        max: None,
+        allowed: None,
+        read_only: false,
+    }
+}
+
+fn data_type_of(value: &payloads::CanDataValue) -> payloads::CanDataType {
+    match value {
+        payloads::CanDataValue::Float32(_) => payloads::CanDataType::Float32,
+        payloads::CanDataValue::Int32(_) => payloads::CanDataType::Int32,
+        payloads::CanDataValue::Int16(_) => payloads::CanDataType::Int16,
+        payloads::CanDataValue::Int8(_) => payloads::CanDataType::Int8,
+        payloads::CanDataValue::UInt32(_) => payloads::CanDataType::UInt32,
+        payloads::CanDataValue::UInt16(_) => payloads::CanDataType::UInt16,
+        payloads::CanDataValue::UInt8(_) => payloads::CanDataType::UInt8,
+        payloads::CanDataValue::Boolean(_) => payloads::CanDataType::Boolean,
+        payloads::CanDataValue::Raw(_) => panic!("test fields need a typed value"),
     }
 }
 
@@ -37,7 +69,24 @@ pub fn emulator_data_with(
         firmware_hash: 0x123,
         liquid_hash: 0x456,
         device_name: "ECUEmulatorTest".to_string(),
-        telemetry_values,
         parameters,
+        telemetry_groups: None,
+        kernel_filter: false,
+        server_ids: vec![1],
+        routing: None,
+        state_file: None,
+        models: None,
+        faults: None,
+        watchdog: None,
+        alarms: None,
+        reboot: None,
+        telemetry_values,
     }
 }
+
+/// The next message on the bus `client` is attached to, or `None` after `timeout` without one.
+#[allow(dead_code)]
+pub fn receive(client: &mut InMemoryTransport, timeout: Duration) -> Option<CanMessage> {
+    let frame = socket_manager::read_frame(client, timeout).ok()?;
+    Some(parse_can_message(frame).unwrap().1)
+}
@@ -0,0 +1,242 @@
+mod common;
+
+use common::{emulator_data_with, parameter, telemetry};
+use liquidcan::{payloads, CanMessage};
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::thread;
+use std::time::{Duration, Instant};
+use ECUEmulator::can_manager::in_memory_bus::{InMemoryBus, InMemoryTransport};
+use ECUEmulator::can_manager::socket_manager;
+use ECUEmulator::control::{self, handle_request};
+use ECUEmulator::emulator::{Emulator, EmulatorHandle};
+use ECUEmulator::message_handling::parse_can_message;
+use ECUEmulator::simulation::actuator::{ActuatorModel, Dynamics};
+use ECUEmulator::simulation::generator::Generator;
+
+fn request(handles: &[EmulatorHandle], request: Value) -> Value {
+    serde_json::from_str(&handle_request(handles, &request.to_string())).unwrap()
+}
+
+fn node(node_id: u32, bus: &InMemoryBus) -> Emulator<InMemoryTransport> {
+    let mut pressure = telemetry("pressure", payloads::CanDataValue::Float32(1.0));
+    pressure.generator = Some(Generator::Ramp {
+        from: 0.0,
+        to: 10.0,
+        period: 1.0,
+    });
+    let mut data = emulator_data_with(
+        Some(vec![pressure]),
+        Some(vec![parameter(
+            "gain",
+            payloads::CanDataValue::UInt8(3),
+            false,
+        )]),
+    );
+    data.node_id = node_id;
+    data.frequency = 0;
+    Emulator::new(data, bus.attach())
+}
+
+#[test]
+fn fields_can_be_listed_read_and_written() {
+    let bus = InMemoryBus::new();
+    let emulator = node(2, &bus);
+    let handles = vec![emulator.handle()];
+
+    let list = request(&handles, json!({ "op": "list_fields" }));
+    assert_eq!(list["ok"], true);
+    assert_eq!(list["result"]["telemetry"][0]["name"], "pressure");
+    assert_eq!(list["result"]["telemetry"][0]["generated"], true);
+    assert_eq!(list["result"]["parameters"][0]["locked"], false);
+
+    let set = request(
+        &handles,
+        json!({ "op": "set_telemetry", "name": "pressure", "value": 35 }),
+    );
+    assert_eq!(set["result"], 35.0);
+    assert_eq!(
+        handles[0].telemetry_value("pressure"),
+        Some(payloads::CanDataValue::Float32(35.0))
+    );
+    let list = request(&handles, json!({ "op": "list_fields" }));
+    assert_eq!(list["result"]["telemetry"][0]["generated"], false);
+
+    let set = request(
+        &handles,
+        json!({ "op": "set_parameter", "name": "gain", "value": 7, "locked": true }),
+    );
+    assert_eq!(set["result"], json!({ "value": 7, "locked": true }));
+    let get = request(&handles, json!({ "op": "get_parameter", "name": "gain" }));
+    assert_eq!(get["result"], json!({ "value": 7, "locked": true }));
+
+    let err = request(
+        &handles,
+        json!({ "op": "set_parameter", "name": "gain", "value": 300 }),
+    );
+    assert_eq!(err["ok"], false);
+    assert!(err["error"].as_str().unwrap().contains("UInt8"), "{err}");
+    let err = request(&handles, json!({ "op": "get_telemetry", "name": "nope" }));
+    assert_eq!(err["ok"], false);
+    let stats = request(&handles, json!({ "op": "stats" }));
+    assert_eq!(stats["result"]["heartbeats"]["received"], 0, "{stats}");
+    assert_eq!(stats["result"]["dropped_frames"]["malformed"], 0, "{stats}");
+    let err = request(&handles, json!({ "op": "reboot", "boot_time": -1 }));
+    assert!(err["error"]
+        .as_str()
+        .unwrap()
+        .contains("Invalid boot time -1"));
+    let reboot = request(&handles, json!({ "op": "reboot", "boot_time": 0.5 }));
+    assert_eq!(reboot["ok"], true, "{reboot}");
+
+    let err = request(&handles, json!({ "op": "explode" }));
+    assert!(err["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("invalid request"));
+}
+
+#[test]
+fn parameter_writes_respect_constraints_and_read_only() {
+    let mut limit = parameter("limit", payloads::CanDataValue::UInt8(5), false);
+    limit.min = Some(payloads::CanDataValue::UInt8(1));
+    limit.max = Some(payloads::CanDataValue::UInt8(10));
+    let mut serial = parameter("serial", payloads::CanDataValue::UInt8(42), false);
+    serial.read_only = true;
+    let bus = InMemoryBus::new();
+    let emulator = Emulator::new(
+        emulator_data_with(None, Some(vec![limit, serial])),
+        bus.attach(),
+    );
+    let handles = vec![emulator.handle()];
+
+    let err = request(
+        &handles,
+        json!({ "op": "set_parameter", "name": "limit", "value": 11 }),
+    );
+    assert!(
+        err["error"].as_str().unwrap().contains("above max"),
+        "{err}"
+    );
+    let err = request(
+        &handles,
+        json!({ "op": "set_parameter", "name": "serial", "value": 1 }),
+    );
+    assert!(
+        err["error"].as_str().unwrap().contains("read-only"),
+        "{err}"
+    );
+    let set = request(
+        &handles,
+        json!({ "op": "set_parameter", "name": "limit", "value": 10 }),
+    );
+    assert_eq!(set["result"], json!({ "value": 10, "locked": false }));
+    let get = request(&handles, json!({ "op": "get_parameter", "name": "serial" }));
+    assert_eq!(get["result"], json!({ "value": 42, "locked": false }));
+}
+
+#[test]
+fn model_driven_telemetry_cannot_be_set() {
+    let bus = InMemoryBus::new();
+    let mut data = emulator_data_with(
+        Some(vec![telemetry(
+            "valve_position",
+            payloads::CanDataValue::UInt8(0),
+        )]),
+        Some(vec![parameter(
+            "valve_target",
+            payloads::CanDataValue::UInt8(0),
+            false,
+        )]),
+    );
+    data.models = Some(vec![ActuatorModel::new(
+        "valve_target",
+        &["valve_position"],
+        Dynamics::Copy,
+    )]);
+    let handles = vec![Emulator::new(data, bus.attach()).handle()];
+
+    let err = request(
+        &handles,
+        json!({ "op": "set_telemetry", "name": "valve_position", "value": 50 }),
+    );
+    assert_eq!(err["ok"], false);
+    assert!(err["error"]
+        .as_str()
+        .unwrap()
+        .contains("driven by model valve_target"));
+    assert_eq!(
+        handles[0].telemetry_value("valve_position"),
+        Some(payloads::CanDataValue::UInt8(0))
+    );
+}
+
+#[test]
+fn requests_pick_the_node_by_id() {
+    let bus = InMemoryBus::new();
+    let handles = vec![node(2, &bus).handle(), node(3, &bus).handle()];
+
+    let err = request(&handles, json!({ "op": "list_fields" }));
+    assert!(err["error"].as_str().unwrap().contains("needs a `node`"));
+
+    request(
+        &handles,
+        json!({ "op": "set_parameter", "node": 3, "name": "gain", "value": 9 }),
+    );
+    assert_eq!(
+        handles[0].parameter_value("gain"),
+        Some(payloads::CanDataValue::UInt8(3))
+    );
+    assert_eq!(
+        handles[1].parameter_value("gain"),
+        Some(payloads::CanDataValue::UInt8(9))
+    );
+}
+
+#[test]
+fn socket_clients_can_trigger_registration_and_status_messages() {
+    let bus = InMemoryBus::new();
+    let mut server = bus.attach();
+    let mut emulator = node(2, &bus);
+    let handle = emulator.handle();
+    let path =
+        std::env::temp_dir().join(format!("ecuemulator_control_{}.sock", std::process::id()));
+    control::spawn(&path, vec![handle.clone()]).unwrap();
+    let runner =
+        thread::spawn(move || emulator.run_until(Instant::now() + Duration::from_secs(10)));
+
+    let drain = |server: &mut InMemoryTransport| {
+        let mut messages = Vec::new();
+        while let Ok(frame) = socket_manager::read_frame(server, Duration::from_millis(200)) {
+            messages.push(parse_can_message(frame).unwrap().1);
+        }
+        messages
+    };
+    drain(&mut server);
+
+    let mut client = UnixStream::connect(&path).unwrap();
+    let mut responses = BufReader::new(client.try_clone().unwrap()).lines();
+    for line in [
+        json!({ "op": "reregister" }),
+        json!({ "op": "send_status", "level": "warning", "message": "valve stuck" }),
+    ] {
+        writeln!(client, "{line}").unwrap();
+        let response: Value = serde_json::from_str(&responses.next().unwrap().unwrap()).unwrap();
+        assert_eq!(response["ok"], true, "{response}");
+    }
+
+    let messages = drain(&mut server);
+    assert!(matches!(
+        messages.first(),
+        Some(CanMessage::NodeInfoAnnouncement { .. })
+    ));
+    let CanMessage::WarningStatus { payload } = messages.last().unwrap() else {
+        panic!("Expected WarningStatus");
+    };
+    assert_eq!(String::from(payload.msg.clone()), "valve stuck");
+
+    handle.shutdown();
+    runner.join().unwrap();
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,274 @@
+mod common;
+
+use common::{emulator_data_with, parameter, receive, telemetry};
+use liquidcan::{payloads, CanMessage};
+use socketcan::{CanFdFrame, EmbeddedFrame, ExtendedId, StandardId};
+use std::thread;
+use std::time::{Duration, Instant};
+use ECUEmulator::can_manager::in_memory_bus::{InMemoryBus, InMemoryTransport};
+use ECUEmulator::can_manager::transport::CanTransport;
+use ECUEmulator::can_manager::{make_message_id, socket_manager};
+use ECUEmulator::emulator::Emulator;
+use ECUEmulator::message_handling::parse_can_message;
+
+const TIMEOUT: Duration = Duration::from_millis(200);
+
+#[test]
+fn first_step_announces_the_node() {
+    let bus = InMemoryBus::new();
+    let mut server = bus.attach();
+    let data = emulator_data_with(
+        Some(vec![telemetry("temp", payloads::CanDataValue::UInt8(20))]),
+        None,
+    );
+    let mut emulator = Emulator::new(data, bus.attach());
+
+    emulator.step();
+
+    assert!(matches!(
+        receive(&mut server, TIMEOUT),
+        Some(CanMessage::NodeInfoAnnouncement { .. })
+    ));
+}
+
+#[test]
+fn running_emulator_answers_requests_and_exposes_values() {
+    let bus = InMemoryBus::new();
+    let mut server = bus.attach();
+    let mut data = emulator_data_with(
+        None,
+        Some(vec![parameter(
+            "gain",
+            payloads::CanDataValue::UInt16(1),
+            false,
+        )]),
+    );
+    data.frequency = 0;
+    let mut emulator = Emulator::new(data, bus.attach());
+    let handle = emulator.handle();
+    let runner =
+        thread::spawn(move || emulator.run_until(Instant::now() + Duration::from_secs(10)));
+
+    // Drain the registration flow.
+    while receive(&mut server, TIMEOUT).is_some() {}
+
+    let request = CanMessage::ParameterSetReq {
+        payload: payloads::ParameterSetReqPayload {
+            parameter_id: 1,
+            value: payloads::CanDataValue::UInt16(42),
+        },
+    };
+    socket_manager::send_frame(&mut server, make_message_id(1, 1), request).unwrap();
+    assert!(matches!(
+        receive(&mut server, TIMEOUT),
+        Some(CanMessage::ParameterSetConfirmation { .. })
+    ));
+    assert_eq!(
+        handle.parameter_value("gain"),
+        Some(payloads::CanDataValue::UInt16(42))
+    );
+
+    handle
+        .set_parameter_value("gain", payloads::CanDataValue::UInt16(7))
+        .unwrap();
+    assert_eq!(
+        handle.parameter_value("gain"),
+        Some(payloads::CanDataValue::UInt16(7))
+    );
+    assert!(handle
+        .set_parameter_value("gain", payloads::CanDataValue::Boolean(true))
+        .is_err());
+    assert!(handle
+        .set_parameter_value("missing", payloads::CanDataValue::UInt16(7))
+        .is_err());
+
+    handle.shutdown();
+    runner.join().unwrap();
+}
+
+#[test]
+fn run_until_sends_telemetry_until_the_deadline() {
+    let bus = InMemoryBus::new();
+    let mut server = bus.attach();
+    let mut data = emulator_data_with(
+        Some(vec![telemetry("temp", payloads::CanDataValue::UInt8(20))]),
+        None,
+    );
+    data.frequency = 100;
+    let mut emulator = Emulator::new(data, bus.attach());
+    let handle = emulator.handle();
+    handle
+        .set_telemetry_value("temp", payloads::CanDataValue::UInt8(33))
+        .unwrap();
+
+    let start = Instant::now();
+    emulator.run_until(start + Duration::from_millis(100));
+    assert!(start.elapsed() >= Duration::from_millis(100));
+
+    let mut updates = 0;
+    while let Some(msg) = receive(&mut server, TIMEOUT) {
+        if matches!(msg, CanMessage::TelemetryGroupUpdate { .. }) {
+            updates += 1;
+        }
+    }
+    assert!(updates >= 3, "expected periodic updates, got {updates}");
+    assert_eq!(
+        handle.telemetry_value("temp"),
+        Some(payloads::CanDataValue::UInt8(33))
+    );
+}
+
+#[test]
+fn nodes_on_one_bus_only_answer_their_own_id() {
+    for kernel_filter in [true, false] {
+        let dropped = answer_heartbeat_for_node_3(kernel_filter);
+        if kernel_filter {
+            assert_eq!(dropped, vec![0, 0]);
+        } else {
+            // Node 2 sees both the request and node 3's response to the server.
+            assert_eq!(dropped, vec![2, 0]);
+        }
+    }
+}
+
+/// Returns how many frames each node dropped while node 3 answered a heartbeat.
+fn answer_heartbeat_for_node_3(kernel_filter: bool) -> Vec<u64> {
+    let bus = InMemoryBus::new();
+    let mut server = bus.attach();
+    let mut emulators: Vec<_> = [2, 3]
+        .into_iter()
+        .map(|node_id| {
+            let mut data = emulator_data_with(None, None);
+            data.node_id = node_id;
+            data.frequency = 0;
+            data.kernel_filter = kernel_filter;
+            Emulator::new(data, bus.attach())
+        })
+        .collect();
+    let settle = |emulators: &mut Vec<Emulator<InMemoryTransport>>| {
+        for emulator in emulators {
+            emulator.run_until(Instant::now() + Duration::from_millis(20));
+        }
+    };
+    settle(&mut emulators);
+    while receive(&mut server, TIMEOUT).is_some() {}
+    let registration_drops: Vec<u64> = emulators
+        .iter()
+        .map(|emulator| emulator.handle().dropped_frames().not_addressed)
+        .collect();
+
+    let request = CanMessage::HeartbeatReq {
+        payload: payloads::HeartbeatPayload { counter: 1 },
+    };
+    socket_manager::send_frame(&mut server, make_message_id(3, 1), request).unwrap();
+    settle(&mut emulators);
+
+    let frame = socket_manager::read_frame(&mut server, TIMEOUT).unwrap();
+    let (id, msg) = parse_can_message(frame).unwrap();
+    assert_eq!(id.sender_id(), 3);
+    assert!(matches!(msg, CanMessage::HeartbeatRes { .. }));
+    assert!(
+        receive(&mut server, TIMEOUT).is_none(),
+        "node 2 must stay silent"
+    );
+    emulators
+        .iter()
+        .zip(registration_drops)
+        .map(|(emulator, before)| emulator.handle().dropped_frames().not_addressed - before)
+        .collect()
+}
+
+#[test]
+fn broadcast_frames_pass_the_receiver_filter() {
+    let bus = InMemoryBus::new();
+    let mut server = bus.attach();
+    let mut data = emulator_data_with(None, None);
+    data.frequency = 0;
+    let mut emulator = Emulator::new(data, bus.attach());
+    emulator.step();
+    while receive(&mut server, TIMEOUT).is_some() {}
+
+    socket_manager::send_frame(&mut server, make_message_id(0, 1), CanMessage::NodeInfoReq)
+        .unwrap();
+    emulator.step();
+
+    assert!(matches!(
+        receive(&mut server, TIMEOUT),
+        Some(CanMessage::NodeInfoAnnouncement { .. })
+    ));
+    assert_eq!(emulator.handle().dropped_frames().not_addressed, 0);
+}
+
+#[test]
+fn unparseable_frames_are_counted_and_the_emulator_keeps_running() {
+    let bus = InMemoryBus::new();
+    let mut server = bus.attach();
+    let mut data = emulator_data_with(None, None);
+    data.frequency = 0;
+    let mut emulator = Emulator::new(data, bus.attach());
+    emulator.run_until(Instant::now() + Duration::from_millis(20));
+    while receive(&mut server, TIMEOUT).is_some() {}
+
+    let node_info: CanFdFrame = CanMessage::NodeInfoReq.into();
+    let extended = CanFdFrame::new(ExtendedId::new(0x0123_4561).unwrap(), node_info.data());
+    let malformed = CanFdFrame::new(StandardId::new(0x21).unwrap(), &[0xFF; 8]);
+    for frame in [extended, malformed, malformed] {
+        server.send_frame(&frame.unwrap()).unwrap();
+    }
+    socket_manager::send_frame(&mut server, make_message_id(1, 1), CanMessage::NodeInfoReq)
+        .unwrap();
+    emulator.run_until(Instant::now() + Duration::from_millis(50));
+
+    let dropped = emulator.handle().dropped_frames();
+    assert_eq!(
+        (dropped.extended_id, dropped.malformed, dropped.non_fd),
+        (1, 2, 0)
+    );
+    assert!(matches!(
+        receive(&mut server, TIMEOUT),
+        Some(CanMessage::NodeInfoAnnouncement { .. })
+    ));
+}
+
+#[test]
+fn heartbeat_anomalies_are_reported_and_counted() {
+    let bus = InMemoryBus::new();
+    let mut server = bus.attach();
+    let mut data = emulator_data_with(None, None);
+    data.frequency = 0;
+    let mut emulator = Emulator::new(data, bus.attach());
+    emulator.run_until(Instant::now() + Duration::from_millis(20));
+    while receive(&mut server, TIMEOUT).is_some() {}
+
+    let mut warnings = Vec::new();
+    for counter in [u32::MAX, 0, 0, 3, 1] {
+        let request = CanMessage::HeartbeatReq {
+            payload: payloads::HeartbeatPayload { counter },
+        };
+        socket_manager::send_frame(&mut server, make_message_id(1, 1), request).unwrap();
+        emulator.run_until(Instant::now() + Duration::from_millis(20));
+        let Some(CanMessage::HeartbeatRes { payload }) = receive(&mut server, TIMEOUT) else {
+            panic!("expected a heartbeat response");
+        };
+        assert_eq!(payload.counter, counter.wrapping_add(1));
+        while let Some(msg) = receive(&mut server, TIMEOUT) {
+            let CanMessage::WarningStatus { payload } = msg else {
+                panic!("expected only warnings besides the response");
+            };
+            warnings.push(String::from(payload.msg.clone()));
+        }
+    }
+
+    assert_eq!(
+        warnings,
+        [
+            "Heartbeat repeat from node 1: counter 0",
+            "Heartbeat gap from node 1: expected 1, got 3",
+            "Heartbeat reset by node 1: expected 4, got 1",
+        ]
+    );
+    let stats = emulator.handle().heartbeat_stats();
+    assert_eq!(stats.received, 5);
+    assert_eq!((stats.gaps, stats.missed), (1, 2));
+    assert_eq!((stats.repeats, stats.resets), (1, 1));
+}
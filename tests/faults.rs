@@ -0,0 +1,191 @@
+mod common;
+
+use common::emulator_data_with;
+use liquidcan::{payloads, CanMessage, CanMessageId};
+use socketcan::{CanAnyFrame, CanFdFrame, EmbeddedFrame, Id};
+use ECUEmulator::config::config_representation::EmulatorData;
+use ECUEmulator::faults::{Direction, Fault, FaultInjector, FaultKind};
+use ECUEmulator::message_handling::parse_can_message;
+use ECUEmulator::message_kind::MessageKind;
+
+const NODE_ID: u8 = 3;
+
+fn heartbeat(counter: u32) -> (u8, CanMessage) {
+    let payload = payloads::HeartbeatPayload { counter };
+    (1, CanMessage::HeartbeatRes { payload })
+}
+
+fn node_with(faults: Vec<Fault>) -> EmulatorData {
+    let mut data = emulator_data_with(None, None);
+    data.node_id = u32::from(NODE_ID);
+    data.faults = Some(faults);
+    data
+}
+
+fn decode(frame: &CanFdFrame) -> (CanMessageId, CanMessage) {
+    parse_can_message(CanAnyFrame::Fd(*frame)).unwrap()
+}
+
+fn counters(frames: &[(u8, CanFdFrame)]) -> Vec<u32> {
+    frames
+        .iter()
+        .map(|(_, frame)| match decode(frame).1 {
+            CanMessage::HeartbeatRes { payload } => payload.counter,
+            _ => panic!("expected heartbeat responses"),
+        })
+        .collect()
+}
+
+#[test]
+fn drop_only_hits_its_message_kinds() {
+    let mut fault = Fault::new("lossy", FaultKind::Drop);
+    fault.messages = vec![MessageKind::HeartbeatRes];
+    let data = node_with(vec![fault]);
+    let mut injector = FaultInjector::new();
+
+    let messages = vec![heartbeat(1), (1, CanMessage::NodeInfoReq), heartbeat(2)];
+    let frames = injector.transmit(0.0, &data, NODE_ID, messages);
+    assert_eq!(frames.len(), 1);
+    assert!(matches!(decode(&frames[0].1).1, CanMessage::NodeInfoReq));
+}
+
+#[test]
+fn seeded_percentages_are_repeatable() {
+    let run = || {
+        let mut fault = Fault::new("lossy", FaultKind::Drop);
+        fault.percent = 30.0;
+        fault.seed = Some(42);
+        let data = node_with(vec![fault]);
+        let messages = (0..200).map(heartbeat).collect();
+        counters(&FaultInjector::new().transmit(0.0, &data, NODE_ID, messages))
+    };
+    let sent = run();
+    assert_eq!(sent, run());
+    assert!((110..170).contains(&sent.len()), "{} sent", sent.len());
+}
+
+#[test]
+fn delayed_frames_go_out_when_due() {
+    let data = node_with(vec![Fault::new("slow", FaultKind::Delay { delay: 0.5 })]);
+    let mut injector = FaultInjector::new();
+
+    assert!(injector
+        .transmit(1.0, &data, NODE_ID, vec![heartbeat(1)])
+        .is_empty());
+    let wait = injector.time_until_next(1.2).unwrap();
+    assert!((wait - 0.3).abs() < 1e-9, "{wait}");
+    assert!(injector.release_due(1.4).is_empty());
+    assert_eq!(counters(&injector.release_due(1.5)), vec![1]);
+    assert_eq!(injector.time_until_next(1.5), None);
+}
+
+#[test]
+fn duplicate_and_reorder_change_the_frame_sequence() {
+    let data = node_with(vec![Fault::new("echo", FaultKind::Duplicate)]);
+    let frames = FaultInjector::new().transmit(0.0, &data, NODE_ID, vec![heartbeat(1)]);
+    assert_eq!(counters(&frames), vec![1, 1]);
+
+    let mut reorder = Fault::new("swap", FaultKind::Reorder);
+    reorder.percent = 50.0;
+    reorder.seed = Some(3);
+    let data = node_with(vec![reorder]);
+    let mut injector = FaultInjector::new();
+    let messages = (0..20).map(heartbeat).collect();
+    let mut sent = counters(&injector.transmit(0.0, &data, NODE_ID, messages));
+    sent.extend(counters(&injector.release_due(1.0)));
+
+    let mut sorted = sent.clone();
+    sorted.sort();
+    assert_eq!(sorted, (0..20).collect::<Vec<_>>());
+    assert_ne!(sent, sorted, "some frames should be overtaken");
+}
+
+#[test]
+fn corrupt_and_wrong_sender_alter_the_frame() {
+    let data = node_with(vec![
+        Fault::new("noise", FaultKind::Corrupt { bytes: 2 }),
+        Fault::new("impostor", FaultKind::WrongSender { sender_id: Some(9) }),
+    ]);
+    let clean = FaultInjector::new().transmit(0.0, &node_with(vec![]), NODE_ID, vec![heartbeat(7)]);
+    let frames = FaultInjector::new().transmit(0.0, &data, NODE_ID, vec![heartbeat(7)]);
+
+    let (_, frame) = &frames[0];
+    assert_ne!(frame.data(), clean[0].1.data());
+    let Id::Standard(raw_id) = frame.id() else {
+        panic!("expected a standard ID");
+    };
+    let id = CanMessageId::from_bytes(raw_id.as_raw().to_le_bytes());
+    assert_eq!(id.sender_id(), 9);
+    assert_eq!(id.receiver_id(), 1);
+}
+
+#[test]
+fn receive_faults_drop_or_repeat_requests() {
+    let request = CanMessage::HeartbeatReq {
+        payload: payloads::HeartbeatPayload { counter: 1 },
+    };
+    let mut drop = Fault::new("deaf", FaultKind::Drop);
+    drop.direction = Direction::Receive;
+    let data = node_with(vec![drop]);
+    assert_eq!(FaultInjector::new().receive(0.0, &data, 1, &request), 0);
+    // Receive faults leave transmitted frames alone.
+    assert_eq!(
+        FaultInjector::new()
+            .transmit(0.0, &data, NODE_ID, vec![heartbeat(1)])
+            .len(),
+        1
+    );
+
+    let mut duplicate = Fault::new("echo", FaultKind::Duplicate);
+    duplicate.direction = Direction::Receive;
+    let data = node_with(vec![duplicate]);
+    assert_eq!(FaultInjector::new().receive(0.0, &data, 1, &request), 2);
+}
+
+#[test]
+fn disabled_faults_act_only_while_activated() {
+    let mut fault = Fault::new("outage", FaultKind::Silence);
+    fault.enabled = false;
+    let data = node_with(vec![fault]);
+    let mut injector = FaultInjector::new();
+
+    assert_eq!(
+        injector
+            .transmit(0.0, &data, NODE_ID, vec![heartbeat(1)])
+            .len(),
+        1
+    );
+    assert!(injector.activate(&data, "outage", 2.0));
+    assert!(!injector.activate(&data, "missing", 2.0));
+    assert!(injector
+        .transmit(1.0, &data, NODE_ID, vec![heartbeat(2)])
+        .is_empty());
+    assert_eq!(
+        injector
+            .transmit(2.0, &data, NODE_ID, vec![heartbeat(3)])
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn silence_holds_back_its_kinds_until_it_ends() {
+    let data = node_with(vec![]);
+    let mut injector = FaultInjector::new();
+    injector.silence(vec![MessageKind::HeartbeatRes], Some(2.0));
+
+    let messages = vec![heartbeat(1), (1, CanMessage::NodeInfoReq)];
+    assert_eq!(injector.transmit(1.0, &data, NODE_ID, messages).len(), 1);
+    assert_eq!(
+        injector
+            .transmit(2.0, &data, NODE_ID, vec![heartbeat(2)])
+            .len(),
+        1
+    );
+
+    injector.silence(Vec::new(), None);
+    let messages = vec![heartbeat(3), (1, CanMessage::NodeInfoReq)];
+    assert!(injector
+        .transmit(100.0, &data, NODE_ID, messages)
+        .is_empty());
+}
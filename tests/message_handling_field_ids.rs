@@ -0,0 +1,132 @@
+mod common;
+
+use common::{emulator_data_with, parameter, telemetry};
+use liquidcan::{payloads, CanMessage};
+use ECUEmulator::message_handling::handle_message;
+
+type NamedIds = Vec<(String, u8)>;
+
+fn registered_ids(responses: &[CanMessage]) -> (NamedIds, NamedIds) {
+    let mut telemetry_ids = Vec::new();
+    let mut parameter_ids = Vec::new();
+    for msg in responses {
+        match msg {
+            CanMessage::TelemetryValueRegistration { payload } => {
+                telemetry_ids.push((payload.field_name.clone().into(), payload.field_id))
+            }
+            CanMessage::ParameterRegistration { payload } => {
+                parameter_ids.push((payload.field_name.clone().into(), payload.field_id))
+            }
+            _ => {}
+        }
+    }
+    (telemetry_ids, parameter_ids)
+}
+
+#[test]
+fn explicit_ids_are_used_and_others_fill_free_slots() {
+    let mut pinned = telemetry("zeta", payloads::CanDataValue::UInt8(1));
+    pinned.id = Some(1);
+    let telemetry_values = vec![
+        pinned,
+        telemetry("beta", payloads::CanDataValue::UInt8(2)),
+        telemetry("alpha", payloads::CanDataValue::UInt8(3)),
+    ];
+    let mut pinned_param = parameter("p_pinned", payloads::CanDataValue::UInt8(4), false);
+    pinned_param.id = Some(10);
+    let parameters = vec![
+        pinned_param,
+        parameter("p_auto", payloads::CanDataValue::UInt8(5), false),
+    ];
+    let mut data = emulator_data_with(Some(telemetry_values), Some(parameters));
+
+    let responses = handle_message(&CanMessage::NodeInfoReq, &mut data);
+    let (telemetry_ids, parameter_ids) = registered_ids(&responses);
+
+    assert_eq!(
+        telemetry_ids,
+        vec![
+            ("zeta".to_string(), 0x81),
+            ("alpha".to_string(), 0x82),
+            ("beta".to_string(), 0x83),
+        ]
+    );
+    assert_eq!(
+        parameter_ids,
+        vec![("p_auto".to_string(), 1), ("p_pinned".to_string(), 10)]
+    );
+}
+
+#[test]
+fn adding_a_field_does_not_renumber_pinned_fields() {
+    let mut pinned = parameter("valve", payloads::CanDataValue::UInt8(0), false);
+    pinned.id = Some(3);
+    let parameters = vec![
+        pinned,
+        parameter("aaa", payloads::CanDataValue::UInt8(0), false),
+    ];
+    let mut data = emulator_data_with(None, Some(parameters));
+
+    let responses = handle_message(&CanMessage::NodeInfoReq, &mut data);
+    let (_, parameter_ids) = registered_ids(&responses);
+
+    assert!(parameter_ids.contains(&("valve".to_string(), 3)));
+}
+
+#[test]
+fn requests_use_the_same_id_mapping_as_registration() {
+    let mut tel = telemetry("pressure", payloads::CanDataValue::UInt16(35));
+    tel.id = Some(20);
+    let mut param = parameter("setpoint", payloads::CanDataValue::UInt16(1), false);
+    param.id = Some(7);
+    let mut data = emulator_data_with(Some(vec![tel]), Some(vec![param]));
+
+    let lookup = CanMessage::FieldIDLookupReq {
+        payload: payloads::FieldIDLookupReqPayload {
+            field_name: payloads::CanString::<61>::try_from("pressure").unwrap(),
+        },
+    };
+    let responses = handle_message(&lookup, &mut data);
+    let CanMessage::FieldIDLookupRes { payload } = &responses[0] else {
+        panic!("Expected FieldIDLookupRes");
+    };
+    assert_eq!(payload.field_id, 0x80 | 20);
+
+    let get = CanMessage::FieldGetReq {
+        payload: payloads::FieldGetReqPayload {
+            field_id: 0x80 | 20,
+        },
+    };
+    let responses = handle_message(&get, &mut data);
+    let CanMessage::FieldGetRes { payload } = &responses[0] else {
+        panic!("Expected FieldGetRes");
+    };
+    assert_eq!(payload.value, payloads::CanDataValue::UInt16(35));
+
+    let set = CanMessage::ParameterSetReq {
+        payload: payloads::ParameterSetReqPayload {
+            parameter_id: 7,
+            value: payloads::CanDataValue::UInt16(99),
+        },
+    };
+    let responses = handle_message(&set, &mut data);
+    let CanMessage::ParameterSetConfirmation { payload } = &responses[0] else {
+        panic!("Expected ParameterSetConfirmation");
+    };
+    assert_eq!(payload.status, payloads::ParameterSetStatus::Success);
+
+    let stale = CanMessage::ParameterSetReq {
+        payload: payloads::ParameterSetReqPayload {
+            parameter_id: 1,
+            value: payloads::CanDataValue::UInt16(5),
+        },
+    };
+    let responses = handle_message(&stale, &mut data);
+    let CanMessage::ParameterSetConfirmation { payload } = &responses[0] else {
+        panic!("Expected ParameterSetConfirmation");
+    };
+    assert_eq!(
+        payload.status,
+        payloads::ParameterSetStatus::InvalidParameterID
+    );
+}
@@ -6,7 +6,7 @@ use ECUEmulator::message_handling::handle_message;
 
 #[test]
 fn field_get_req_returns_telemetry_value() {
-    let telemetry_values = vec![telemetry("t1", 0xAA, payloads::CanDataType::UInt8)];
+    let telemetry_values = vec![telemetry("t1", payloads::CanDataValue::UInt8(0xAA))];
     let mut data = emulator_data_with(Some(telemetry_values), None);
 
     let request = CanMessage::FieldGetReq {
@@ -24,7 +24,7 @@ fn field_get_req_returns_telemetry_value() {
 
 #[test]
 fn field_get_req_returns_parameter_value() {
-    let parameters = vec![parameter("p1", 0x10, payloads::CanDataType::UInt8, false)];
+    let parameters = vec![parameter("p1", payloads::CanDataValue::UInt8(0x10), false)];
     let mut data = emulator_data_with(None, Some(parameters));
 
     let request = CanMessage::FieldGetReq {
@@ -59,8 +59,8 @@ fn field_get_req_not_found_returns_empty_raw() {
 
 #[test]
 fn field_id_lookup_prefers_telemetry_when_names_match() {
-    let telemetry_values = vec![telemetry("dup", 1, payloads::CanDataType::UInt8)];
-    let parameters = vec![parameter("dup", 2, payloads::CanDataType::UInt8, false)];
+    let telemetry_values = vec![telemetry("dup", payloads::CanDataValue::UInt8(1))];
+    let parameters = vec![parameter("dup", payloads::CanDataValue::UInt8(2), false)];
     let mut data = emulator_data_with(Some(telemetry_values), Some(parameters));
 
     let request = CanMessage::FieldIDLookupReq {
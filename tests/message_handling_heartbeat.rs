@@ -1,18 +1,15 @@
+mod common;
+
+use common::emulator_data_with;
 use liquidcan::{payloads, CanMessage};
-use ECUEmulator::message_handling::{build_status_message, handle_message, StatusMessageKind};
+use ECUEmulator::message_handling::{
+    build_status_message, handle_message, HeartbeatAnomaly, HeartbeatStats, HeartbeatTracker,
+    StatusMessageKind, MAX_STATUS_LEN,
+};
 
 #[test]
 fn heartbeat_req_increments_counter() {
-    let mut data = ECUEmulator::config::config_representation::EmulatorData {
-        node_id: 1,
-        can_interface: "vcan0".to_string(),
-        frequency: 100,
-        firmware_hash: 0,
-        liquid_hash: 0,
-        device_name: "ECUEmulatorTest".to_string(),
-        telemetry_values: None,
-        parameters: None,
-    };
+    let mut data = emulator_data_with(None, None);
 
     let request = CanMessage::HeartbeatReq {
         payload: payloads::HeartbeatPayload { counter: 41 },
@@ -24,6 +21,15 @@ fn heartbeat_req_increments_counter() {
     };
 
     assert_eq!(payload.counter, 42);
+
+    let request = CanMessage::HeartbeatReq {
+        payload: payloads::HeartbeatPayload { counter: u32::MAX },
+    };
+    let responses = handle_message(&request, &mut data);
+    let CanMessage::HeartbeatRes { payload } = &responses[0] else {
+        panic!("Expected HeartbeatRes");
+    };
+    assert_eq!(payload.counter, 0);
 }
 
 #[test]
@@ -36,3 +42,65 @@ fn build_status_message_variants() {
     assert!(matches!(warn, CanMessage::WarningStatus { .. }));
     assert!(matches!(err, CanMessage::ErrorStatus { .. }));
 }
+
+#[test]
+fn counters_wrap_around() {
+    let mut tracker = HeartbeatTracker::new();
+    assert_eq!(tracker.track(1, u32::MAX - 1), None);
+    assert_eq!(tracker.track(1, u32::MAX), None);
+    assert_eq!(tracker.track(1, 0), None);
+    assert_eq!(
+        tracker.track(1, 3),
+        Some(HeartbeatAnomaly::Gap {
+            expected: 1,
+            received: 3
+        })
+    );
+    assert_eq!(tracker.stats().missed, 2);
+}
+
+#[test]
+fn senders_are_tracked_separately() {
+    let mut tracker = HeartbeatTracker::new();
+    assert_eq!(tracker.track(1, 10), None);
+    assert_eq!(tracker.track(5, 500), None);
+    assert_eq!(tracker.track(1, 11), None);
+    assert_eq!(
+        tracker.track(5, 500),
+        Some(HeartbeatAnomaly::Repeat { counter: 500 })
+    );
+    assert_eq!(
+        tracker.track(1, 0),
+        Some(HeartbeatAnomaly::Reset {
+            expected: 12,
+            received: 0
+        })
+    );
+    assert_eq!(
+        tracker.stats(),
+        HeartbeatStats {
+            received: 5,
+            gaps: 0,
+            missed: 0,
+            repeats: 1,
+            resets: 1,
+        }
+    );
+}
+
+#[test]
+fn descriptions_fit_into_a_status_message() {
+    for anomaly in [
+        HeartbeatAnomaly::Gap {
+            expected: u32::MAX,
+            received: u32::MAX,
+        },
+        HeartbeatAnomaly::Repeat { counter: u32::MAX },
+        HeartbeatAnomaly::Reset {
+            expected: u32::MAX,
+            received: u32::MAX,
+        },
+    ] {
+        assert!(anomaly.describe(30).len() <= MAX_STATUS_LEN);
+    }
+}
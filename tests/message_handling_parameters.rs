@@ -2,11 +2,12 @@ mod common;
 
 use common::{emulator_data_with, parameter};
 use liquidcan::{payloads, CanMessage};
+use ECUEmulator::config::config_representation::Parameter;
 use ECUEmulator::message_handling::handle_message;
 
 #[test]
 fn parameter_set_req_updates_value_when_unlocked() {
-    let parameters = vec![parameter("p1", 10, payloads::CanDataType::UInt16, false)];
+    let parameters = vec![parameter("p1", payloads::CanDataValue::UInt16(10), false)];
     let mut data = emulator_data_with(None, Some(parameters));
 
     let request = CanMessage::ParameterSetReq {
@@ -29,7 +30,7 @@ fn parameter_set_req_updates_value_when_unlocked() {
 
 #[test]
 fn parameter_set_req_respects_lock() {
-    let parameters = vec![parameter("p1", 99, payloads::CanDataType::UInt8, true)];
+    let parameters = vec![parameter("p1", payloads::CanDataValue::UInt8(99), true)];
     let mut data = emulator_data_with(None, Some(parameters));
 
     let request = CanMessage::ParameterSetReq {
@@ -53,7 +54,7 @@ fn parameter_set_req_respects_lock() {
 
 #[test]
 fn parameter_set_req_invalid_id_returns_invalid_status() {
-    let parameters = vec![parameter("p1", 10, payloads::CanDataType::UInt32, false)];
+    let parameters = vec![parameter("p1", payloads::CanDataValue::UInt32(10), false)];
     let mut data = emulator_data_with(None, Some(parameters));
 
     let request = CanMessage::ParameterSetReq {
@@ -76,7 +77,7 @@ fn parameter_set_req_invalid_id_returns_invalid_status() {
 
 #[test]
 fn parameter_set_req_invalid_payload_returns_invalid_status() {
-    let parameters = vec![parameter("p1", 10, payloads::CanDataType::UInt32, false)];
+    let parameters = vec![parameter("p1", payloads::CanDataValue::UInt32(10), false)];
     let mut data = emulator_data_with(None, Some(parameters));
 
     let request = CanMessage::ParameterSetReq {
@@ -99,7 +100,7 @@ fn parameter_set_req_invalid_payload_returns_invalid_status() {
 
 #[test]
 fn parameter_set_lock_updates_state() {
-    let parameters = vec![parameter("p1", 10, payloads::CanDataType::UInt8, false)];
+    let parameters = vec![parameter("p1", payloads::CanDataValue::UInt8(10), false)];
     let mut data = emulator_data_with(None, Some(parameters));
 
     let request = CanMessage::ParameterSetLockReq {
@@ -123,3 +124,120 @@ fn parameter_set_lock_updates_state() {
     let updated = data.parameters.as_ref().unwrap();
     assert!(updated[0].locked);
 }
+
+/// Sends `value` to the only parameter and checks the confirmation.
+fn assert_set_confirmed(
+    param: Parameter,
+    value: payloads::CanDataValue,
+    status: payloads::ParameterSetStatus,
+    confirmed_value: payloads::CanDataValue,
+) {
+    let mut data = emulator_data_with(None, Some(vec![param]));
+    let request = CanMessage::ParameterSetReq {
+        payload: payloads::ParameterSetReqPayload {
+            parameter_id: 1,
+            value,
+        },
+    };
+    let responses = handle_message(&request, &mut data);
+    let CanMessage::ParameterSetConfirmation { payload } = &responses[0] else {
+        panic!("Expected ParameterSetConfirmation");
+    };
+    assert_eq!(payload.status, status);
+    assert_eq!(payload.value, confirmed_value);
+}
+
+#[test]
+fn parameter_set_req_rejects_values_outside_min_max() {
+    let bounded = || {
+        let mut param = parameter("p1", payloads::CanDataValue::Int16(0), false);
+        param.min = Some(payloads::CanDataValue::Int16(-10));
+        param.max = Some(payloads::CanDataValue::Int16(10));
+        param
+    };
+
+    for value in [-10, 10] {
+        let value = payloads::CanDataValue::Int16(value);
+        let status = payloads::ParameterSetStatus::Success;
+        assert_set_confirmed(bounded(), value.clone(), status, value);
+    }
+    for value in [-11, 11] {
+        assert_set_confirmed(
+            bounded(),
+            payloads::CanDataValue::Int16(value),
+            payloads::ParameterSetStatus::InvalidParameterID,
+            payloads::CanDataValue::Int16(0),
+        );
+    }
+}
+
+#[test]
+fn parameter_set_req_rejects_non_finite_values_of_bounded_parameters() {
+    let bounded = |min: Option<f32>, max: Option<f32>| {
+        let mut param = parameter("gain", payloads::CanDataValue::Float32(1.0), false);
+        param.min = min.map(payloads::CanDataValue::Float32);
+        param.max = max.map(payloads::CanDataValue::Float32);
+        param
+    };
+
+    for value in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+        for (min, max) in [
+            (Some(0.0), None),
+            (None, Some(10.0)),
+            (Some(0.0), Some(10.0)),
+        ] {
+            assert_set_confirmed(
+                bounded(min, max),
+                payloads::CanDataValue::Float32(value),
+                payloads::ParameterSetStatus::InvalidParameterID,
+                payloads::CanDataValue::Float32(1.0),
+            );
+        }
+    }
+    // Without bounds, any float goes.
+    assert_set_confirmed(
+        bounded(None, None),
+        payloads::CanDataValue::Float32(f32::INFINITY),
+        payloads::ParameterSetStatus::Success,
+        payloads::CanDataValue::Float32(f32::INFINITY),
+    );
+}
+
+#[test]
+fn parameter_set_req_rejects_values_not_allowed() {
+    let modes = || {
+        let mut param = parameter("mode", payloads::CanDataValue::UInt8(1), false);
+        param.allowed = Some(vec![
+            payloads::CanDataValue::UInt8(1),
+            payloads::CanDataValue::UInt8(2),
+            payloads::CanDataValue::UInt8(4),
+        ]);
+        param
+    };
+
+    assert_set_confirmed(
+        modes(),
+        payloads::CanDataValue::UInt8(4),
+        payloads::ParameterSetStatus::Success,
+        payloads::CanDataValue::UInt8(4),
+    );
+    assert_set_confirmed(
+        modes(),
+        payloads::CanDataValue::UInt8(3),
+        payloads::ParameterSetStatus::InvalidParameterID,
+        payloads::CanDataValue::UInt8(1),
+    );
+}
+
+#[test]
+fn parameter_set_req_rejects_read_only_parameters() {
+    let mut param = parameter("serial", payloads::CanDataValue::UInt32(1234), false);
+    param.read_only = true;
+
+    assert_set_confirmed(
+        param,
+        payloads::CanDataValue::UInt32(1),
+        payloads::ParameterSetStatus::ParameterLocked,
+        payloads::CanDataValue::UInt32(1234),
+    );
+}
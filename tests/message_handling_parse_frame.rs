@@ -0,0 +1,38 @@
+use liquidcan::CanMessage;
+use socketcan::{CanAnyFrame, CanDataFrame, CanFdFrame, EmbeddedFrame, ExtendedId, StandardId};
+use ECUEmulator::message_handling::errors::ParseFrameError;
+use ECUEmulator::message_handling::parse_can_message;
+
+fn fd_frame(id: impl Into<socketcan::Id>, data: &[u8]) -> CanAnyFrame {
+    CanAnyFrame::Fd(CanFdFrame::new(id, data).unwrap())
+}
+
+#[test]
+fn classic_frames_are_rejected() {
+    let frame = CanDataFrame::new(StandardId::new(0x21).unwrap(), &[1, 2, 3]).unwrap();
+    let err = parse_can_message(CanAnyFrame::Normal(frame)).err().unwrap();
+    assert!(matches!(err, ParseFrameError::NonFdFrame { .. }), "{err}");
+}
+
+#[test]
+fn extended_ids_are_rejected() {
+    let message: CanFdFrame = CanMessage::NodeInfoReq.into();
+    let frame = fd_frame(ExtendedId::new(0x0123_4567).unwrap(), message.data());
+    let err = parse_can_message(frame).err().unwrap();
+    assert!(
+        matches!(
+            err,
+            ParseFrameError::ExtendedId {
+                raw_id: 0x0123_4567
+            }
+        ),
+        "{err}"
+    );
+}
+
+#[test]
+fn malformed_payloads_are_rejected() {
+    let frame = fd_frame(StandardId::new(0x21).unwrap(), &[0xFF; 8]);
+    let err = parse_can_message(frame).err().unwrap();
+    assert!(matches!(err, ParseFrameError::Malformed { .. }), "{err}");
+}
@@ -7,12 +7,12 @@ use ECUEmulator::message_handling::handle_message;
 #[test]
 fn node_info_req_emits_full_registration_flow() {
     let telemetry_values = vec![
-        telemetry("beta", 10, payloads::CanDataType::UInt16),
-        telemetry("alpha", 20, payloads::CanDataType::UInt8),
+        telemetry("beta", payloads::CanDataValue::UInt16(10)),
+        telemetry("alpha", payloads::CanDataValue::UInt8(20)),
     ];
     let parameters = vec![
-        parameter("p2", 100, payloads::CanDataType::UInt32, false),
-        parameter("p1", 200, payloads::CanDataType::Boolean, true),
+        parameter("p2", payloads::CanDataValue::UInt32(100), false),
+        parameter("p1", payloads::CanDataValue::Boolean(true), true),
     ];
     let mut data = emulator_data_with(Some(telemetry_values), Some(parameters));
 
@@ -83,13 +83,7 @@ fn node_info_req_emits_full_registration_flow() {
 #[test]
 fn telemetry_group_definition_splits_by_payload_size() {
     let telemetry_values: Vec<_> = (1..=16)
-        .map(|idx| {
-            telemetry(
-                &format!("t{:02}", idx),
-                idx as u32,
-                payloads::CanDataType::UInt32,
-            )
-        })
+        .map(|idx| telemetry(&format!("t{:02}", idx), payloads::CanDataValue::UInt32(idx)))
         .collect();
     let mut data = emulator_data_with(Some(telemetry_values), None);
 
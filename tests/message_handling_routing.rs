@@ -0,0 +1,95 @@
+mod common;
+
+use common::{emulator_data_with, parameter};
+use liquidcan::{payloads, CanMessage};
+use std::collections::HashMap;
+use ECUEmulator::message_handling::handle_message;
+use ECUEmulator::message_handling::routing::route_messages;
+use ECUEmulator::message_kind::MessageKind;
+
+fn set_request() -> CanMessage {
+    CanMessage::ParameterSetReq {
+        payload: payloads::ParameterSetReqPayload {
+            parameter_id: 1,
+            value: payloads::CanDataValue::UInt8(5),
+        },
+    }
+}
+
+fn receivers(routed: &[(u8, CanMessage)]) -> Vec<u8> {
+    routed.iter().map(|(receiver, _)| *receiver).collect()
+}
+
+#[test]
+fn confirmations_are_mirrored_to_the_servers_by_default() {
+    let mut data = emulator_data_with(
+        None,
+        Some(vec![parameter(
+            "gain",
+            payloads::CanDataValue::UInt8(1),
+            false,
+        )]),
+    );
+    data.server_ids = vec![1, 4];
+
+    let responses = handle_message(&set_request(), &mut data);
+    let routed = route_messages(&data, &[9], responses);
+    assert_eq!(receivers(&routed), vec![9, 1, 4]);
+    assert!(routed
+        .iter()
+        .all(|(_, msg)| matches!(msg, CanMessage::ParameterSetConfirmation { .. })));
+
+    // A server that asked itself gets a single copy.
+    let responses = handle_message(&set_request(), &mut data);
+    assert_eq!(
+        receivers(&route_messages(&data, &[4], responses)),
+        vec![4, 1]
+    );
+
+    let responses = handle_message(&CanMessage::NodeInfoReq, &mut data);
+    let routed = route_messages(&data, &[9], responses);
+    assert!(routed.iter().all(|(receiver, _)| *receiver == 9));
+}
+
+#[test]
+fn routing_table_overrides_only_the_kinds_it_lists() {
+    let mut data = emulator_data_with(
+        None,
+        Some(vec![parameter(
+            "gain",
+            payloads::CanDataValue::UInt8(1),
+            false,
+        )]),
+    );
+    data.routing = Some(HashMap::from([(MessageKind::HeartbeatRes, vec![12])]));
+
+    let responses = handle_message(&set_request(), &mut data);
+    assert_eq!(
+        receivers(&route_messages(&data, &[9], responses)),
+        vec![9, 1]
+    );
+
+    let heartbeat = CanMessage::HeartbeatReq {
+        payload: payloads::HeartbeatPayload { counter: 1 },
+    };
+    let responses = handle_message(&heartbeat, &mut data);
+    assert_eq!(
+        receivers(&route_messages(&data, &[9], responses)),
+        vec![9, 12]
+    );
+
+    data.routing
+        .as_mut()
+        .unwrap()
+        .insert(MessageKind::ParameterSetConfirmation, Vec::new());
+    let responses = handle_message(&set_request(), &mut data);
+    assert_eq!(receivers(&route_messages(&data, &[9], responses)), vec![9]);
+}
+
+#[test]
+fn message_kinds_round_trip_through_their_config_names() {
+    let kind: MessageKind = "parameter_set_lock_confirmation".parse().unwrap();
+    assert_eq!(kind, MessageKind::ParameterSetLockConfirmation);
+    assert_eq!(kind.to_string(), "parameter_set_lock_confirmation");
+    assert!("not_a_kind".parse::<MessageKind>().is_err());
+}
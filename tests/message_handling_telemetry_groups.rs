@@ -0,0 +1,115 @@
+mod common;
+
+use common::{emulator_data_with, telemetry};
+use liquidcan::{payloads, CanMessage};
+use std::time::{Duration, Instant};
+use ECUEmulator::config::config_representation::TelemetryGroup;
+use ECUEmulator::message_handling::{
+    build_telemetry_group_update, handle_message, telemetry_group_layout, TelemetrySchedule,
+};
+
+fn grouped_data() -> ECUEmulator::config::config_representation::EmulatorData {
+    let telemetry_values = vec![
+        telemetry("a_housekeeping", payloads::CanDataValue::UInt8(1)),
+        telemetry("b_pressure", payloads::CanDataValue::UInt16(2)),
+        telemetry("c_valve", payloads::CanDataValue::UInt8(3)),
+        telemetry("d_temperature", payloads::CanDataValue::UInt8(4)),
+    ];
+    let mut data = emulator_data_with(Some(telemetry_values), None);
+    data.frequency = 1;
+    data.telemetry_groups = Some(vec![TelemetryGroup {
+        name: "fast".to_string(),
+        members: vec!["c_valve".to_string(), "b_pressure".to_string()],
+        rate: 1000.0,
+    }]);
+    data
+}
+
+#[test]
+fn explicit_groups_define_layout_and_rate() {
+    let mut data = grouped_data();
+
+    let layout = telemetry_group_layout(&data);
+    assert_eq!(layout.len(), 2);
+    assert_eq!((layout[0].group_id, layout[0].rate), (1, 1000.0));
+    assert_eq!((layout[1].group_id, layout[1].rate), (2, 1.0));
+
+    let responses = handle_message(&CanMessage::NodeInfoReq, &mut data);
+    let group_defs: Vec<Vec<u8>> = responses
+        .iter()
+        .filter_map(|msg| match msg {
+            CanMessage::TelemetryGroupDefinition { payload } => {
+                let ids: &[u8] = (&payload.field_ids).into();
+                Some(ids.to_vec())
+            }
+            _ => None,
+        })
+        .collect();
+
+    // Members keep their listed order; ungrouped telemetry is packed into the remaining group.
+    assert_eq!(group_defs, vec![vec![0x83, 0x82], vec![0x81, 0x84]]);
+}
+
+#[test]
+fn single_group_update_packs_members_in_order() {
+    let data = grouped_data();
+
+    let update = build_telemetry_group_update(&data, 1).expect("group 1 exists");
+    let CanMessage::TelemetryGroupUpdate { payload } = update else {
+        panic!("Expected TelemetryGroupUpdate");
+    };
+    assert_eq!(payload.group_id, 1);
+    let values: Vec<_> = payload
+        .values
+        .unpack([payloads::CanDataType::UInt8, payloads::CanDataType::UInt16].into_iter())
+        .map(|val| val.expect("unpack should succeed"))
+        .collect();
+    assert_eq!(
+        values,
+        vec![
+            payloads::CanDataValue::UInt8(3),
+            payloads::CanDataValue::UInt16(2)
+        ]
+    );
+
+    assert!(build_telemetry_group_update(&data, 3).is_none());
+}
+
+#[test]
+fn schedule_runs_each_group_at_its_own_rate() {
+    let data = grouped_data();
+    let start = Instant::now();
+    let mut schedule = TelemetrySchedule::new(&data, start);
+
+    assert!(schedule.due_groups(start).is_empty());
+
+    let mut fast = 0;
+    let mut slow = 0;
+    for ms in 1..=2000 {
+        for group_id in schedule.due_groups(start + Duration::from_millis(ms)) {
+            match group_id {
+                1 => fast += 1,
+                2 => slow += 1,
+                other => panic!("unexpected group {other}"),
+            }
+        }
+    }
+
+    assert_eq!(fast, 2000);
+    assert_eq!(slow, 2);
+}
+
+#[test]
+fn groups_too_slow_to_ever_be_due_are_not_scheduled() {
+    let mut data = grouped_data();
+    data.telemetry_groups.as_mut().unwrap()[0].rate = 1e-20;
+    let start = Instant::now();
+    let mut schedule = TelemetrySchedule::new(&data, start);
+
+    assert_eq!(schedule.due_groups(start + Duration::from_secs(1)), vec![2]);
+    assert!(schedule.update(&data, 1).is_none());
+    let Some(CanMessage::TelemetryGroupUpdate { payload }) = schedule.update(&data, 2) else {
+        panic!("Expected TelemetryGroupUpdate");
+    };
+    assert_eq!(payload.group_id, 2);
+}
@@ -7,8 +7,8 @@ use ECUEmulator::message_handling::build_telemetry_group_updates;
 #[test]
 fn telemetry_group_update_orders_values_by_name() {
     let telemetry_values = vec![
-        telemetry("b", 2, payloads::CanDataType::UInt16),
-        telemetry("a", 1, payloads::CanDataType::UInt16),
+        telemetry("b", payloads::CanDataValue::UInt16(2)),
+        telemetry("a", payloads::CanDataValue::UInt16(1)),
     ];
     let data = emulator_data_with(Some(telemetry_values), None);
 
@@ -32,13 +32,7 @@ fn telemetry_group_update_orders_values_by_name() {
 #[test]
 fn telemetry_group_update_splits_by_payload_size() {
     let telemetry_values: Vec<_> = (1..=16)
-        .map(|idx| {
-            telemetry(
-                &format!("t{:02}", idx),
-                idx as u32,
-                payloads::CanDataType::UInt32,
-            )
-        })
+        .map(|idx| telemetry(&format!("t{:02}", idx), payloads::CanDataValue::UInt32(idx)))
         .collect();
     let data = emulator_data_with(Some(telemetry_values), None);
 
@@ -0,0 +1,67 @@
+mod common;
+
+use common::{emulator_data_with, parameter};
+use liquidcan::{payloads, CanMessage};
+use std::fs;
+use std::time::{Duration, Instant};
+use ECUEmulator::can_manager::in_memory_bus::InMemoryBus;
+use ECUEmulator::can_manager::{make_message_id, socket_manager};
+use ECUEmulator::config::config_representation::EmulatorData;
+use ECUEmulator::emulator::Emulator;
+
+fn node_data(state_file: &std::path::Path) -> EmulatorData {
+    let mut data = emulator_data_with(
+        None,
+        Some(vec![parameter(
+            "gain",
+            payloads::CanDataValue::UInt16(1),
+            false,
+        )]),
+    );
+    data.frequency = 0;
+    data.state_file = Some(state_file.to_path_buf());
+    data
+}
+
+#[test]
+fn parameters_set_over_can_survive_a_restart() {
+    let path = std::env::temp_dir().join(format!(
+        "ecuemulator_parameter_state_{}.toml",
+        std::process::id()
+    ));
+    let _ = fs::remove_file(&path);
+    let bus = InMemoryBus::new();
+    let mut server = bus.attach();
+    let mut emulator = Emulator::new(node_data(&path), bus.attach());
+    emulator.run_until(Instant::now() + Duration::from_millis(20));
+    assert!(!path.exists(), "unchanged parameters are not written");
+
+    let requests = [
+        CanMessage::ParameterSetReq {
+            payload: payloads::ParameterSetReqPayload {
+                parameter_id: 1,
+                value: payloads::CanDataValue::UInt16(42),
+            },
+        },
+        CanMessage::ParameterSetLockReq {
+            payload: payloads::ParameterSetLockPayload {
+                parameter_id: 1,
+                parameter_lock: payloads::ParameterLockStatus::Locked,
+            },
+        },
+    ];
+    for request in requests {
+        socket_manager::send_frame(&mut server, make_message_id(1, 1), request).unwrap();
+    }
+    emulator.run_until(Instant::now() + Duration::from_millis(50));
+    drop(emulator);
+
+    let restarted = Emulator::new(node_data(&path), bus.attach());
+    let _ = fs::remove_file(&path);
+    let handle = restarted.handle();
+    assert_eq!(
+        handle.parameter_value("gain"),
+        Some(payloads::CanDataValue::UInt16(42))
+    );
+    assert!(handle.with_data(|data| data.parameters.as_ref().unwrap()[0].locked));
+}
@@ -0,0 +1,300 @@
+mod common;
+
+use common::{emulator_data_with, parameter, receive, telemetry};
+use liquidcan::{payloads, CanMessage};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use ECUEmulator::can_manager::in_memory_bus::{InMemoryBus, InMemoryTransport};
+use ECUEmulator::can_manager::{make_message_id, socket_manager};
+use ECUEmulator::config::config_loader::load_nodes;
+use ECUEmulator::config::config_representation::EmulatorData;
+use ECUEmulator::emulator::{Emulator, EmulatorHandle};
+use ECUEmulator::faults::FaultInjector;
+use ECUEmulator::reboot::Reboot;
+use ECUEmulator::scenario::Scenario;
+
+const TIMEOUT: Duration = Duration::from_millis(50);
+
+fn config(firmware_hash: &str, telemetry: &str) -> String {
+    format!(
+        r#"node_id = 2
+frequency = 0
+firmware_hash = "{firmware_hash}"
+can_interface = "vcan0"
+liquid_hash = "0x456"
+device_name = "Rebooting"
+
+[TelemetryValues]
+{telemetry}
+
+[Parameters]
+    [Parameters.gain]
+    value = 1
+    locked = false
+    datatype = "UInt16"
+"#
+    )
+}
+
+const PRESSURE: &str = r#"    [TelemetryValues.pressure]
+    value = 5
+    datatype = "UInt8""#;
+
+const PRESSURE_AND_TEMP: &str = r#"    [TelemetryValues.pressure]
+    value = 5
+    datatype = "UInt8"
+    [TelemetryValues.temp]
+    value = 20
+    datatype = "Int16""#;
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "ecuemulator_reboot_{name}_{}.toml",
+        std::process::id()
+    ))
+}
+
+fn received(client: &mut InMemoryTransport) -> Vec<CanMessage> {
+    let mut messages = Vec::new();
+    while let Some(msg) = receive(client, TIMEOUT) {
+        messages.push(msg);
+    }
+    messages
+}
+
+fn heartbeat(counter: u32) -> CanMessage {
+    CanMessage::HeartbeatReq {
+        payload: payloads::HeartbeatPayload { counter },
+    }
+}
+
+/// Starts `data` on a bus and drops the registration flow of its first step.
+fn start(
+    data: EmulatorData,
+) -> (
+    InMemoryTransport,
+    Emulator<InMemoryTransport>,
+    EmulatorHandle,
+) {
+    let bus = InMemoryBus::new();
+    let mut server = bus.attach();
+    let mut emulator = Emulator::new(data, bus.attach());
+    let handle = emulator.handle();
+    emulator.run_until(Instant::now() + Duration::from_millis(20));
+    received(&mut server);
+    (server, emulator, handle)
+}
+
+fn gain(handle: &EmulatorHandle) -> (payloads::CanDataValue, bool) {
+    handle.with_data(|data| {
+        let param = &data.parameters.as_ref().unwrap()[0];
+        (param.value.clone(), param.locked)
+    })
+}
+
+#[test]
+fn reboot_goes_silent_resets_parameters_and_registers_again() {
+    let mut data = emulator_data_with(
+        Some(vec![telemetry("temp", payloads::CanDataValue::UInt8(20))]),
+        Some(vec![parameter(
+            "gain",
+            payloads::CanDataValue::UInt16(1),
+            false,
+        )]),
+    );
+    data.frequency = 0;
+    let (mut server, mut emulator, handle) = start(data);
+
+    handle
+        .set_parameter_value("gain", payloads::CanDataValue::UInt16(7))
+        .unwrap();
+    handle.with_data(|data| data.parameters.as_mut().unwrap()[0].locked = true);
+    assert!(handle.reboot(Some(-1.0), None).is_err());
+    handle.reboot(Some(0.3), None).unwrap();
+    emulator.run_until(Instant::now() + Duration::from_millis(20));
+    assert_eq!(gain(&handle), (payloads::CanDataValue::UInt16(1), false));
+
+    // Heartbeats sent while the node boots are lost.
+    socket_manager::send_frame(&mut server, make_message_id(1, 1), heartbeat(1)).unwrap();
+    emulator.run_until(Instant::now() + Duration::from_millis(50));
+    assert!(received(&mut server).is_empty());
+
+    emulator.run_until(Instant::now() + Duration::from_millis(300));
+    let messages = received(&mut server);
+    assert!(matches!(
+        messages.first(),
+        Some(CanMessage::NodeInfoAnnouncement { .. })
+    ));
+    socket_manager::send_frame(&mut server, make_message_id(1, 1), heartbeat(2)).unwrap();
+    emulator.run_until(Instant::now() + Duration::from_millis(20));
+    assert!(matches!(
+        receive(&mut server, TIMEOUT),
+        Some(CanMessage::HeartbeatRes { .. })
+    ));
+}
+
+#[test]
+fn heartbeat_counters_start_over_after_a_reboot() {
+    let mut data = emulator_data_with(None, None);
+    data.frequency = 0;
+    let (mut server, mut emulator, handle) = start(data);
+
+    socket_manager::send_frame(&mut server, make_message_id(1, 1), heartbeat(41)).unwrap();
+    emulator.run_until(Instant::now() + Duration::from_millis(20));
+    received(&mut server);
+    handle.reboot(Some(0.0), None).unwrap();
+    emulator.run_until(Instant::now() + Duration::from_millis(20));
+    received(&mut server);
+
+    socket_manager::send_frame(&mut server, make_message_id(1, 1), heartbeat(1)).unwrap();
+    emulator.run_until(Instant::now() + Duration::from_millis(20));
+    let messages = received(&mut server);
+    assert!(
+        matches!(messages.as_slice(), [CanMessage::HeartbeatRes { .. }]),
+        "the first heartbeat after a reboot is no counter reset"
+    );
+    assert_eq!(handle.heartbeat_stats().received, 2);
+    assert_eq!(handle.heartbeat_stats().resets, 0);
+}
+
+#[test]
+fn reboot_into_another_config_announces_the_new_firmware() {
+    let path = temp_path("firmware");
+    let update = temp_path("firmware_update");
+    fs::write(&path, config("0x123", PRESSURE)).unwrap();
+    fs::write(&update, config("0x124", PRESSURE_AND_TEMP)).unwrap();
+    let node = load_nodes(path.to_str().unwrap()).unwrap().remove(0);
+    let (mut server, mut emulator, handle) = start(node);
+
+    let err = handle
+        .reboot(None, Some(&temp_path("missing")))
+        .unwrap_err();
+    assert!(
+        format!("{err:#}").contains("Failed to load reboot config"),
+        "{err:#}"
+    );
+    handle.reboot(Some(0.0), Some(&update)).unwrap();
+    emulator.run_until(Instant::now() + Duration::from_millis(20));
+    let messages = received(&mut server);
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(&update);
+
+    let Some(CanMessage::NodeInfoAnnouncement { payload }) = messages.first() else {
+        panic!("expected the node to announce itself again");
+    };
+    assert_eq!(payload.firmware_hash, 0x124);
+    assert_eq!(payload.tel_count, 2);
+    assert_eq!(
+        handle.telemetry_value("temp"),
+        Some(payloads::CanDataValue::Int16(20))
+    );
+}
+
+#[test]
+fn scenario_reboots_load_their_config_after_the_step() {
+    let path = temp_path("scenario_node");
+    let update = temp_path("scenario_update");
+    fs::write(&path, config("0x123", PRESSURE)).unwrap();
+    fs::write(&update, config("0x124", PRESSURE)).unwrap();
+    let mut node = load_nodes(path.to_str().unwrap()).unwrap().remove(0);
+    let scenario = Scenario::parse(
+        &format!(
+            r#"
+            [[steps]]
+            at = 0.0
+            action = "reboot"
+            boot_time = 0.0
+            config = '{}'
+            "#,
+            update.display()
+        ),
+        std::slice::from_ref(&node),
+    )
+    .unwrap();
+    let mut runner = scenario.runner_for(2);
+
+    // Steps run while the emulator holds the node's data, so the step must not read the file.
+    fs::remove_file(&update).unwrap();
+    runner.advance(0.0, &mut node, &mut FaultInjector::new());
+    let request = runner.take_reboot().expect("the step asks for a reboot");
+    assert_eq!(request.config.as_deref(), Some(update.as_path()));
+
+    fs::write(&update, config("0x124", PRESSURE)).unwrap();
+    let data = Mutex::new(node);
+    let reboot = Reboot::load(&data, request.boot_time, request.config.as_deref());
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(&update);
+
+    assert_eq!(reboot.unwrap().config.unwrap().firmware_hash, 0x124);
+    assert!(data.try_lock().is_ok());
+}
+
+#[test]
+fn persisted_parameters_survive_a_reboot() {
+    let state_file = temp_path("state");
+    let mut data = emulator_data_with(
+        None,
+        Some(vec![parameter(
+            "gain",
+            payloads::CanDataValue::UInt16(1),
+            false,
+        )]),
+    );
+    data.frequency = 0;
+    data.state_file = Some(state_file.clone());
+    let (_server, mut emulator, handle) = start(data);
+
+    handle
+        .set_parameter_value("gain", payloads::CanDataValue::UInt16(7))
+        .unwrap();
+    emulator.run_until(Instant::now() + Duration::from_millis(20));
+    handle.reboot(Some(0.0), None).unwrap();
+    emulator.run_until(Instant::now() + Duration::from_millis(20));
+    let _ = fs::remove_file(&state_file);
+
+    assert_eq!(gain(&handle), (payloads::CanDataValue::UInt16(7), false));
+}
+
+#[test]
+fn scenario_steps_reboot_the_node() {
+    let mut data = emulator_data_with(
+        None,
+        Some(vec![parameter(
+            "gain",
+            payloads::CanDataValue::UInt16(1),
+            false,
+        )]),
+    );
+    data.frequency = 0;
+    let scenario = Scenario::parse(
+        r#"
+        [[steps]]
+        at = 0.1
+        action = "set"
+        field = "gain"
+        value = 9
+
+        [[steps]]
+        at = 0.2
+        action = "reboot"
+        boot_time = 0.3
+        "#,
+        std::slice::from_ref(&data),
+    )
+    .unwrap();
+    let (mut server, mut emulator, handle) = start(data);
+    emulator.set_scenario(scenario.runner_for(1));
+
+    // The step set the parameter before the reboot reset it, and the node is still booting.
+    emulator.run_until(Instant::now() + Duration::from_millis(300));
+    assert_eq!(gain(&handle), (payloads::CanDataValue::UInt16(1), false));
+    assert!(received(&mut server).is_empty());
+
+    emulator.run_until(Instant::now() + Duration::from_millis(300));
+    assert!(matches!(
+        received(&mut server).first(),
+        Some(CanMessage::NodeInfoAnnouncement { .. })
+    ));
+}
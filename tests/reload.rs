@@ -0,0 +1,232 @@
+mod common;
+
+use common::receive;
+use liquidcan::{payloads, CanMessage};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+use ECUEmulator::can_manager::in_memory_bus::{InMemoryBus, InMemoryTransport};
+use ECUEmulator::can_manager::{make_message_id, socket_manager};
+use ECUEmulator::config::config_loader::load_nodes;
+use ECUEmulator::emulator::{Emulator, EmulatorHandle};
+use ECUEmulator::reload::{reload_nodes, reload_or_report, ConfigChange, ConfigWatcher};
+
+const TIMEOUT: Duration = Duration::from_millis(50);
+
+fn config(node_id: u32, telemetry: &str, gain: u32, rate: u32) -> String {
+    format!(
+        r#"node_id = {node_id}
+frequency = {rate}
+firmware_hash = "0x123"
+can_interface = "vcan0"
+liquid_hash = "0x456"
+device_name = "Reloadable"
+
+[TelemetryValues]
+{telemetry}
+
+[Parameters]
+    [Parameters.gain]
+    value = {gain}
+    locked = false
+    datatype = "UInt16"
+"#
+    )
+}
+
+const PRESSURE: &str = r#"    [TelemetryValues.pressure]
+    value = 5
+    datatype = "UInt8""#;
+
+const PRESSURE_AND_TEMP: &str = r#"    [TelemetryValues.pressure]
+    value = 5
+    datatype = "UInt8"
+    [TelemetryValues.temp]
+    value = 20
+    datatype = "Int16""#;
+
+struct Setup {
+    path: PathBuf,
+    server: InMemoryTransport,
+    emulator: Emulator<InMemoryTransport>,
+    handle: EmulatorHandle,
+}
+
+impl Setup {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "ecuemulator_reload_{name}_{}.toml",
+            std::process::id()
+        ));
+        fs::write(&path, config(2, PRESSURE, 1, 0)).unwrap();
+        let node = load_nodes(path.to_str().unwrap()).unwrap().remove(0);
+        let bus = InMemoryBus::new();
+        let mut server = bus.attach();
+        let mut emulator = Emulator::new(node, bus.attach());
+        emulator.run_until(Instant::now() + Duration::from_millis(20));
+        while receive(&mut server, TIMEOUT).is_some() {}
+        let handle = emulator.handle();
+        Self {
+            path,
+            server,
+            emulator,
+            handle,
+        }
+    }
+
+    fn reload(&self, contents: &str) -> anyhow::Result<Vec<ConfigChange>> {
+        fs::write(&self.path, contents).unwrap();
+        reload_nodes(
+            self.path.to_str().unwrap(),
+            std::slice::from_ref(&self.handle),
+        )
+    }
+
+    fn sent_messages(&mut self) -> Vec<CanMessage> {
+        self.emulator
+            .run_until(Instant::now() + Duration::from_millis(20));
+        let mut messages = Vec::new();
+        while let Some(msg) = receive(&mut self.server, TIMEOUT) {
+            messages.push(msg);
+        }
+        messages
+    }
+}
+
+impl Drop for Setup {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[test]
+fn changed_values_are_applied_in_place() {
+    let mut setup = Setup::new("values");
+
+    let changes = setup.reload(&config(2, PRESSURE, 7, 0)).unwrap();
+
+    assert_eq!(changes, vec![ConfigChange::Values]);
+    assert_eq!(
+        setup.handle.parameter_value("gain"),
+        Some(payloads::CanDataValue::UInt16(7))
+    );
+    assert!(setup.sent_messages().is_empty());
+}
+
+#[test]
+fn runtime_values_survive_unrelated_reloads() {
+    let mut setup = Setup::new("runtime");
+    let request = CanMessage::ParameterSetReq {
+        payload: payloads::ParameterSetReqPayload {
+            parameter_id: 1,
+            value: payloads::CanDataValue::UInt16(5),
+        },
+    };
+    socket_manager::send_frame(&mut setup.server, make_message_id(2, 1), request).unwrap();
+    setup.sent_messages();
+    setup
+        .handle
+        .with_data(|data| data.parameters.as_mut().unwrap()[0].locked = true);
+    setup
+        .handle
+        .set_telemetry_value("pressure", payloads::CanDataValue::UInt8(9))
+        .unwrap();
+
+    let changes = setup.reload(&config(2, PRESSURE, 1, 200)).unwrap();
+
+    assert_eq!(changes, vec![ConfigChange::Values]);
+    assert_eq!(
+        setup.handle.parameter_value("gain"),
+        Some(payloads::CanDataValue::UInt16(5))
+    );
+    assert!(setup
+        .handle
+        .with_data(|data| data.parameters.as_ref().unwrap()[0].locked));
+    assert_eq!(
+        setup.handle.telemetry_value("pressure"),
+        Some(payloads::CanDataValue::UInt8(9))
+    );
+
+    // A value the config changes replaces the one set at runtime.
+    setup.reload(&config(2, PRESSURE, 3, 200)).unwrap();
+    assert_eq!(
+        setup.handle.parameter_value("gain"),
+        Some(payloads::CanDataValue::UInt16(3))
+    );
+}
+
+#[test]
+fn changed_rates_reschedule_telemetry() {
+    let mut setup = Setup::new("rates");
+
+    let changes = setup.reload(&config(2, PRESSURE, 1, 200)).unwrap();
+
+    assert_eq!(changes, vec![ConfigChange::Values]);
+    let messages = setup.sent_messages();
+    assert!(messages
+        .iter()
+        .any(|msg| matches!(msg, CanMessage::TelemetryGroupUpdate { .. })));
+    assert!(!messages
+        .iter()
+        .any(|msg| matches!(msg, CanMessage::NodeInfoAnnouncement { .. })));
+}
+
+#[test]
+fn changed_layouts_are_registered_again() {
+    let mut setup = Setup::new("layout");
+
+    let changes = setup.reload(&config(2, PRESSURE_AND_TEMP, 1, 0)).unwrap();
+
+    assert_eq!(changes, vec![ConfigChange::Layout]);
+    let messages = setup.sent_messages();
+    assert!(matches!(
+        messages.first(),
+        Some(CanMessage::NodeInfoAnnouncement { .. })
+    ));
+    let registrations = messages
+        .iter()
+        .filter(|msg| matches!(msg, CanMessage::TelemetryValueRegistration { .. }))
+        .count();
+    assert_eq!(registrations, 2);
+    assert_eq!(
+        setup.handle.telemetry_value("temp"),
+        Some(payloads::CanDataValue::Int16(20))
+    );
+}
+
+#[test]
+fn failed_reloads_keep_the_old_config_and_report_an_error() {
+    let mut setup = Setup::new("failed");
+
+    for contents in ["node_id = ", config(3, PRESSURE, 9, 0).as_str()] {
+        fs::write(&setup.path, contents).unwrap();
+        reload_or_report(
+            setup.path.to_str().unwrap(),
+            std::slice::from_ref(&setup.handle),
+        );
+
+        assert_eq!(
+            setup.handle.parameter_value("gain"),
+            Some(payloads::CanDataValue::UInt16(1))
+        );
+        let messages = setup.sent_messages();
+        assert!(
+            matches!(messages.as_slice(), [CanMessage::ErrorStatus { .. }]),
+            "expected a single ErrorStatus for {contents:?}"
+        );
+    }
+}
+
+#[test]
+fn watcher_notices_modifications() {
+    let setup = Setup::new("watcher");
+    let mut watcher = ConfigWatcher::new(&setup.path).unwrap();
+    assert!(!watcher.poll());
+
+    let file = fs::File::options().write(true).open(&setup.path).unwrap();
+    file.set_modified(SystemTime::now() + Duration::from_secs(5))
+        .unwrap();
+
+    assert!(watcher.poll());
+    assert!(!watcher.poll());
+}
@@ -0,0 +1,275 @@
+mod common;
+
+use common::{emulator_data_with, parameter, receive, telemetry, telemetry_value};
+use liquidcan::{payloads, CanMessage};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use ECUEmulator::can_manager::in_memory_bus::InMemoryBus;
+use ECUEmulator::can_manager::{make_message_id, socket_manager};
+use ECUEmulator::config::config_loader::load_nodes;
+use ECUEmulator::config::config_representation::EmulatorData;
+use ECUEmulator::emulator::Emulator;
+use ECUEmulator::faults::FaultInjector;
+use ECUEmulator::message_handling::{handle_message, Event};
+use ECUEmulator::scenario::Scenario;
+use ECUEmulator::simulation::actuator::{ActuatorModel, Dynamics};
+use ECUEmulator::simulation::generator::Generator;
+
+const TIMEOUT: Duration = Duration::from_millis(200);
+
+fn valve_node() -> EmulatorData {
+    let mut pressure = telemetry("chamber_pressure", payloads::CanDataValue::Float32(1.0));
+    pressure.generator = Some(Generator::Ramp {
+        from: 0.0,
+        to: 1.0,
+        period: 1.0,
+    });
+    emulator_data_with(
+        Some(vec![
+            pressure,
+            telemetry("valve_position", payloads::CanDataValue::UInt8(0)),
+        ]),
+        Some(vec![
+            parameter("valve_target", payloads::CanDataValue::UInt8(0), false),
+            parameter(
+                "igniter_enabled",
+                payloads::CanDataValue::Boolean(false),
+                false,
+            ),
+        ]),
+    )
+}
+
+fn parameter_state(data: &EmulatorData, name: &str) -> (payloads::CanDataValue, bool) {
+    data.parameters
+        .iter()
+        .flatten()
+        .find(|param| param.name == name)
+        .map(|param| (param.value.clone(), param.locked))
+        .unwrap()
+}
+
+#[test]
+fn timed_steps_set_and_ramp_fields() {
+    let mut data = valve_node();
+    let scenario = Scenario::parse(
+        r#"
+        [[steps]]
+        at = 1
+        action = "ramp"
+        field = "chamber_pressure"
+        from = 0
+        to = 40
+        duration = 4
+
+        [[steps]]
+        at = 2
+        action = "set"
+        field = "igniter_enabled"
+        value = true
+        "#,
+        std::slice::from_ref(&data),
+    )
+    .unwrap();
+    let mut runner = scenario.runner_for(1);
+    let mut faults = FaultInjector::new();
+
+    runner.advance(0.5, &mut data, &mut faults);
+    assert_eq!(
+        parameter_state(&data, "igniter_enabled").0,
+        payloads::CanDataValue::Boolean(false)
+    );
+
+    runner.advance(3.0, &mut data, &mut faults);
+    assert_eq!(
+        telemetry_value(&data, "chamber_pressure"),
+        payloads::CanDataValue::Float32(20.0)
+    );
+    assert_eq!(
+        parameter_state(&data, "igniter_enabled").0,
+        payloads::CanDataValue::Boolean(true)
+    );
+
+    runner.advance(10.0, &mut data, &mut faults);
+    assert_eq!(
+        telemetry_value(&data, "chamber_pressure"),
+        payloads::CanDataValue::Float32(40.0)
+    );
+    // The ramp replaced the generator, so the value holds at the end of the ramp.
+    assert!(data.telemetry_values.as_ref().unwrap()[0]
+        .generator
+        .is_none());
+    assert_eq!(runner.time_until_next(10.0), None);
+}
+
+#[test]
+fn parameter_set_over_can_triggers_steps_after_their_delay() {
+    let mut data = valve_node();
+    let scenario = Scenario::parse(
+        r#"
+        [[steps]]
+        on = { parameter_set = "valve_target" }
+        delay = 0.5
+        action = "lock"
+        parameter = "valve_target"
+
+        [[steps]]
+        on = { parameter_set = "valve_target" }
+        action = "status"
+        level = "warning"
+        message = "Valve moving"
+        "#,
+        std::slice::from_ref(&data),
+    )
+    .unwrap();
+    let mut runner = scenario.runner_for(1);
+    let mut faults = FaultInjector::new();
+
+    let request = CanMessage::ParameterSetReq {
+        payload: payloads::ParameterSetReqPayload {
+            parameter_id: 2,
+            value: payloads::CanDataValue::UInt8(80),
+        },
+    };
+    let responses = handle_message(&request, &mut data);
+    let event = Event::of_exchange(&request, &responses, &data).unwrap();
+    assert_eq!(event, Event::ParameterSet("valve_target".to_string()));
+    runner.notify(&event, 2.0);
+
+    let messages = runner.advance(2.0, &mut data, &mut faults);
+    let [CanMessage::WarningStatus { payload }] = messages.as_slice() else {
+        panic!("expected one warning status");
+    };
+    assert_eq!(String::from(payload.msg.clone()), "Valve moving");
+    assert!(!parameter_state(&data, "valve_target").1);
+
+    runner.advance(2.5, &mut data, &mut faults);
+    assert_eq!(
+        parameter_state(&data, "valve_target"),
+        (payloads::CanDataValue::UInt8(80), true)
+    );
+
+    // A rejected write is not an event.
+    let responses = handle_message(&request, &mut data);
+    assert_eq!(Event::of_exchange(&request, &responses, &data), None);
+}
+
+#[test]
+fn reregister_sends_the_registration_flow() {
+    let mut data = valve_node();
+    let scenario = Scenario::parse(
+        "[[steps]]\nat = 0\naction = \"reregister\"\n",
+        std::slice::from_ref(&data),
+    )
+    .unwrap();
+    let mut runner = scenario.runner_for(1);
+
+    let messages = runner.advance(0.0, &mut data, &mut FaultInjector::new());
+    assert!(matches!(
+        messages.first(),
+        Some(CanMessage::NodeInfoAnnouncement { .. })
+    ));
+    assert!(runner
+        .advance(1.0, &mut data, &mut FaultInjector::new())
+        .is_empty());
+}
+
+#[test]
+fn invalid_steps_are_rejected() {
+    let mut data = valve_node();
+    data.models = Some(vec![ActuatorModel::new(
+        "valve_target",
+        &["valve_position"],
+        Dynamics::Copy,
+    )]);
+    let mut other = valve_node();
+    other.node_id = 2;
+
+    for (steps, reason) in [
+        (
+            "at = 1\naction = \"set\"\nfield = \"missing\"\nvalue = 1",
+            "unknown field missing",
+        ),
+        (
+            "at = 1\naction = \"set\"\nfield = \"valve_target\"\nvalue = 300",
+            "does not fit in UInt8",
+        ),
+        (
+            "at = 1\naction = \"ramp\"\nfield = \"valve_position\"\nto = 5\nduration = 1",
+            "driven by model valve_target",
+        ),
+        ("action = \"reregister\"", "needs either `at` or `on`"),
+        (
+            "at = 1\ndelay = 1\naction = \"reregister\"",
+            "`delay` only applies",
+        ),
+        (
+            "on = { parameter_set = \"missing\" }\naction = \"reregister\"",
+            "unknown parameter missing",
+        ),
+        (
+            "at = 1\naction = \"status\"\nlevel = \"info\"\nmessage = \"\
+             This status message is much too long to fit into a single CAN FD frame\"",
+            "longer than 63 bytes",
+        ),
+        (
+            "at = 1\nnode = 9\naction = \"reregister\"",
+            "no node with id 9",
+        ),
+        (
+            "at = 1\naction = \"reboot\"\nboot_time = -1",
+            "invalid boot time -1",
+        ),
+        (
+            "at = 1\naction = \"reboot\"\nconfig = \"data/missing.toml\"",
+            "Failed to load reboot config data/missing.toml",
+        ),
+    ] {
+        let err = Scenario::parse(&format!("[[steps]]\n{steps}"), std::slice::from_ref(&data))
+            .expect_err(steps);
+        assert!(format!("{err:#}").contains(reason), "{steps}: {err:#}");
+    }
+
+    let err = Scenario::parse("[[steps]]\nat = 1\naction = \"reregister\"", &[data, other])
+        .err()
+        .unwrap();
+    assert!(format!("{err:#}").contains("needs a `node`"), "{err:#}");
+}
+
+#[test]
+fn silence_stops_the_emulator_answering_heartbeats() {
+    let bus = InMemoryBus::new();
+    let mut server = bus.attach();
+    let mut data = emulator_data_with(None, None);
+    data.frequency = 0;
+    let scenario = Scenario::parse(
+        "[[steps]]\nat = 0\naction = \"silence\"\nkinds = [\"heartbeat_res\"]\nduration = 0.3\n",
+        std::slice::from_ref(&data),
+    )
+    .unwrap();
+    let mut emulator = Emulator::new(data, bus.attach());
+    emulator.set_scenario(scenario.runner_for(1));
+    emulator.run_until(Instant::now() + Duration::from_millis(20));
+    while receive(&mut server, TIMEOUT).is_some() {}
+
+    let request = CanMessage::HeartbeatReq {
+        payload: payloads::HeartbeatPayload { counter: 1 },
+    };
+    socket_manager::send_frame(&mut server, make_message_id(1, 1), request.clone()).unwrap();
+    emulator.run_until(Instant::now() + Duration::from_millis(20));
+    assert!(receive(&mut server, TIMEOUT).is_none());
+
+    emulator.run_until(Instant::now() + Duration::from_millis(300));
+    socket_manager::send_frame(&mut server, make_message_id(1, 1), request).unwrap();
+    emulator.run_until(Instant::now() + Duration::from_millis(20));
+    assert!(matches!(
+        receive(&mut server, TIMEOUT),
+        Some(CanMessage::HeartbeatRes { .. })
+    ));
+}
+
+#[test]
+fn sample_scenario_fits_the_sample_config() {
+    let nodes = load_nodes("data/sample_config.toml").unwrap();
+    Scenario::load(Path::new("data/sample_scenario.toml"), &nodes).unwrap();
+}
@@ -0,0 +1,155 @@
+mod common;
+
+use common::{emulator_data_with, parameter, telemetry};
+use liquidcan::payloads;
+use std::thread;
+use std::time::{Duration, Instant};
+use ECUEmulator::can_manager::in_memory_bus::InMemoryBus;
+use ECUEmulator::config::config_representation::EmulatorData;
+use ECUEmulator::emulator::Emulator;
+use ECUEmulator::faults::{Fault, FaultKind};
+use ECUEmulator::message_kind::MessageKind;
+use ECUEmulator::server::{check_node, ConformanceReport, Outcome, ServerOptions};
+
+fn options(node_id: u8) -> ServerOptions {
+    ServerOptions {
+        server_id: 1,
+        node_id,
+        timeout: Duration::from_millis(100),
+        heartbeats: 3,
+    }
+}
+
+fn node() -> EmulatorData {
+    let mut read_only = parameter("serial", payloads::CanDataValue::UInt32(1234), false);
+    read_only.read_only = true;
+    let mut data = emulator_data_with(
+        Some(vec![
+            telemetry("chamber_pressure", payloads::CanDataValue::Float32(1.5)),
+            telemetry("valve_position", payloads::CanDataValue::UInt8(0)),
+        ]),
+        Some(vec![
+            parameter("gain", payloads::CanDataValue::UInt16(7), false),
+            parameter(
+                "igniter_enabled",
+                payloads::CanDataValue::Boolean(false),
+                true,
+            ),
+            read_only,
+        ]),
+    );
+    data.node_id = 3;
+    data
+}
+
+/// Runs the conformance check against an emulated `data` node on an in-memory bus.
+/// Also returns every parameter's value and lock state afterwards.
+fn check_emulated(data: EmulatorData) -> (ConformanceReport, Vec<(payloads::CanDataValue, bool)>) {
+    let bus = InMemoryBus::new();
+    let mut server = bus.attach();
+    let node_id = data.node_id as u8;
+    let mut emulator = Emulator::new(data, bus.attach());
+    let handle = emulator.handle();
+    let runner =
+        thread::spawn(move || emulator.run_until(Instant::now() + Duration::from_secs(10)));
+
+    let report = check_node(&mut server, &options(node_id));
+    let parameters = handle.with_data(|data| {
+        data.parameters
+            .iter()
+            .flatten()
+            .map(|param| (param.value.clone(), param.locked))
+            .collect()
+    });
+    handle.shutdown();
+    runner.join().unwrap();
+    (report, parameters)
+}
+
+fn outcome(report: &ConformanceReport, name: &str) -> Outcome {
+    report
+        .check(name)
+        .unwrap_or_else(|| panic!("no check {name}:\n{}", report.to_text()))
+        .outcome
+}
+
+#[test]
+fn emulated_node_passes_and_keeps_its_state() {
+    let (report, parameters) = check_emulated(node());
+    assert!(report.passed(), "{}", report.to_text());
+
+    let dictionary = &report.dictionary;
+    assert_eq!(dictionary.device_name, "ECUEmulatorTest");
+    let names: Vec<&str> = dictionary
+        .parameters
+        .iter()
+        .map(|field| field.name.as_str())
+        .collect();
+    assert_eq!(names, ["gain", "igniter_enabled", "serial"]);
+    assert_eq!(
+        dictionary.telemetry[0].value,
+        Some(payloads::CanDataValue::Float32(1.5))
+    );
+    assert_eq!(dictionary.groups.len(), 1);
+
+    for name in [
+        "registration counts",
+        "get valve_position",
+        "lookup gain",
+        "get unknown field",
+        "heartbeat",
+        "set gain",
+        "set serial",
+        "lock gain",
+        "set gain while locked",
+        "unlock gain",
+        "set gain after unlock",
+        "lock unknown parameter",
+        "telemetry group updates",
+    ] {
+        assert_eq!(outcome(&report, name), Outcome::Passed, "{name}");
+    }
+    assert_eq!(
+        report.check("set igniter_enabled").unwrap().detail,
+        "rejected as locked or read-only"
+    );
+
+    // Only current values were written, and every lock was released again.
+    assert_eq!(
+        parameters[..2],
+        [
+            (payloads::CanDataValue::UInt16(7), false),
+            (payloads::CanDataValue::Boolean(false), true),
+        ]
+    );
+}
+
+#[test]
+fn dropped_heartbeats_fail_the_report() {
+    let mut fault = Fault::new("deaf", FaultKind::Drop);
+    fault.messages = vec![MessageKind::HeartbeatRes];
+    let mut data = node();
+    data.frequency = 0;
+    data.faults = Some(vec![fault]);
+
+    let (report, _) = check_emulated(data);
+    assert!(!report.passed());
+    assert_eq!(outcome(&report, "heartbeat"), Outcome::Failed);
+    assert_eq!(outcome(&report, "set gain"), Outcome::Passed);
+    assert_eq!(
+        outcome(&report, "telemetry group updates"),
+        Outcome::Skipped
+    );
+    assert!(report.to_text().ends_with("1 failed, 1 skipped: FAIL"));
+}
+
+#[test]
+fn silent_node_fails_the_registration() {
+    let bus = InMemoryBus::new();
+    let mut server = bus.attach();
+    let report = check_node(&mut server, &options(3));
+
+    assert!(!report.passed());
+    assert_eq!(outcome(&report, "node info announcement"), Outcome::Failed);
+    assert!(report.check("heartbeat").is_none());
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use common::{emulator_data_with, parameter, telemetry, telemetry_value};
+use liquidcan::{payloads, CanMessage};
+use std::time::Duration;
+use ECUEmulator::message_handling::handle_message;
+use ECUEmulator::simulation::actuator::{ActuatorModel, Dynamics};
+use ECUEmulator::simulation::update_telemetry;
+
+#[test]
+fn telemetry_follows_a_parameter_set_over_can() {
+    let mut data = emulator_data_with(
+        Some(vec![
+            telemetry("valve_position", payloads::CanDataValue::UInt8(0)),
+            telemetry("valve_position_raw", payloads::CanDataValue::Float32(0.0)),
+        ]),
+        Some(vec![parameter(
+            "valve_target",
+            payloads::CanDataValue::UInt8(0),
+            false,
+        )]),
+    );
+    data.models = Some(vec![ActuatorModel::new(
+        "valve_target",
+        &["valve_position", "valve_position_raw"],
+        Dynamics::RateLimit { rate: 50.0 },
+    )]);
+    update_telemetry(&mut data, &[], Duration::ZERO);
+
+    let request = CanMessage::ParameterSetReq {
+        payload: payloads::ParameterSetReqPayload {
+            parameter_id: 1,
+            value: payloads::CanDataValue::UInt8(100),
+        },
+    };
+    handle_message(&request, &mut data);
+    update_telemetry(&mut data, &[], Duration::from_secs(1));
+    update_telemetry(&mut data, &[], Duration::from_millis(1500));
+
+    assert_eq!(
+        telemetry_value(&data, "valve_position"),
+        payloads::CanDataValue::UInt8(25)
+    );
+    assert_eq!(
+        telemetry_value(&data, "valve_position_raw"),
+        payloads::CanDataValue::Float32(25.0)
+    );
+
+    update_telemetry(&mut data, &[], Duration::from_secs(5));
+    assert_eq!(
+        telemetry_value(&data, "valve_position"),
+        payloads::CanDataValue::UInt8(100)
+    );
+}
+
+#[test]
+fn rejected_writes_leave_the_model_at_rest() {
+    let mut target = parameter("valve_target", payloads::CanDataValue::UInt8(0), false);
+    target.locked = true;
+    let mut data = emulator_data_with(
+        Some(vec![telemetry(
+            "valve_position",
+            payloads::CanDataValue::UInt8(0),
+        )]),
+        Some(vec![target]),
+    );
+    data.models = Some(vec![ActuatorModel::new(
+        "valve_target",
+        &["valve_position"],
+        Dynamics::Copy,
+    )]);
+    update_telemetry(&mut data, &[], Duration::ZERO);
+
+    let request = CanMessage::ParameterSetReq {
+        payload: payloads::ParameterSetReqPayload {
+            parameter_id: 1,
+            value: payloads::CanDataValue::UInt8(100),
+        },
+    };
+    handle_message(&request, &mut data);
+    update_telemetry(&mut data, &[], Duration::from_secs(1));
+
+    assert_eq!(
+        telemetry_value(&data, "valve_position"),
+        payloads::CanDataValue::UInt8(0)
+    );
+}
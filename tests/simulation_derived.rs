@@ -0,0 +1,111 @@
+mod common;
+
+use common::{emulator_data_with, parameter, telemetry, telemetry_value};
+use liquidcan::payloads;
+use std::time::{Duration, Instant};
+use ECUEmulator::can_manager::in_memory_bus::InMemoryBus;
+use ECUEmulator::config::config_representation::TelemetryValue;
+use ECUEmulator::emulator::Emulator;
+use ECUEmulator::simulation::derived::evaluation_order;
+use ECUEmulator::simulation::expression::Expression;
+use ECUEmulator::simulation::update_telemetry;
+
+fn derived(name: &str, value: payloads::CanDataValue, expr: &str) -> TelemetryValue {
+    let mut tel = telemetry(name, value);
+    tel.expr = Some(Expression::parse(expr).unwrap());
+    tel
+}
+
+#[test]
+fn expressions_read_telemetry_parameters_and_time() {
+    let mut data = emulator_data_with(
+        Some(vec![
+            // Listed before the value it depends on.
+            derived(
+                "delta_scaled",
+                payloads::CanDataValue::Int16(0),
+                "delta * gain + t",
+            ),
+            derived(
+                "delta",
+                payloads::CanDataValue::Float32(0.0),
+                "inlet - outlet",
+            ),
+            telemetry("inlet", payloads::CanDataValue::Float32(12.5)),
+            telemetry("outlet", payloads::CanDataValue::Float32(2.0)),
+        ]),
+        Some(vec![parameter(
+            "gain",
+            payloads::CanDataValue::UInt8(3),
+            false,
+        )]),
+    );
+
+    let order = evaluation_order(data.telemetry_values.as_deref().unwrap()).unwrap();
+    update_telemetry(&mut data, &order, Duration::from_secs(2));
+
+    assert_eq!(
+        telemetry_value(&data, "delta"),
+        payloads::CanDataValue::Float32(10.5)
+    );
+    // 10.5 * 3 + 2 = 33.5, rounded into the Int16 field.
+    assert_eq!(
+        telemetry_value(&data, "delta_scaled"),
+        payloads::CanDataValue::Int16(34)
+    );
+}
+
+#[test]
+fn results_saturate_at_the_datatype_bounds() {
+    let mut data = emulator_data_with(
+        Some(vec![
+            derived("small", payloads::CanDataValue::UInt8(0), "-5"),
+            derived("large", payloads::CanDataValue::UInt8(0), "1000"),
+        ]),
+        None,
+    );
+
+    let order = evaluation_order(data.telemetry_values.as_deref().unwrap()).unwrap();
+    update_telemetry(&mut data, &order, Duration::ZERO);
+
+    assert_eq!(
+        telemetry_value(&data, "small"),
+        payloads::CanDataValue::UInt8(0)
+    );
+    assert_eq!(
+        telemetry_value(&data, "large"),
+        payloads::CanDataValue::UInt8(255)
+    );
+}
+
+#[test]
+fn cycles_are_reported() {
+    let telemetry = vec![
+        derived("a", payloads::CanDataValue::Float32(0.0), "b + 1"),
+        derived("b", payloads::CanDataValue::Float32(0.0), "c * 2"),
+        derived("c", payloads::CanDataValue::Float32(0.0), "a"),
+    ];
+    let err = evaluation_order(&telemetry).unwrap_err();
+    assert!(err.contains("a -> b -> c -> a"), "{err}");
+}
+
+#[test]
+fn emulators_evaluate_expressions_of_data_built_in_code() {
+    let data = emulator_data_with(
+        Some(vec![
+            derived("doubled", payloads::CanDataValue::Float32(0.0), "inlet * 2"),
+            telemetry("inlet", payloads::CanDataValue::Float32(4.0)),
+        ]),
+        None,
+    );
+    let bus = InMemoryBus::new();
+    let mut emulator = Emulator::new(data, bus.attach());
+    let handle = emulator.handle();
+
+    emulator.run_until(Instant::now() + Duration::from_millis(50));
+
+    assert_eq!(
+        handle.telemetry_value("doubled"),
+        Some(payloads::CanDataValue::Float32(8.0))
+    );
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use common::{emulator_data_with, telemetry};
+use liquidcan::{payloads, CanMessage};
+use std::time::Duration;
+use ECUEmulator::message_handling::build_telemetry_group_updates;
+use ECUEmulator::simulation::generator::Generator;
+use ECUEmulator::simulation::update_telemetry;
+
+fn unpack_single(
+    updates: &[CanMessage],
+    datatype: payloads::CanDataType,
+) -> payloads::CanDataValue {
+    let CanMessage::TelemetryGroupUpdate { payload } = &updates[0] else {
+        panic!("Expected TelemetryGroupUpdate");
+    };
+    payload
+        .values
+        .unpack([datatype].into_iter())
+        .next()
+        .expect("one value")
+        .expect("unpack should succeed")
+}
+
+#[test]
+fn generator_changes_value_between_ticks() {
+    let mut tel = telemetry("ramp", payloads::CanDataValue::Float32(0.0));
+    tel.generator = Some(Generator::Ramp {
+        from: 0.0,
+        to: 10.0,
+        period: 10.0,
+    });
+    let mut data = emulator_data_with(Some(vec![tel]), None);
+
+    update_telemetry(&mut data, &[], Duration::from_secs(1));
+    let first = unpack_single(
+        &build_telemetry_group_updates(&data),
+        payloads::CanDataType::Float32,
+    );
+    update_telemetry(&mut data, &[], Duration::from_secs(4));
+    let second = unpack_single(
+        &build_telemetry_group_updates(&data),
+        payloads::CanDataType::Float32,
+    );
+
+    assert_eq!(first, payloads::CanDataValue::Float32(1.0));
+    assert_eq!(second, payloads::CanDataValue::Float32(4.0));
+}
+
+#[test]
+fn generator_saturates_integer_fields() {
+    let mut tel = telemetry("sq", payloads::CanDataValue::Int8(0));
+    tel.generator = Some(Generator::Square {
+        low: -1000.0,
+        high: 1000.0,
+        period: 2.0,
+        duty: 0.5,
+    });
+    let mut data = emulator_data_with(Some(vec![tel]), None);
+
+    update_telemetry(&mut data, &[], Duration::from_millis(500));
+    let high = unpack_single(
+        &build_telemetry_group_updates(&data),
+        payloads::CanDataType::Int8,
+    );
+    update_telemetry(&mut data, &[], Duration::from_millis(1500));
+    let low = unpack_single(
+        &build_telemetry_group_updates(&data),
+        payloads::CanDataType::Int8,
+    );
+
+    assert_eq!(high, payloads::CanDataValue::Int8(i8::MAX));
+    assert_eq!(low, payloads::CanDataValue::Int8(i8::MIN));
+}
+
+#[test]
+fn fields_without_generator_keep_their_value() {
+    let telemetry_values = vec![telemetry("fixed", payloads::CanDataValue::UInt8(7))];
+    let mut data = emulator_data_with(Some(telemetry_values), None);
+
+    update_telemetry(&mut data, &[], Duration::from_secs(3));
+
+    assert_eq!(
+        data.telemetry_values.as_ref().unwrap()[0].value,
+        payloads::CanDataValue::UInt8(7)
+    );
+}
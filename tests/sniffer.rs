@@ -0,0 +1,123 @@
+mod common;
+
+use common::{emulator_data_with, parameter, telemetry};
+use liquidcan::{payloads, CanMessage};
+use std::time::Duration;
+use ECUEmulator::can_manager::in_memory_bus::{InMemoryBus, InMemoryTransport};
+use ECUEmulator::can_manager::{make_message_id, send_messages, socket_manager};
+use ECUEmulator::message_handling::{
+    build_telemetry_group_updates, parse_can_message, registration_flow_messages,
+};
+use ECUEmulator::message_kind::MessageKind;
+use ECUEmulator::sniffer::{DecodedFrame, SniffFilter, Sniffer};
+
+fn sniff_all(sniffer: &mut Sniffer, listener: &mut InMemoryTransport) -> Vec<DecodedFrame> {
+    let mut decoded = Vec::new();
+    while let Ok(frame) = socket_manager::read_frame(listener, Duration::from_millis(50)) {
+        let (id, msg) = parse_can_message(frame).unwrap();
+        decoded.push(sniffer.decode(id, &msg));
+    }
+    decoded
+}
+
+#[test]
+fn group_updates_are_resolved_from_registrations() {
+    let bus = InMemoryBus::new();
+    let mut node = bus.attach();
+    let mut listener = bus.attach();
+    let mut data = emulator_data_with(
+        Some(vec![
+            telemetry("pressure", payloads::CanDataValue::UInt16(1234)),
+            telemetry("armed", payloads::CanDataValue::Boolean(true)),
+        ]),
+        Some(vec![parameter(
+            "gain",
+            payloads::CanDataValue::UInt8(3),
+            false,
+        )]),
+    );
+    data.node_id = 5;
+
+    let mut sniffer = Sniffer::new();
+    let updates = build_telemetry_group_updates(&data);
+
+    // Without the registration flow the values cannot be resolved.
+    send_messages(&mut node, 5, vec![(1, updates[0].clone())]);
+    let unresolved = sniff_all(&mut sniffer, &mut listener);
+    assert_eq!(unresolved[0].fields[1].0, "resolved");
+
+    let registration = registration_flow_messages(&data);
+    send_messages(
+        &mut node,
+        5,
+        registration.into_iter().map(|msg| (1, msg)).collect(),
+    );
+    send_messages(&mut node, 5, vec![(1, updates[0].clone())]);
+    let decoded = sniff_all(&mut sniffer, &mut listener);
+
+    let update = decoded.last().unwrap();
+    assert_eq!(update.kind, MessageKind::TelemetryGroupUpdate);
+    assert_eq!((update.sender_id, update.receiver_id), (5, 1));
+    assert!(
+        update.to_text().contains("armed=true"),
+        "{}",
+        update.to_text()
+    );
+    assert!(
+        update.to_text().contains("pressure=1234"),
+        "{}",
+        update.to_text()
+    );
+
+    let json: serde_json::Value = serde_json::from_str(&update.to_json()).unwrap();
+    assert_eq!(json["kind"], "telemetry_group_update");
+    assert_eq!(json["sender"], 5);
+    assert_eq!(json["fields"]["pressure"], 1234);
+
+    let announcement = &decoded[0];
+    assert_eq!(announcement.kind, MessageKind::NodeInfoAnnouncement);
+    assert!(announcement
+        .fields
+        .contains(&("device_name".to_string(), "ECUEmulatorTest".into())));
+
+    // Requests to the node resolve parameter names and types, too.
+    let request = CanMessage::ParameterSetReq {
+        payload: payloads::ParameterSetReqPayload {
+            parameter_id: 1,
+            value: payloads::CanDataValue::UInt8(9),
+        },
+    };
+    let mut server = bus.attach();
+    socket_manager::send_frame(&mut server, make_message_id(5, 1), request).unwrap();
+    let decoded = sniff_all(&mut sniffer, &mut listener);
+    let text = decoded[0].to_text();
+    assert!(text.contains("name=\"gain\""), "{text}");
+    assert!(text.contains("value=9"), "{text}");
+}
+
+#[test]
+fn filter_matches_node_and_kind() {
+    let frame = DecodedFrame {
+        priority: "low",
+        sender_id: 5,
+        receiver_id: 1,
+        kind: MessageKind::HeartbeatRes,
+        fields: Vec::new(),
+    };
+    assert!(SniffFilter::default().matches(&frame));
+    assert!(SniffFilter {
+        node_id: Some(1),
+        kinds: Vec::new(),
+    }
+    .matches(&frame));
+    assert!(!SniffFilter {
+        node_id: Some(7),
+        kinds: Vec::new(),
+    }
+    .matches(&frame));
+    assert!(!SniffFilter {
+        node_id: None,
+        kinds: vec![MessageKind::HeartbeatReq],
+    }
+    .matches(&frame));
+}
@@ -0,0 +1,206 @@
+mod common;
+
+use common::{emulator_data_with, parameter, receive};
+use liquidcan::{payloads, CanMessage};
+use std::time::{Duration, Instant};
+use ECUEmulator::can_manager::in_memory_bus::InMemoryBus;
+use ECUEmulator::can_manager::{make_message_id, socket_manager};
+use ECUEmulator::config::config_representation::EmulatorData;
+use ECUEmulator::emulator::Emulator;
+use ECUEmulator::watchdog::{HeartbeatMonitor, Watchdog};
+
+const TIMEOUT: Duration = Duration::from_millis(200);
+
+fn valve_node(watchdog: &str) -> EmulatorData {
+    let mut data = emulator_data_with(
+        None,
+        Some(vec![
+            parameter("valve_target", payloads::CanDataValue::UInt8(50), false),
+            parameter(
+                "igniter_enabled",
+                payloads::CanDataValue::Boolean(true),
+                true,
+            ),
+        ]),
+    );
+    data.watchdog = Some(toml::from_str::<Watchdog>(watchdog).unwrap());
+    data
+}
+
+fn parameters(data: &EmulatorData) -> Vec<(payloads::CanDataValue, bool)> {
+    data.parameters
+        .iter()
+        .flatten()
+        .map(|param| (param.value.clone(), param.locked))
+        .collect()
+}
+
+fn status_text(msg: &CanMessage) -> String {
+    match msg {
+        CanMessage::InfoStatus { payload }
+        | CanMessage::WarningStatus { payload }
+        | CanMessage::ErrorStatus { payload } => String::from(payload.msg.clone()),
+        _ => panic!("expected a status message"),
+    }
+}
+
+#[test]
+fn missed_heartbeats_enter_the_safe_state_until_they_return() {
+    let mut data = valve_node(
+        r#"
+        timeout = 1.0
+        lock = true
+        reregister = true
+        safe_values = { valve_target = 0, igniter_enabled = false }
+        "#,
+    );
+    let mut monitor = HeartbeatMonitor::new();
+
+    // Not armed before the first heartbeat.
+    assert!(monitor.check(5.0, &mut data).is_empty());
+    assert_eq!(monitor.time_until_next(5.0, &data), None);
+
+    assert!(monitor.heartbeat(10.0, &mut data).is_empty());
+    assert_eq!(monitor.time_until_next(10.5, &data), Some(0.5));
+    assert!(monitor.check(10.9, &mut data).is_empty());
+
+    let messages = monitor.check(11.0, &mut data);
+    let [CanMessage::ErrorStatus { .. }] = messages.as_slice() else {
+        panic!("expected one error status");
+    };
+    assert_eq!(
+        status_text(&messages[0]),
+        "Server heartbeat lost, entering safe state"
+    );
+    assert!(monitor.is_tripped());
+    assert_eq!(
+        parameters(&data),
+        vec![
+            (payloads::CanDataValue::UInt8(0), true),
+            (payloads::CanDataValue::Boolean(false), true),
+        ]
+    );
+    assert!(monitor.check(20.0, &mut data).is_empty());
+    assert_eq!(monitor.time_until_next(20.0, &data), None);
+
+    let messages = monitor.heartbeat(20.5, &mut data);
+    assert_eq!(status_text(&messages[0]), "Server heartbeat restored");
+    assert!(matches!(
+        messages[1],
+        CanMessage::NodeInfoAnnouncement { .. }
+    ));
+    assert!(!monitor.is_tripped());
+    // The safe values stay; only the locks the watchdog added are released.
+    assert_eq!(
+        parameters(&data),
+        vec![
+            (payloads::CanDataValue::UInt8(0), false),
+            (payloads::CanDataValue::Boolean(false), true),
+        ]
+    );
+}
+
+#[test]
+fn emulator_reports_a_lost_server_and_its_return() {
+    let bus = InMemoryBus::new();
+    let mut server = bus.attach();
+    let mut data = valve_node(
+        r#"
+        timeout = 0.1
+        level = "warning"
+        message = "Lost LLServer"
+        safe_values = { valve_target = 0 }
+        "#,
+    );
+    data.frequency = 0;
+    let mut emulator = Emulator::new(data, bus.attach());
+    let handle = emulator.handle();
+    emulator.run_until(Instant::now() + Duration::from_millis(20));
+    while receive(&mut server, TIMEOUT).is_some() {}
+
+    let heartbeat = |counter| CanMessage::HeartbeatReq {
+        payload: payloads::HeartbeatPayload { counter },
+    };
+    socket_manager::send_frame(&mut server, make_message_id(1, 1), heartbeat(1)).unwrap();
+    emulator.run_until(Instant::now() + Duration::from_millis(50));
+    assert!(matches!(
+        receive(&mut server, TIMEOUT),
+        Some(CanMessage::HeartbeatRes { .. })
+    ));
+    assert_eq!(
+        handle.parameter_value("valve_target"),
+        Some(payloads::CanDataValue::UInt8(50))
+    );
+
+    emulator.run_until(Instant::now() + Duration::from_millis(100));
+    let warning = receive(&mut server, TIMEOUT).unwrap();
+    assert!(matches!(warning, CanMessage::WarningStatus { .. }));
+    assert_eq!(status_text(&warning), "Lost LLServer");
+    assert_eq!(
+        handle.parameter_value("valve_target"),
+        Some(payloads::CanDataValue::UInt8(0))
+    );
+
+    socket_manager::send_frame(&mut server, make_message_id(1, 1), heartbeat(2)).unwrap();
+    emulator.run_until(Instant::now() + Duration::from_millis(20));
+    assert!(matches!(
+        receive(&mut server, TIMEOUT),
+        Some(CanMessage::HeartbeatRes { .. })
+    ));
+    assert!(matches!(
+        receive(&mut server, TIMEOUT),
+        Some(CanMessage::InfoStatus { .. })
+    ));
+}
+
+#[test]
+fn only_server_heartbeats_end_the_safe_state() {
+    let bus = InMemoryBus::new();
+    let mut server = bus.attach();
+    let mut data = valve_node(
+        r#"
+        timeout = 0.1
+        safe_values = { valve_target = 0 }
+        "#,
+    );
+    data.node_id = 2;
+    data.frequency = 0;
+    let mut emulator = Emulator::new(data, bus.attach());
+    let handle = emulator.handle();
+    emulator.run_until(Instant::now() + Duration::from_millis(20));
+    while receive(&mut server, TIMEOUT).is_some() {}
+
+    let heartbeat = |counter| CanMessage::HeartbeatReq {
+        payload: payloads::HeartbeatPayload { counter },
+    };
+    socket_manager::send_frame(&mut server, make_message_id(2, 1), heartbeat(1)).unwrap();
+    emulator.run_until(Instant::now() + Duration::from_millis(150));
+    while receive(&mut server, TIMEOUT).is_some() {}
+    assert_eq!(
+        handle.parameter_value("valve_target"),
+        Some(payloads::CanDataValue::UInt8(0))
+    );
+
+    // Another node's heartbeat is answered, but the server is still gone.
+    socket_manager::send_frame(&mut server, make_message_id(2, 5), heartbeat(1)).unwrap();
+    emulator.run_until(Instant::now() + Duration::from_millis(20));
+    let mut messages = Vec::new();
+    while let Some(msg) = receive(&mut server, TIMEOUT) {
+        messages.push(msg);
+    }
+    assert!(matches!(
+        messages.as_slice(),
+        [CanMessage::HeartbeatRes { .. }]
+    ));
+
+    socket_manager::send_frame(&mut server, make_message_id(2, 1), heartbeat(2)).unwrap();
+    emulator.run_until(Instant::now() + Duration::from_millis(20));
+    assert!(matches!(
+        receive(&mut server, TIMEOUT),
+        Some(CanMessage::HeartbeatRes { .. })
+    ));
+    assert!(matches!(
+        receive(&mut server, TIMEOUT),
+        Some(CanMessage::InfoStatus { .. })
+    ));
+}